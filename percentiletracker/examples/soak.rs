@@ -0,0 +1,33 @@
+//! Runs the soak harness and prints its JSON report to stdout.
+//!
+//! Usage: `cargo run --release --features soak --example soak -- [seconds] [seed]`
+//!
+//! The process exits with a non-zero status if any invariant violation was observed.
+
+use std::time::Duration;
+
+use percentiletracker::soak::{self, SoakConfig};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let seconds = args
+        .next()
+        .map(|arg| arg.parse().expect("seconds must be an integer"))
+        .unwrap_or(60);
+    let seed = args
+        .next()
+        .map(|arg| arg.parse().expect("seed must be an integer"))
+        .unwrap_or(42);
+
+    let config = SoakConfig {
+        seed,
+        duration: Duration::from_secs(seconds),
+        ..SoakConfig::default()
+    };
+    let report = soak::run(&config);
+    println!("{}", report.to_json());
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,136 @@
+//! Reporting how far a percentile may be from the exact answer.
+//!
+//! A plain [`PercentileTracker`] is exact, but sampling, a full reservoir and every sketch
+//! backend trade exactness for speed or memory. [`ErrorBound`] describes the resulting
+//! error in the terms each backend can actually promise, so a dashboard can label a p99 as
+//! exact or as carrying a ± error.
+
+use std::fmt;
+
+use crate::PercentileTracker;
+
+/// How far a reported percentile may be from the exact answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorBound {
+    /// Reported values are exact.
+    Exact,
+
+    /// The rank of a reported value may be off by about this fraction of the number of
+    /// values, e.g. 0.01 means a reported p99 lies somewhere between p98 and p100.
+    Rank(f64),
+
+    /// A reported value may be off by this fraction of the exact value at that rank, e.g.
+    /// 0.01 means a reported 200ms stands for something between 198ms and 202ms.
+    Relative(f64),
+}
+
+impl ErrorBound {
+    /// Returns true if reported values are exact.
+    pub fn is_exact(&self) -> bool {
+        *self == ErrorBound::Exact
+    }
+}
+
+impl fmt::Display for ErrorBound {
+    /// Formats the bound as a dashboard annotation, e.g. `exact` or `±0.50% rank`. The
+    /// percentage has 2 decimal places unless a precision is given, as in `{:.1}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(2);
+        match self {
+            ErrorBound::Exact => f.write_str("exact"),
+            ErrorBound::Rank(error) => write!(f, "±{:.*}% rank", precision, error * 100.0),
+            ErrorBound::Relative(error) => write!(f, "±{:.*}%", precision, error * 100.0),
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns true if the stored values are every value that was kept by the accepted
+    /// range, so percentiles are exact.
+    pub fn is_exact(&self) -> bool {
+        self.error_bound().is_exact()
+    }
+
+    /// Returns how far the tracked percentile may be from the exact answer.
+    ///
+    /// A tracker is exact until sampling drops a value, its reservoir overflows, or it
+    /// downsamples to fit a memory budget. From then
+    /// on the stored values are a uniform sample of `k` values, and the rank of the tracked
+    /// percentile `p` has a standard error of `sqrt(p * (1 - p) / k)` as a fraction of the
+    /// stream, which is what this reports.
+    ///
+    /// ```
+    /// use percentiletracker::{ErrorBound, PercentileTracker};
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .percentile(90)
+    ///     .reservoir(10_000)
+    ///     .build()
+    ///     .unwrap();
+    /// for value in 0..10_000 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.error_bound(), ErrorBound::Exact);
+    /// tracker.insert(10_000);
+    /// let ErrorBound::Rank(error) = tracker.error_bound() else {
+    ///     panic!("a full reservoir has rank error");
+    /// };
+    /// assert!((error - 0.003).abs() < 1e-9);
+    /// assert_eq!(tracker.error_bound().to_string(), "±0.30% rank");
+    /// ```
+    pub fn error_bound(&self) -> ErrorBound {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return sketch.error_bound();
+        }
+        let sampled = self
+            .sampler
+            .as_ref()
+            .is_some_and(|sampler| sampler.dropped > 0);
+        let overflowed = self
+            .reservoir
+            .as_ref()
+            .is_some_and(|reservoir| reservoir.seen > reservoir.capacity as u64);
+        let downsampled = self.downsampling_factor() > 1;
+        if !sampled && !overflowed && !downsampled {
+            return ErrorBound::Exact;
+        }
+        let p = self.percentile as f64 / 100.0;
+        let k = self.count().max(1) as f64;
+        ErrorBound::Rank((p * (1.0 - p) / k).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sampling;
+
+    #[test]
+    fn test_sampling_error_bound() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .sampling(Sampling::Every(2))
+            .build()
+            .unwrap();
+        tracker.insert(1i64);
+        assert!(tracker.is_exact());
+        tracker.insert(2);
+        assert!(!tracker.is_exact());
+        for value in 0..198 {
+            tracker.insert(value);
+        }
+        assert_eq!(format!("{:.1}", tracker.error_bound()), "±5.0% rank");
+
+        let mut exact = PercentileTracker::new(99);
+        for value in 0..1_000u32 {
+            exact.insert(value);
+        }
+        assert!(exact.is_exact());
+        assert_eq!(exact.error_bound().to_string(), "exact");
+        assert_eq!(ErrorBound::Relative(0.01).to_string(), "±1.00%");
+    }
+}
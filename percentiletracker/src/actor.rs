@@ -0,0 +1,198 @@
+//! Ingesting values through a channel into a tracker owned by its own thread.
+//!
+//! [`PercentileTracker::spawn_actor`] moves a tracker onto a dedicated thread. Producers get
+//! a plain [`Sender`], so recording a value is a single channel send that never waits for a
+//! lock or for rebalancing. Queries go through an [`ActorHandle`], which runs them on the
+//! actor thread and waits for the answer. While no values arrive, the actor rebalances, so
+//! queries usually find nothing left to do.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::PercentileTracker;
+
+/// How long the actor waits for a value before checking for queries again.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A query to run on the actor thread.
+type Query<T> = Box<dyn FnOnce(&mut PercentileTracker<T>) + Send>;
+
+/// A handle for querying a tracker owned by an actor thread.
+///
+/// Handles are cheap to clone. Every value sent before a query, from any thread, is in the
+/// tracker when the query runs. The actor stops once every handle has been dropped, after
+/// which further sends from producers fail.
+///
+/// ```
+/// use percentiletracker::PercentileTracker;
+///
+/// let (sender, handle) = PercentileTracker::<u64>::new(99).spawn_actor();
+/// for latency_us in 0..1_000 {
+///     sender.send(latency_us).unwrap();
+/// }
+/// assert_eq!(handle.get_percentile(), Some(990));
+/// assert_eq!(handle.query(|tracker| tracker.max()), Some(Some(999)));
+/// ```
+pub struct ActorHandle<T>
+where
+    T: Ord,
+{
+    queries: Sender<Query<T>>,
+}
+
+impl<T> ActorHandle<T>
+where
+    T: Ord + Send + 'static,
+{
+    /// Runs a function on the tracker on the actor thread, and returns its result.
+    ///
+    /// Returns `None` if the actor has stopped, which only happens if an earlier query
+    /// panicked.
+    pub fn query<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut PercentileTracker<T>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, answer) = mpsc::channel();
+        let query: Query<T> = Box::new(move |tracker| {
+            // The caller may have given up waiting, which is fine
+            let _ = reply.send(f(tracker));
+        });
+        self.queries.send(query).ok()?;
+        answer.recv().ok()
+    }
+
+    /// Retrieves the current target percentile value, or `None` if the tracker is empty or
+    /// the actor has stopped.
+    pub fn get_percentile(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.query(|tracker| (!tracker.is_empty()).then(|| tracker.get_percentile()))
+            .flatten()
+    }
+
+    /// Returns the number of values in the tracker, or 0 if the actor has stopped.
+    pub fn len(&self) -> usize {
+        self.query(|tracker| tracker.len()).unwrap_or(0)
+    }
+
+    /// Returns true if the tracker holds no values or the actor has stopped.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for ActorHandle<T>
+where
+    T: Ord,
+{
+    /// Returns another handle to the same actor.
+    fn clone(&self) -> Self {
+        ActorHandle {
+            queries: self.queries.clone(),
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord + Send + 'static,
+{
+    /// Moves the tracker onto its own thread, returning a sender for values and a handle for
+    /// queries.
+    ///
+    /// See [`ActorHandle`].
+    ///
+    /// # Panics
+    /// Panics if the actor thread can't be spawned.
+    pub fn spawn_actor(self) -> (Sender<T>, ActorHandle<T>) {
+        let (sender, values) = mpsc::channel();
+        let (queries, requests) = mpsc::channel();
+        thread::Builder::new()
+            .name("percentile-actor".to_string())
+            .spawn(move || self.run_actor(&values, &requests))
+            .expect("Failed to spawn the actor thread");
+        (sender, ActorHandle { queries })
+    }
+
+    /// The actor loop: insert values as they arrive, answer queries, and rebalance while idle.
+    fn run_actor(mut self, values: &Receiver<T>, requests: &Receiver<Query<T>>) {
+        let mut producers_left = true;
+        loop {
+            if producers_left {
+                match values.recv_timeout(POLL_INTERVAL) {
+                    Ok(value) => self.insert(value),
+                    Err(RecvTimeoutError::Timeout) => self.rebalance(),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        producers_left = false;
+                        self.rebalance();
+                    }
+                }
+            }
+            let query = if producers_left {
+                match requests.try_recv() {
+                    Ok(query) => query,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            } else {
+                // Nothing else can arrive, so wait for queries alone
+                match requests.recv() {
+                    Ok(query) => query,
+                    Err(_) => return,
+                }
+            };
+            // Values sent before the query are already waiting in the channel
+            for value in values.try_iter() {
+                self.insert(value);
+            }
+            query(&mut self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actor_sees_every_sent_value() {
+        let (sender, handle) = PercentileTracker::new(90).spawn_actor();
+        assert!(handle.is_empty());
+        assert_eq!(handle.get_percentile(), None);
+        thread::scope(|s| {
+            for worker in 0..4u64 {
+                let sender = sender.clone();
+                let handle = handle.clone();
+                s.spawn(move || {
+                    for i in 0..500 {
+                        sender.send(i * 4 + worker).unwrap();
+                        if i % 100 == 99 {
+                            // This thread's own sends are always visible to its queries
+                            assert!(handle.len() > i as usize);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(handle.len(), 2_000);
+        assert_eq!(handle.get_percentile(), Some(1_800));
+
+        // Queries still work after every producer is gone
+        drop(sender);
+        assert_eq!(handle.query(|tracker| tracker.min()), Some(Some(0)));
+    }
+
+    #[test]
+    fn test_actor_stops_with_handles() {
+        let (sender, handle) = PercentileTracker::<u8>::new(50).spawn_actor();
+        sender.send(1).unwrap();
+        drop(handle);
+        // The actor notices the handles are gone at its next poll, and drops the receiver
+        while sender.send(2).is_ok() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Callbacks fired when the tracked percentile crosses a threshold.
+//!
+//! Alerts are evaluated at the end of every rebalance, which is when the tracked percentile
+//! can change: on the next query by default, or on every insert with
+//! [`amortized_rebalancing`](PercentileTrackerBuilder::amortized_rebalancing). A service
+//! that already reports its percentile therefore gets "call me when p99 exceeds 500ms"
+//! without polling from a separate task.
+
+use std::cell::Cell;
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// Which way the tracked percentile crossed an alert's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossing {
+    /// The percentile rose above the alert's trigger value.
+    Above,
+
+    /// The percentile fell back to or below the alert's rearm value.
+    Below,
+}
+
+/// A crossing reported to an alert callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlertEvent<'a, T> {
+    /// Which way the percentile crossed.
+    pub crossing: Crossing,
+
+    /// The tracked percentile.
+    pub percentile: usize,
+
+    /// The value at the tracked percentile that caused the crossing.
+    pub value: &'a T,
+}
+
+/// A callback fired when an alert's threshold is crossed.
+type AlertCallback<T> = Box<dyn Fn(&AlertEvent<'_, T>) + Send + Sync>;
+
+/// A registered alert and whether it is currently firing.
+pub(crate) struct Alert<T> {
+    /// The value the percentile must rise above to fire.
+    trigger: T,
+
+    /// The value the percentile must fall to before the alert can fire again.
+    rearm: T,
+
+    /// Called on each crossing.
+    callback: AlertCallback<T>,
+
+    /// Whether the percentile has risen above the trigger without falling back to the
+    /// rearm value since.
+    firing: Cell<bool>,
+}
+
+impl<T> Alert<T>
+where
+    T: Ord,
+{
+    /// Returns true if the rearm value is above the trigger, which would never rearm.
+    pub(crate) fn is_inverted(&self) -> bool {
+        self.rearm > self.trigger
+    }
+
+    /// Fires the callback if the percentile crossed the trigger or the rearm value.
+    fn check(&self, percentile: usize, value: &T) {
+        let crossing = if !self.firing.get() && *value > self.trigger {
+            Crossing::Above
+        } else if self.firing.get() && *value <= self.rearm {
+            Crossing::Below
+        } else {
+            return;
+        };
+        self.firing.set(crossing == Crossing::Above);
+        (self.callback)(&AlertEvent {
+            crossing,
+            percentile,
+            value,
+        });
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns how many alerts are currently firing, i.e. the tracked percentile rose above
+    /// their trigger and hasn't fallen back to their rearm value.
+    pub fn firing_alerts(&self) -> usize {
+        self.alerts
+            .iter()
+            .filter(|alert| alert.firing.get())
+            .count()
+    }
+
+    /// Checks every alert against the value at the tracked percentile after a rebalance.
+    pub(crate) fn check_alerts(&self, value: &T) {
+        for alert in &self.alerts {
+            alert.check(self.percentile, value);
+        }
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Registers a callback fired when the tracked percentile rises above `trigger`, and
+    /// again when it falls back to `rearm` or below.
+    ///
+    /// Setting `rearm` below `trigger` adds hysteresis, so a percentile hovering around the
+    /// trigger doesn't fire on every rebalance. Use the same value for both to fire on every
+    /// crossing.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use percentiletracker::{Crossing, PercentileTracker};
+    ///
+    /// let crossings = Arc::new(Mutex::new(Vec::new()));
+    /// let log = Arc::clone(&crossings);
+    /// let mut latencies = PercentileTracker::<u64>::builder()
+    ///     .percentile(99)
+    ///     .alert(500, 400, move |event| {
+    ///         log.lock().unwrap().push((event.crossing, *event.value))
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// for ms in [100, 900, 450] {
+    ///     latencies.insert(ms);
+    ///     latencies.get_percentile();
+    /// }
+    /// assert_eq!(*crossings.lock().unwrap(), vec![(Crossing::Above, 900)]);
+    /// assert_eq!(latencies.firing_alerts(), 1);
+    /// ```
+    pub fn alert<F>(mut self, trigger: T, rearm: T, callback: F) -> Self
+    where
+        F: Fn(&AlertEvent<'_, T>) + Send + Sync + 'static,
+    {
+        self.alerts.push(Alert {
+            trigger,
+            rearm,
+            callback: Box::new(callback),
+            firing: Cell::new(false),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildError;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_alert_hysteresis() {
+        let crossings = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&crossings);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(4)
+            .amortized_rebalancing()
+            .alert(100, 80, move |event| {
+                log.lock()
+                    .unwrap()
+                    .push((event.crossing, *event.value, event.percentile))
+            })
+            .build()
+            .unwrap();
+        // The median climbs past 100, hovers between the thresholds, then falls below 80
+        for value in [90, 110, 120, 95, 130, 85, 60, 50, 40, 30, 20, 10, 1] {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            *crossings.lock().unwrap(),
+            vec![(Crossing::Above, 110, 50), (Crossing::Below, 60, 50)]
+        );
+        assert_eq!(tracker.firing_alerts(), 0);
+    }
+
+    #[test]
+    fn test_alerts_wait_for_rebalance() {
+        let fired = Arc::new(Mutex::new(0));
+        let count = Arc::clone(&fired);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .alert(10, 10, move |_| *count.lock().unwrap() += 1)
+            .build()
+            .unwrap();
+        for value in 0..100i64 {
+            tracker.insert(value);
+        }
+        assert_eq!(*fired.lock().unwrap(), 0);
+        assert_eq!(tracker.get_percentile(), 90);
+        assert_eq!(*fired.lock().unwrap(), 1);
+        tracker.get_percentile();
+        assert_eq!(*fired.lock().unwrap(), 1);
+
+        assert_eq!(
+            PercentileTracker::<u8>::builder()
+                .alert(10, 20, |_| {})
+                .build()
+                .err(),
+            Some(BuildError::InvertedAlert)
+        );
+    }
+}
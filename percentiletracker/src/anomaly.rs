@@ -0,0 +1,180 @@
+//! Flagging inserted values that are unusually large for the stream so far.
+//!
+//! [`PercentileTracker::insert_and_check`] compares each value with a percentile of the
+//! values inserted before it, so the threshold calibrates itself to whatever the stream
+//! normally looks like instead of being a hand-tuned constant.
+
+use crate::{target_rank, BuildError, PercentileTracker, PercentileTrackerBuilder};
+
+/// The number of values a tracker needs before it flags anomalies, unless configured.
+pub const DEFAULT_ANOMALY_WARM_UP: usize = 100;
+
+/// The result of checking an inserted value with
+/// [`insert_and_check`](PercentileTracker::insert_and_check).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anomaly<T> {
+    /// The tracker held fewer values than the warm-up count, so the value wasn't checked.
+    WarmingUp,
+
+    /// The value was at or below the threshold.
+    Normal,
+
+    /// The value exceeded the threshold percentile of the values inserted before it.
+    Exceeds {
+        /// The value at the threshold percentile before the value was inserted.
+        threshold: T,
+    },
+}
+
+impl<T> Anomaly<T> {
+    /// Returns true if the value exceeded the threshold.
+    pub fn is_anomalous(&self) -> bool {
+        matches!(self, Anomaly::Exceeds { .. })
+    }
+}
+
+/// Which percentile inserted values are checked against, and after how many values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AnomalyThreshold {
+    /// The threshold percentile, or `None` to use the tracked percentile.
+    pub(crate) percentile: Option<usize>,
+
+    /// The number of values needed before values are checked.
+    pub(crate) warm_up: usize,
+}
+
+impl Default for AnomalyThreshold {
+    fn default() -> Self {
+        AnomalyThreshold {
+            percentile: None,
+            warm_up: DEFAULT_ANOMALY_WARM_UP,
+        }
+    }
+}
+
+impl AnomalyThreshold {
+    /// Checks that the threshold percentile, if set, is between 1 and 99 inclusive.
+    pub(crate) fn validate(&self) -> Result<(), BuildError> {
+        match self.percentile {
+            Some(percentile) if !(1..=99).contains(&percentile) => {
+                Err(BuildError::InvalidPercentile(percentile))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Inserts a value, and reports whether it exceeds the threshold percentile of the
+    /// values inserted before it.
+    ///
+    /// The threshold is the tracked percentile unless the builder set another with
+    /// [`anomaly_threshold`](PercentileTrackerBuilder::anomaly_threshold), and values are
+    /// only checked once the tracker holds the warm-up count, [`DEFAULT_ANOMALY_WARM_UP`]
+    /// by default. The value is inserted either way, so the threshold keeps adapting.
+    ///
+    /// ```
+    /// use percentiletracker::{Anomaly, PercentileTracker};
+    ///
+    /// let mut latencies = PercentileTracker::<u64>::builder()
+    ///     .anomaly_threshold(99, 50)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(latencies.insert_and_check(500), Anomaly::WarmingUp);
+    /// for ms in 1..=100 {
+    ///     latencies.insert(ms);
+    /// }
+    /// assert_eq!(latencies.insert_and_check(40), Anomaly::Normal);
+    /// assert_eq!(
+    ///     latencies.insert_and_check(250),
+    ///     Anomaly::Exceeds { threshold: 100 }
+    /// );
+    /// ```
+    pub fn insert_and_check(&mut self, value: T) -> Anomaly<T> {
+        let anomaly = if self.count() < self.anomaly_threshold.warm_up.max(1) {
+            Anomaly::WarmingUp
+        } else {
+            let threshold = match self.anomaly_threshold.percentile {
+                Some(percentile) if percentile != self.percentile => {
+                    self.select_rank(target_rank(percentile, self.count()))
+                }
+                _ => self.get_percentile(),
+            };
+            if value > threshold {
+                Anomaly::Exceeds { threshold }
+            } else {
+                Anomaly::Normal
+            }
+        };
+        self.insert(value);
+        anomaly
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Sets the percentile (1-99) that [`insert_and_check`](PercentileTracker::insert_and_check)
+    /// compares values with, and the number of values needed before it checks any.
+    pub fn anomaly_threshold(mut self, percentile: usize, warm_up: usize) -> Self {
+        self.anomaly_threshold = AnomalyThreshold {
+            percentile: Some(percentile),
+            warm_up,
+        };
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_threshold() {
+        let mut tracker = PercentileTracker::new(90);
+        for value in 0..DEFAULT_ANOMALY_WARM_UP as i64 {
+            assert_eq!(tracker.insert_and_check(value * 10), Anomaly::WarmingUp);
+        }
+        assert_eq!(tracker.insert_and_check(900), Anomaly::Normal);
+        let anomaly = tracker.insert_and_check(901);
+        assert!(anomaly.is_anomalous());
+        assert_eq!(anomaly, Anomaly::Exceeds { threshold: 900 });
+
+        // Raising the tracked percentile raises the default threshold
+        tracker.set_percentile(99).unwrap();
+        assert_eq!(tracker.insert_and_check(901), Anomaly::Normal);
+        assert_eq!(tracker.count(), 103);
+    }
+
+    #[test]
+    fn test_configured_threshold() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(4)
+            .anomaly_threshold(75, 0)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.insert_and_check(10), Anomaly::WarmingUp);
+        for value in [20, 30, 40] {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.insert_and_check(40), Anomaly::Normal);
+        assert_eq!(
+            tracker.insert_and_check(41),
+            Anomaly::Exceeds { threshold: 40 }
+        );
+        assert_eq!(tracker.get_percentile(), 40);
+
+        assert_eq!(
+            PercentileTracker::<u8>::builder()
+                .anomaly_threshold(100, 10)
+                .build()
+                .err(),
+            Some(BuildError::InvalidPercentile(100))
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::PercentileTracker;
+
+/// A tracker that stores each value behind an [`Arc`].
+///
+/// Most queries return values by cloning them, e.g. percentiles, ranks and snapshots. For
+/// large values, e.g. long strings or structs, storing an `Arc<T>` makes every one of those
+/// clones a reference count increment, and percentile queries return a cheap handle to the
+/// stored value. `Arc<T>` orders like `T`, so every query behaves exactly as it would on the
+/// values themselves.
+///
+/// ```
+/// use std::sync::Arc;
+/// use percentiletracker::ArcTracker;
+///
+/// let mut tracker = ArcTracker::new(50);
+/// for path in ["/a/long/path", "/b", "/another/long/path"] {
+///     tracker.insert_owned(path.repeat(100));
+/// }
+/// let median: Arc<String> = tracker.get_percentile();
+/// assert!(median.starts_with("/another/long/path"));
+/// ```
+pub type ArcTracker<T> = PercentileTracker<Arc<T>>;
+
+impl<T> PercentileTracker<Arc<T>>
+where
+    T: Ord,
+{
+    /// Moves a value into shared storage and inserts it.
+    pub fn insert_owned(&mut self, value: T) {
+        self.insert(Arc::new(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arc_tracker_shares_values() {
+        let mut tracker = ArcTracker::new(90);
+        let heavy = Arc::new(vec![u8::MAX; 1 << 20]);
+        tracker.insert(Arc::clone(&heavy));
+        for i in 0..200u8 {
+            tracker.insert_owned(vec![i]);
+        }
+        let p90 = tracker.get_percentile();
+        assert_eq!(*p90, vec![180]);
+
+        // The heavy value sorts last, and the tracker only holds references to it
+        let max = tracker.top_k(1).pop().unwrap();
+        assert!(Arc::ptr_eq(&max, &heavy));
+    }
+}
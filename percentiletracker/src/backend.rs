@@ -0,0 +1,201 @@
+use crate::{rank_for_percentile, ErrorBound, MergeError, PercentileTracker};
+
+/// The operations shared by every way of summarizing a stream of values.
+///
+/// [`PercentileTracker`] and the naive [`ExactTracker`](crate::ExactTracker) are the exact
+/// implementations. The sketches behind the `tdigest`, `ddsketch`, `gk`, `hdr` and `kll`
+/// features trade exactness for bounded memory, and implement this trait too so code that
+/// only needs to record values and query ranks can be written once and handed whichever
+/// backend suits the workload, e.g. an exact tracker in tests and benchmarks and a sketch in
+/// production.
+///
+/// A [`PercentileTracker`] can also be given sketch storage by
+/// [`PercentileTrackerBuilder`](crate::PercentileTrackerBuilder), with the `kll` feature, so
+/// the choice can be made in configuration while the calling code keeps the one front end.
+/// See [`Storage`](crate::Storage) for which queries a sketch answers.
+///
+/// Queries are [`value_at_percentile`](Self::value_at_percentile) and
+/// [`value_at_rank`](Self::value_at_rank), and the count is [`len`](Self::len).
+///
+/// ```
+/// use percentiletracker::{PercentileTracker, QuantileEstimator};
+///
+/// fn record_and_query<B: QuantileEstimator<u64>>(backend: &mut B) -> Option<u64> {
+///     for value in 1..=100 {
+///         backend.insert(value);
+///     }
+///     backend.value_at_percentile(90.0)
+/// }
+///
+/// assert_eq!(record_and_query(&mut PercentileTracker::new(50)), Some(91));
+/// ```
+pub trait QuantileEstimator<T> {
+    /// Adds a value.
+    fn insert(&mut self, value: T);
+
+    /// Returns the number of values summarized.
+    fn len(&self) -> usize;
+
+    /// Returns true if no values have been summarized.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value at a zero-based rank in the sorted order of all values, or `None`
+    /// if the rank is out of bounds. Approximate backends return an estimate.
+    fn value_at_rank(&self, rank: usize) -> Option<T>;
+
+    /// Returns the value at a percentile (0-100), or `None` if empty.
+    fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.len()))
+    }
+
+    /// Adds every value summarized by another backend of the same type.
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError>;
+
+    /// Returns how far reported values may be from the exact answer.
+    fn error_bound(&self) -> ErrorBound;
+
+    /// Returns true if reported values are exact.
+    fn is_exact(&self) -> bool {
+        self.error_bound().is_exact()
+    }
+}
+
+impl<T> QuantileEstimator<T> for PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    fn insert(&mut self, value: T) {
+        PercentileTracker::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        PercentileTracker::len(self)
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        PercentileTracker::value_at_rank(self, rank)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        PercentileTracker::merge(self, other)
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        PercentileTracker::error_bound(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExactTracker;
+
+    /// Inserts the same shuffled values into two halves of a backend, merges them, and
+    /// returns the merged median.
+    fn merged_median<T, B>(mut left: B, mut right: B, values: &[T]) -> Option<T>
+    where
+        T: Clone,
+        B: QuantileEstimator<T>,
+    {
+        for (i, value) in values.iter().enumerate() {
+            if i % 2 == 0 {
+                left.insert(value.clone());
+            } else {
+                right.insert(value.clone());
+            }
+        }
+        left.merge(&right).unwrap();
+        assert_eq!(left.len(), values.len());
+        assert!(left.value_at_rank(values.len()).is_none());
+        left.value_at_percentile(50.0)
+    }
+
+    #[test]
+    fn test_backends_are_interchangeable() {
+        let values: Vec<u64> = (0..10_000).map(|i| 1 + (i * 7919) % 10_000).collect();
+
+        let exact = merged_median(
+            PercentileTracker::new(90),
+            PercentileTracker::new(90),
+            &values,
+        );
+        assert_eq!(exact, Some(5001));
+        let naive = merged_median(ExactTracker::new(90), ExactTracker::new(50), &values);
+        assert_eq!(naive, exact);
+        assert!(QuantileEstimator::<u64>::is_exact(&PercentileTracker::new(
+            90
+        )));
+        assert!(QuantileEstimator::<u64>::is_empty(&PercentileTracker::new(
+            90
+        )));
+
+        #[cfg(feature = "hdr")]
+        {
+            use crate::HdrHistogram;
+            let median = merged_median(
+                HdrHistogram::new(90, 100_000, 3),
+                HdrHistogram::new(90, 100_000, 3),
+                &values,
+            );
+            assert!(median.unwrap().abs_diff(5001) <= 5);
+            assert_eq!(
+                HdrHistogram::new(90, 100_000, 3).error_bound(),
+                ErrorBound::Relative(0.001)
+            );
+            let mut left = HdrHistogram::new(90, 100_000, 3);
+            assert_eq!(
+                QuantileEstimator::merge(&mut left, &HdrHistogram::new(90, 100_000, 2)),
+                Err(MergeError::IncompatibleParameters)
+            );
+        }
+
+        #[cfg(feature = "gk")]
+        {
+            use crate::GkSummary;
+            let median = merged_median(GkSummary::new(90), GkSummary::new(90), &values);
+            assert!(median.unwrap().abs_diff(5001) <= 10);
+            assert_eq!(
+                GkSummary::<u64>::new(90).error_bound(),
+                ErrorBound::Rank(0.001)
+            );
+        }
+
+        #[cfg(feature = "kll")]
+        {
+            use crate::{KllSketch, Storage};
+            let median = merged_median(KllSketch::new(90), KllSketch::new(90), &values);
+            assert!(median.unwrap().abs_diff(5001) <= 300);
+            let kll_tracker = || {
+                PercentileTracker::builder()
+                    .percentile(90)
+                    .storage(Storage::Kll { k: 200 })
+                    .build()
+                    .unwrap()
+            };
+            let median = merged_median(kll_tracker(), kll_tracker(), &values);
+            assert!(median.unwrap().abs_diff(5001) <= 300);
+        }
+
+        #[cfg(any(feature = "tdigest", feature = "ddsketch"))]
+        let floats: Vec<f64> = values.iter().map(|&v| v as f64).collect();
+        #[cfg(feature = "tdigest")]
+        {
+            use crate::TDigest;
+            let median = merged_median(TDigest::new(90), TDigest::new(90), &floats);
+            assert!((median.unwrap() - 5001.0).abs() <= 50.0);
+            assert!(!TDigest::new(90).is_exact());
+        }
+        #[cfg(feature = "ddsketch")]
+        {
+            use crate::DDSketch;
+            let median = merged_median(DDSketch::new(90), DDSketch::new(90), &floats);
+            assert!((median.unwrap() - 5001.0).abs() <= 5001.0 * 0.01);
+            assert_eq!(DDSketch::new(90).error_bound(), ErrorBound::Relative(0.01));
+        }
+    }
+}
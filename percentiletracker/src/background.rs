@@ -0,0 +1,193 @@
+//! Rebalancing on a background thread.
+//!
+//! A [`PercentileTracker`] defers splitting and sorting to the next query, so after a burst
+//! of inserts the first query pays for all of it. [`BackgroundTracker`] moves that work to a
+//! worker thread that rebalances whenever new values arrive, so by the time a query runs
+//! there is usually nothing left to do.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::PercentileTracker;
+
+/// What the worker should do next, protected by `Shared::state`.
+#[derive(Default)]
+struct State {
+    /// Values were inserted since the worker last rebalanced.
+    dirty: bool,
+
+    /// The owning `BackgroundTracker` was dropped.
+    shutdown: bool,
+}
+
+struct Shared<T>
+where
+    T: Ord,
+{
+    tracker: Mutex<PercentileTracker<T>>,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl<T> Shared<T>
+where
+    T: Ord,
+{
+    fn tracker(&self) -> MutexGuard<'_, PercentileTracker<T>> {
+        self.tracker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A thread-safe tracker whose rebalancing runs on a dedicated worker thread.
+///
+/// Inserts and queries take `&self` and lock the tracker briefly, so the tracker can be
+/// shared between threads behind an `Arc`. The worker is stopped when the tracker is dropped.
+///
+/// ```
+/// use percentiletracker::{BackgroundTracker, PercentileTracker};
+///
+/// let tracker = BackgroundTracker::new(PercentileTracker::<u64>::new(99));
+/// for latency_us in 0..1_000 {
+///     tracker.insert(latency_us);
+/// }
+/// assert_eq!(tracker.get_percentile(), 990);
+/// ```
+pub struct BackgroundTracker<T>
+where
+    T: Ord,
+{
+    shared: Arc<Shared<T>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T> BackgroundTracker<T>
+where
+    T: Ord + Send + 'static,
+{
+    /// Moves a tracker behind a lock and starts its rebalancing worker.
+    ///
+    /// # Panics
+    /// Panics if the worker thread can't be spawned.
+    pub fn new(tracker: PercentileTracker<T>) -> Self {
+        let shared = Arc::new(Shared {
+            tracker: Mutex::new(tracker),
+            state: Mutex::new(State::default()),
+            wake: Condvar::new(),
+        });
+        let worker_shared = Arc::clone(&shared);
+        let worker = thread::Builder::new()
+            .name("percentile-rebalance".to_string())
+            .spawn(move || Self::run(&worker_shared))
+            .expect("Failed to spawn the rebalancing thread");
+        BackgroundTracker {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// The worker loop: wait for inserts, then rebalance.
+    fn run(shared: &Shared<T>) {
+        loop {
+            {
+                let mut state = shared
+                    .wake
+                    .wait_while(shared.state(), |state| !state.dirty && !state.shutdown)
+                    .unwrap_or_else(|e| e.into_inner());
+                if state.shutdown {
+                    return;
+                }
+                state.dirty = false;
+            }
+            shared.tracker().rebalance();
+        }
+    }
+}
+
+impl<T> BackgroundTracker<T>
+where
+    T: Ord,
+{
+    /// Inserts a value and wakes the worker to rebalance.
+    pub fn insert(&self, value: T) {
+        self.shared.tracker().insert(value);
+        let mut state = self.shared.state();
+        if !state.dirty {
+            state.dirty = true;
+            self.shared.wake.notify_one();
+        }
+    }
+
+    /// Retrieves the current target percentile value.
+    ///
+    /// This only rebalances itself if the worker hasn't caught up with the latest inserts.
+    pub fn get_percentile(&self) -> T
+    where
+        T: Clone,
+    {
+        self.shared.tracker().get_percentile()
+    }
+
+    /// Locks the tracker for any other query.
+    ///
+    /// The worker and inserts are blocked while the guard is held, so keep it short.
+    pub fn lock(&self) -> MutexGuard<'_, PercentileTracker<T>> {
+        self.shared.tracker()
+    }
+}
+
+impl<T> Drop for BackgroundTracker<T>
+where
+    T: Ord,
+{
+    fn drop(&mut self) {
+        self.shared.state().shutdown = true;
+        self.shared.wake.notify_one();
+        if let Some(worker) = self.worker.take() {
+            // A panic on the worker has already been reported, so there is nothing to add
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_background_rebalancing() {
+        let tracker = Arc::new(BackgroundTracker::new(
+            PercentileTracker::builder()
+                .percentile(90)
+                .max_bucket_size(16)
+                .build()
+                .unwrap(),
+        ));
+        let writers: Vec<_> = (0..4i64)
+            .map(|writer| {
+                let tracker = Arc::clone(&tracker);
+                thread::spawn(move || {
+                    for i in 0..2_500 {
+                        tracker.insert(writer * 2_500 + i);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // The worker catches up without any query being made
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while tracker.lock().needs_rebalancing.get() {
+            assert!(Instant::now() < deadline, "The worker never rebalanced");
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(tracker.get_percentile(), 9_000);
+        assert_eq!(tracker.lock().count(), 10_000);
+    }
+}
@@ -0,0 +1,184 @@
+//! Prints percentiles of newline-delimited numbers read from stdin or files.
+//!
+//! Usage: `ptile [-p PERCENTILES] [FILE...]`
+//!
+//! `PERCENTILES` is a comma-separated list (0-100), defaulting to `50,90,99,99.9`. Input is
+//! streamed into a tracker line by line, so files of any size can be read. Blank lines are
+//! skipped, and any other line that isn't a number is an error.
+//!
+//! Build with `cargo install --path percentiletracker --features cli`.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::ExitCode;
+
+use percentiletracker::{Numeric, PercentileTracker, DELTA_PERCENTILES};
+
+const USAGE: &str = "Usage: ptile [-p PERCENTILES] [FILE...]";
+
+/// An `f64` ordered with `total_cmp`, so any parsed number can be tracked.
+#[derive(Debug, Clone, Copy)]
+struct Number(f64);
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Numeric for Number {
+    fn to_f64(self) -> f64 {
+        self.0
+    }
+}
+
+/// The parsed command line.
+#[derive(Debug, PartialEq)]
+struct Args {
+    percentiles: Vec<f64>,
+    files: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut percentiles = DELTA_PERCENTILES.to_vec();
+    let mut files = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "--percentiles" => {
+                let list = args.next().ok_or("-p needs a list of percentiles")?;
+                percentiles = parse_percentiles(&list)?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => files.push(arg),
+        }
+    }
+    Ok(Args { percentiles, files })
+}
+
+fn parse_percentiles(list: &str) -> Result<Vec<f64>, String> {
+    list.split(',')
+        .map(|item| match item.trim().parse::<f64>() {
+            Ok(percentile) if (0.0..=100.0).contains(&percentile) => Ok(percentile),
+            _ => Err(format!("Invalid percentile '{}', expected 0-100", item)),
+        })
+        .collect()
+}
+
+/// Inserts every number in `input` into the tracker.
+fn read_numbers(
+    input: impl BufRead,
+    name: &str,
+    tracker: &mut PercentileTracker<Number>,
+) -> Result<(), String> {
+    for (line_number, line) in input.lines().enumerate() {
+        let line = line.map_err(|err| format!("{}: {}", name, err))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.parse::<f64>() {
+            Ok(value) if !value.is_nan() => tracker.insert(Number(value)),
+            _ => {
+                return Err(format!(
+                    "{}:{}: '{}' is not a number",
+                    name,
+                    line_number + 1,
+                    line
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1))?;
+    let mut tracker = PercentileTracker::new(50);
+    if args.files.is_empty() {
+        read_numbers(io::stdin().lock(), "<stdin>", &mut tracker)?;
+    }
+    for path in &args.files {
+        let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+        read_numbers(BufReader::new(file), path, &mut tracker)?;
+    }
+
+    let summary = tracker.summary_at(&args.percentiles);
+    let mut out = io::stdout().lock();
+    let mut print = |name: &str, value: Option<f64>| {
+        let value = value.map_or("-".to_string(), |value| value.to_string());
+        writeln!(out, "{}\t{}", name, value).map_err(|err| err.to_string())
+    };
+    print("count", Some(summary.count as f64))?;
+    print("min", summary.min.map(Number::to_f64))?;
+    print("mean", summary.mean)?;
+    for (percentile, value) in &summary.percentiles {
+        print(&format!("p{}", percentile), Some(value.to_f64()))?;
+    }
+    print("max", summary.max.map(Number::to_f64))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("ptile: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<Args, String> {
+        parse_args(list.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            args(&[]).unwrap(),
+            Args {
+                percentiles: DELTA_PERCENTILES.to_vec(),
+                files: Vec::new(),
+            }
+        );
+        assert_eq!(
+            args(&["a.txt", "-p", "25, 75", "b.txt"]).unwrap(),
+            Args {
+                percentiles: vec![25.0, 75.0],
+                files: vec!["a.txt".to_string(), "b.txt".to_string()],
+            }
+        );
+        assert!(args(&["-p"]).is_err());
+        assert!(args(&["-p", "101"]).is_err());
+    }
+
+    #[test]
+    fn test_read_numbers() {
+        let mut tracker = PercentileTracker::new(50);
+        read_numbers("3\n\n-1.5\n 2 \n".as_bytes(), "input", &mut tracker).unwrap();
+        assert_eq!(tracker.get_percentile().0, 2.0);
+        assert_eq!(
+            read_numbers("1\nten\n".as_bytes(), "input", &mut tracker),
+            Err("input:2: 'ten' is not a number".to_string())
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Keeping a tracker within a memory budget by downsampling.
+//!
+//! A tracker built with [`PercentileTrackerBuilder::memory_budget`] halves its stored values
+//! whenever they would outgrow the budget, keeping every second value in sorted order. Each
+//! kept value then stands for twice as many inserts, and later inserts are thinned by the
+//! same factor, so the stored values stay an even sample of the whole stream. Percentiles
+//! become estimates once the first halving happens, and
+//! [`PercentileTracker::downsampling_factor`] reports how many inserts each value stands for.
+
+use std::mem::size_of;
+
+use crate::{Bucket, PercentileTracker, PercentileTrackerBuilder};
+
+/// State for a tracker that downsamples to stay within a memory budget.
+#[derive(Debug, Clone)]
+pub(crate) struct Downsampler {
+    /// The most bytes the tracker may use.
+    pub(crate) max_bytes: usize,
+
+    /// The number of inserts each stored value stands for, a power of two.
+    factor: u64,
+
+    /// Number of values offered since the last halving.
+    offered: u64,
+
+    /// The stored count at which the size is measured again.
+    next_check: usize,
+}
+
+impl Downsampler {
+    pub(crate) fn new(max_bytes: usize) -> Self {
+        Downsampler {
+            max_bytes,
+            factor: 1,
+            offered: 0,
+            next_check: 0,
+        }
+    }
+
+    /// Counts an offered value, and returns true if it should be kept.
+    fn admit(&mut self) -> bool {
+        let offered = self.offered;
+        self.offered += 1;
+        offered % self.factor == 0
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the memory budget in bytes, or `None` if the tracker has none.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.downsampler
+            .as_ref()
+            .map(|downsampler| downsampler.max_bytes)
+    }
+
+    /// Returns the number of inserts each stored value stands for because of downsampling,
+    /// which is 1 until the tracker first outgrows its memory budget.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut latencies = PercentileTracker::<u64>::builder()
+    ///     .percentile(90)
+    ///     .memory_budget(16 * 1024)
+    ///     .build()
+    ///     .unwrap();
+    /// for ms in 0..100_000 {
+    ///     latencies.insert(ms % 1_000);
+    /// }
+    /// assert!(latencies.downsampling_factor() > 1);
+    /// assert!(latencies.memory_usage().len * 8 <= 16 * 1024);
+    /// assert!(latencies.get_percentile().abs_diff(900) <= 10);
+    /// ```
+    pub fn downsampling_factor(&self) -> u64 {
+        self.downsampler
+            .as_ref()
+            .map_or(1, |downsampler| downsampler.factor)
+    }
+
+    /// Decides whether the next inserted value is kept after downsampling.
+    pub(crate) fn admit_under_budget(&mut self) -> bool {
+        match &mut self.downsampler {
+            Some(downsampler) => downsampler.admit(),
+            None => true,
+        }
+    }
+
+    /// Halves the stored values until they fit within the memory budget, if there is one.
+    ///
+    /// Measuring the size walks the buckets, so it's only done once the stored count reaches
+    /// the point where the values added since the last measurement could have used up the
+    /// room that was left, assuming each takes at most two slots as vectors double. The
+    /// room shrinks geometrically as the tracker fills up, so there are O(log n)
+    /// measurements between halvings rather than one per insert.
+    pub(crate) fn enforce_budget(&mut self) {
+        let Some(downsampler) = &self.downsampler else {
+            return;
+        };
+        if self.count() < downsampler.next_check {
+            return;
+        }
+        let max_bytes = downsampler.max_bytes;
+        let mut bytes = self.stored_bytes();
+        while self.count() > 1 && bytes > max_bytes {
+            self.downsample();
+            bytes = self.stored_bytes();
+        }
+        let room = max_bytes.saturating_sub(bytes) / (2 * size_of::<T>()).max(1);
+        let next_check = self.count() + room.max(1);
+        if let Some(downsampler) = &mut self.downsampler {
+            downsampler.next_check = next_check;
+        }
+    }
+
+    /// Makes the next [`enforce_budget`](Self::enforce_budget) measure the size, e.g. after
+    /// the stored values were replaced.
+    pub(crate) fn remeasure_budget(&mut self) {
+        if let Some(downsampler) = &mut self.downsampler {
+            downsampler.next_check = 0;
+        }
+    }
+
+    /// Keeps every second stored value in sorted order and doubles the downsampling factor.
+    ///
+    /// Which half is kept alternates between halvings, so the rounding of ranks doesn't
+    /// drift in one direction.
+    fn downsample(&mut self) {
+        let downsampler = self
+            .downsampler
+            .as_mut()
+            .expect("Only trackers with a memory budget downsample");
+        let mut index = downsampler.factor.trailing_zeros() as usize;
+        downsampler.factor = downsampler.factor.saturating_mul(2);
+        downsampler.offered = 0;
+
+        let sort_values = self.sort_values;
+        let mut halved = Vec::new();
+        for mut bucket in std::mem::take(self.buckets.get_mut()) {
+            if !bucket.sorted {
+                sort_values(&mut bucket.values);
+            }
+            let mut kept: Vec<T> = bucket
+                .values
+                .into_iter()
+                .filter(|_| {
+                    index += 1;
+                    index % 2 == 1
+                })
+                .collect();
+            // Collecting in place keeps the old allocation, which would still count
+            kept.shrink_to_fit();
+            if !kept.is_empty() {
+                halved.push(Bucket::from_run(kept, true));
+            }
+        }
+        self.load_buckets(halved);
+    }
+}
+
+/// Returns the smallest memory budget for a tracker of `T`: the tracker itself and a bucket
+/// list and minimum index big enough for a couple of values, so that downsampling always
+/// leaves room to record more.
+pub(crate) fn min_memory_budget<T: Ord>() -> usize {
+    size_of::<PercentileTracker<T>>() + 4 * size_of::<Bucket<T>>() + 12 * size_of::<T>()
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Keeps the tracker within roughly `bytes` of memory by downsampling once it outgrows
+    /// them.
+    ///
+    /// The budget covers the tracker, its buckets and the minimum index. Copies of values
+    /// held by checkpoints, the undo journal, the history and the shadow oracle are bounded by
+    /// their own settings instead, since halving the stored values wouldn't shrink them.
+    /// [`build`](Self::build) fails if the budget can't hold the tracker and a few values.
+    ///
+    /// Downsampling resets the running mean and sum, and clears the undo journal, since they
+    /// described values that are no longer stored.
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuildError, ReadReplica, RefreshPolicy};
+
+    #[test]
+    fn test_downsampling_keeps_distribution() {
+        // Exactly enough for the first 1,000 values
+        let mut unbounded = PercentileTracker::new(75);
+        for value in 0..1_000u32 {
+            unbounded.insert(value);
+        }
+        let budget = unbounded.memory_usage().bytes;
+        let mut tracker = PercentileTracker::builder()
+            .percentile(75)
+            .memory_budget(budget)
+            .build()
+            .unwrap();
+        for value in 0..1_000u32 {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.downsampling_factor(), 1);
+        assert!(tracker.is_exact());
+        assert_eq!(tracker.get_percentile(), 750);
+
+        for value in 1_000..100_000u32 {
+            tracker.insert(value % 10_000);
+        }
+        assert_eq!(tracker.memory_budget(), Some(budget));
+        let factor = tracker.downsampling_factor();
+        assert!(factor > 1 && factor.is_power_of_two());
+        assert_eq!(tracker.sample_rate(), factor);
+        assert!(!tracker.is_exact());
+        assert!(tracker.len() <= 1_000);
+        assert!(tracker.memory_usage().bytes <= budget);
+        assert!(tracker.get_percentile().abs_diff(7_500) <= 100);
+        assert!(tracker.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_budget_holds_after_bulk_loads() {
+        let mut budgeted = PercentileTracker::builder()
+            .percentile(50)
+            .memory_budget(4 * 1024)
+            .build()
+            .unwrap();
+        let mut shard = PercentileTracker::new(50);
+        for value in 0..10_000u64 {
+            shard.insert(value);
+        }
+        budgeted.merge_all([&shard]).unwrap();
+        assert!(budgeted.downsampling_factor() > 1);
+        assert!(budgeted.memory_usage().bytes <= 4 * 1024);
+
+        let factor = budgeted.downsampling_factor();
+        budgeted.merge(&shard).unwrap();
+        assert!(budgeted.downsampling_factor() > factor);
+        assert!(budgeted.memory_usage().bytes <= 4 * 1024);
+        assert!(budgeted.get_percentile().abs_diff(5_000) <= 500);
+        assert!(budgeted.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_downsampling_keeps_replica_fresh() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .memory_budget(2 * 1024)
+            .build()
+            .unwrap();
+        let replica = ReadReplica::new(
+            &tracker,
+            RefreshPolicy {
+                max_age: None,
+                max_inserts: Some(100),
+            },
+        );
+        let mut refreshes = 0;
+        for value in 0..10_000u64 {
+            tracker.insert(value);
+            if replica.refresh_if_stale(&tracker) {
+                refreshes += 1;
+            }
+        }
+        // Downsampling shrinks the stored count, but inserts keep counting
+        assert!(tracker.downsampling_factor() > 1);
+        assert_eq!(refreshes, 100);
+    }
+
+    #[test]
+    fn test_budget_ignores_copies() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .memory_budget(4 * 1024)
+            .checkpoint_depth(4)
+            .undo_journal(10_000)
+            .build()
+            .unwrap();
+        for value in 0..2_000u64 {
+            tracker.insert(value);
+        }
+        tracker.checkpoint();
+        // The checkpoint and journal alone outgrow the budget, but don't drain the tracker
+        assert!(tracker.memory_usage().bytes > 4 * 1024);
+        let factor = tracker.downsampling_factor();
+        for value in 0..100_000u64 {
+            tracker.insert(value % 2_000);
+        }
+        assert!(tracker.len() > 100);
+        assert!(tracker.downsampling_factor() <= factor * 64);
+        assert!(tracker.get_percentile().abs_diff(1_000) <= 100);
+    }
+
+    #[test]
+    fn test_budget_measured_sparingly() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .memory_budget(64 * 1024)
+            .build()
+            .unwrap();
+        tracker.insert(1u64);
+        // Thousands of values fit, so the next measurement is far away
+        let next_check = tracker.downsampler.as_ref().unwrap().next_check;
+        assert!(next_check > 1_000);
+    }
+
+    #[test]
+    fn test_budget_too_small() {
+        assert_eq!(
+            PercentileTracker::<u64>::builder()
+                .memory_budget(16)
+                .build()
+                .err(),
+            Some(BuildError::InvalidMemoryBudget(16))
+        );
+        assert!(PercentileTracker::<u64>::builder()
+            .memory_budget(min_memory_budget::<u64>())
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,499 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::alert::Alert;
+use crate::anomaly::AnomalyThreshold;
+use crate::budget::{min_memory_budget, Downsampler};
+use crate::checkpoint::{Checkpoints, DEFAULT_CHECKPOINT_DEPTH};
+use crate::history::History;
+use crate::instrument::Instrumentation;
+use crate::journal::Journal;
+#[cfg(feature = "kll")]
+use crate::kll::{KllSketch, MIN_K};
+use crate::min_index::MinIndex;
+use crate::observer::Observers;
+use crate::range::RangeFilter;
+use crate::reservoir::{Reservoir, DEFAULT_RESERVOIR_SEED};
+use crate::rng::SplitMix64;
+use crate::sampling::{Sampler, Sampling};
+#[cfg(feature = "kll")]
+use crate::Storage;
+use crate::{PercentileTracker, QuantileMethod, Unit, MAX_BUCKET_SIZE};
+
+/// An error returned by [`PercentileTrackerBuilder::build`] when the configuration is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The percentile was outside of the supported 1-99 range.
+    InvalidPercentile(usize),
+
+    /// The maximum bucket size was zero.
+    InvalidBucketSize(usize),
+
+    /// The reservoir capacity was zero.
+    InvalidReservoirCapacity(usize),
+
+    /// The history was configured to keep no points.
+    InvalidHistoryCapacity(usize),
+
+    /// The checkpoint ring was configured to keep no checkpoints.
+    InvalidCheckpointDepth(usize),
+
+    /// The undo journal was configured to keep no inserts.
+    InvalidJournalCapacity(usize),
+
+    /// The accepted range starts above its end, so no value would be accepted.
+    EmptyRange,
+
+    /// The sampling rate was zero.
+    InvalidSamplingRate(u64),
+
+    /// The memory budget was too small to hold the tracker and a few values.
+    InvalidMemoryBudget(usize),
+
+    /// An alert's rearm value was above its trigger, so it could never rearm.
+    InvertedAlert,
+
+    /// The sketch size was below the minimum of 8.
+    InvalidSketchSize(usize),
+
+    /// The named option acts on the stored values, but the tracker keeps them in a sketch.
+    NeedsExactStorage(&'static str),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidPercentile(percentile) => write!(
+                f,
+                "Percentile must be between 1 and 99 inclusive, got {}",
+                percentile
+            ),
+            BuildError::InvalidBucketSize(size) => {
+                write!(f, "Maximum bucket size must be at least 1, got {}", size)
+            }
+            BuildError::InvalidReservoirCapacity(capacity) => {
+                write!(f, "Reservoir capacity must be at least 1, got {}", capacity)
+            }
+            BuildError::InvalidHistoryCapacity(capacity) => {
+                write!(f, "History must keep at least 1 point, got {}", capacity)
+            }
+            BuildError::InvalidCheckpointDepth(depth) => {
+                write!(f, "Checkpoint depth must be at least 1, got {}", depth)
+            }
+            BuildError::InvalidJournalCapacity(capacity) => {
+                write!(
+                    f,
+                    "Undo journal must keep at least 1 insert, got {}",
+                    capacity
+                )
+            }
+            BuildError::EmptyRange => f.write_str("The accepted range must not be empty"),
+            BuildError::InvalidSamplingRate(rate) => {
+                write!(f, "Sampling rate must be at least 1, got {}", rate)
+            }
+            BuildError::InvalidMemoryBudget(bytes) => write!(
+                f,
+                "Memory budget must fit the tracker and a few values, got {} bytes",
+                bytes
+            ),
+            BuildError::InvertedAlert => {
+                f.write_str("An alert's rearm value must not be above its trigger")
+            }
+            BuildError::InvalidSketchSize(k) => {
+                write!(f, "Sketch size must be at least 8, got {}", k)
+            }
+            BuildError::NeedsExactStorage(option) => {
+                write!(f, "The {} option needs exact storage", option)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// A builder for configuring a [`PercentileTracker`] before construction.
+///
+/// Every option has a sensible default, and the configuration is validated as a whole
+/// in [`build`](Self::build).
+///
+/// ```
+/// use percentiletracker::PercentileTracker;
+///
+/// let tracker = PercentileTracker::<i64>::builder()
+///     .percentile(99)
+///     .max_bucket_size(128)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct PercentileTrackerBuilder<T> {
+    /// The percentile to track (1-99). Defaults to the median.
+    percentile: usize,
+
+    /// The size above which the critical bucket is split.
+    max_bucket_size: usize,
+
+    /// The unit of the values that will be inserted.
+    unit: Unit,
+
+    /// How interpolated percentile queries estimate values.
+    quantile_method: QuantileMethod,
+
+    /// Which inserts to record, if the tracker should record only 1 in N.
+    pub(crate) sampling: Option<Sampling>,
+
+    /// The most bytes the tracker may use before downsampling, if limited.
+    pub(crate) memory_budget: Option<usize>,
+
+    /// The reservoir capacity, if the tracker should sample instead of keeping every value.
+    reservoir: Option<usize>,
+
+    /// The seed used to choose which values the reservoir keeps.
+    reservoir_seed: u64,
+
+    /// A replacement for `sort_unstable` when sorting buckets.
+    pub(crate) sort_values: Option<fn(&mut [T])>,
+
+    /// Copies values into a shadow oracle, if enabled.
+    #[cfg(feature = "shadow-oracle")]
+    pub(crate) oracle_clone: Option<fn(&T) -> T>,
+
+    /// Receives events about the tracker's internal work.
+    pub(crate) instrumentation: Option<Box<dyn Instrumentation>>,
+
+    /// Whether each insert does its share of the rebalancing work.
+    amortized_rebalancing: bool,
+
+    /// Copies bucket minimums into a flat index, if enabled.
+    pub(crate) clone_min: Option<fn(&T) -> T>,
+
+    /// The empty percentile time series, if enabled.
+    pub(crate) history: Option<History<T>>,
+
+    /// The number of checkpoints kept.
+    pub(crate) checkpoint_depth: usize,
+
+    /// The empty journal of recent inserts, if undo is enabled.
+    pub(crate) journal: Option<Journal<T>>,
+
+    /// The accepted range of inserted values, if any.
+    pub(crate) range_filter: Option<RangeFilter<T>>,
+
+    /// Callbacks run on every kept insert.
+    pub(crate) observers: Option<Observers<T>>,
+
+    /// The percentile and warm-up count used to flag anomalies.
+    pub(crate) anomaly_threshold: AnomalyThreshold,
+
+    /// Callbacks fired when the tracked percentile crosses a threshold.
+    pub(crate) alerts: Vec<Alert<T>>,
+
+    /// What the tracker keeps its values in.
+    #[cfg(feature = "kll")]
+    pub(crate) storage: Storage,
+
+    _marker: PhantomData<T>,
+}
+
+impl<T> Default for PercentileTrackerBuilder<T> {
+    fn default() -> Self {
+        PercentileTrackerBuilder {
+            percentile: 50,
+            max_bucket_size: MAX_BUCKET_SIZE,
+            unit: Unit::None,
+            quantile_method: QuantileMethod::default(),
+            sampling: None,
+            memory_budget: None,
+            reservoir: None,
+            reservoir_seed: DEFAULT_RESERVOIR_SEED,
+            #[cfg(feature = "shadow-oracle")]
+            oracle_clone: None,
+            instrumentation: None,
+            amortized_rebalancing: false,
+            sort_values: None,
+            clone_min: None,
+            history: None,
+            checkpoint_depth: DEFAULT_CHECKPOINT_DEPTH,
+            journal: None,
+            range_filter: None,
+            observers: None,
+            anomaly_threshold: AnomalyThreshold::default(),
+            alerts: Vec::new(),
+            #[cfg(feature = "kll")]
+            storage: Storage::Exact,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the percentile to track (1-99).
+    pub fn percentile(mut self, percentile: usize) -> Self {
+        self.percentile = percentile;
+        self
+    }
+
+    /// Sets the size above which the bucket containing the percentile is split.
+    ///
+    /// Smaller buckets make each query cheaper to sort but create more buckets to search on insert.
+    pub fn max_bucket_size(mut self, max_bucket_size: usize) -> Self {
+        self.max_bucket_size = max_bucket_size;
+        self
+    }
+
+    /// Sets the unit of the values that will be inserted, used when formatting reports.
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets the method used by the interpolated percentile queries, to match the definition
+    /// of a percentile used by another tool.
+    pub fn quantile_method(mut self, quantile_method: QuantileMethod) -> Self {
+        self.quantile_method = quantile_method;
+        self
+    }
+
+    /// Keeps a uniform random sample of at most `capacity` values instead of every value.
+    ///
+    /// Memory stays constant for unbounded streams, but percentiles become estimates once
+    /// more than `capacity` values have been inserted. See the `reservoir` module docs for
+    /// the accuracy trade-off.
+    pub fn reservoir(mut self, capacity: usize) -> Self {
+        self.reservoir = Some(capacity);
+        self
+    }
+
+    /// Sets the seed used to choose which values the reservoir keeps.
+    ///
+    /// Sampling is deterministic for a given seed and input, which keeps tests reproducible.
+    /// Shards that are later merged should use different seeds.
+    pub fn reservoir_seed(mut self, seed: u64) -> Self {
+        self.reservoir_seed = seed;
+        self
+    }
+
+    /// Rebalances on every insert instead of on the next query.
+    ///
+    /// By default, inserts only place the value in a bucket, and the next query splits and
+    /// sorts the bucket holding the percentile. After many inserts that can be a lot of work
+    /// for one query. With amortized rebalancing, each insert keeps that bucket split and
+    /// sorted, which costs at most one split and an insertion into `max_bucket_size` sorted
+    /// values. A query then does no sorting unless the percentile moved into a new bucket.
+    ///
+    /// This bounds query latency at the cost of total throughput, so it suits services that
+    /// query on a latency-sensitive path.
+    pub fn amortized_rebalancing(mut self) -> Self {
+        self.amortized_rebalancing = true;
+        self
+    }
+
+    /// Validates the configuration and constructs the tracker.
+    pub fn build(self) -> Result<PercentileTracker<T>, BuildError> {
+        if !(1..=99).contains(&self.percentile) {
+            return Err(BuildError::InvalidPercentile(self.percentile));
+        }
+        if self.max_bucket_size == 0 {
+            return Err(BuildError::InvalidBucketSize(self.max_bucket_size));
+        }
+        if let Some(sampling) = &self.sampling {
+            if sampling.rate() == 0 {
+                return Err(BuildError::InvalidSamplingRate(0));
+            }
+        }
+        if let Some(bytes) = self.memory_budget {
+            if bytes < min_memory_budget::<T>() {
+                return Err(BuildError::InvalidMemoryBudget(bytes));
+            }
+        }
+        self.anomaly_threshold.validate()?;
+        if self.reservoir == Some(0) {
+            return Err(BuildError::InvalidReservoirCapacity(0));
+        }
+        if self.checkpoint_depth == 0 {
+            return Err(BuildError::InvalidCheckpointDepth(0));
+        }
+        if self
+            .journal
+            .as_ref()
+            .is_some_and(|journal| journal.capacity() == 0)
+        {
+            return Err(BuildError::InvalidJournalCapacity(0));
+        }
+        if self
+            .range_filter
+            .as_ref()
+            .is_some_and(|filter| filter.is_empty())
+        {
+            return Err(BuildError::EmptyRange);
+        }
+        if self.alerts.iter().any(|alert| alert.is_inverted()) {
+            return Err(BuildError::InvertedAlert);
+        }
+        if let Some(history) = &self.history {
+            if history.policy.max_points == 0 {
+                return Err(BuildError::InvalidHistoryCapacity(0));
+            }
+        }
+        #[cfg(feature = "kll")]
+        let sketch = self.build_sketch()?;
+        Ok(PercentileTracker {
+            buckets: RefCell::new(Vec::new()),
+            total_count: 0,
+            added: 0,
+            percentile_bucket_idx: Cell::new(0),
+            percentile_bucket_offset: Cell::new(0),
+            percentile: self.percentile,
+            max_bucket_size: self.max_bucket_size,
+            unit: self.unit,
+            quantile_method: self.quantile_method,
+            sampler: self.sampling.map(Sampler::new),
+            downsampler: self.memory_budget.map(Downsampler::new),
+            reservoir: self.reservoir.map(|capacity| Reservoir {
+                capacity,
+                seen: 0,
+                rng: SplitMix64(self.reservoir_seed),
+            }),
+            sort_values: self.sort_values.unwrap_or(<[T]>::sort_unstable),
+            min_index: RefCell::new(self.clone_min.map(MinIndex::new)),
+            moments: RefCell::new(None),
+            #[cfg(feature = "num-traits")]
+            sum: RefCell::new(None),
+            #[cfg(feature = "shadow-oracle")]
+            oracle: self.oracle_clone.map(crate::oracle::ShadowOracle::new),
+            instrumentation: self.instrumentation,
+            checkpoints: Checkpoints::new(self.checkpoint_depth),
+            history: self.history,
+            journal: self.journal,
+            range_filter: self.range_filter,
+            observers: self.observers,
+            anomaly_threshold: self.anomaly_threshold,
+            alerts: self.alerts,
+            #[cfg(feature = "kll")]
+            sketch,
+            amortized_rebalancing: self.amortized_rebalancing,
+            needs_rebalancing: Cell::new(false),
+        })
+    }
+}
+
+#[cfg(feature = "kll")]
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Creates the sketch the values are kept in, if the storage is a sketch.
+    fn build_sketch(&self) -> Result<Option<KllSketch<T>>, BuildError> {
+        let Storage::Kll { k } = self.storage else {
+            return Ok(None);
+        };
+        if k < MIN_K {
+            return Err(BuildError::InvalidSketchSize(k));
+        }
+        #[cfg(feature = "shadow-oracle")]
+        if self.oracle_clone.is_some() {
+            return Err(BuildError::NeedsExactStorage("shadow_oracle"));
+        }
+        let conflicts = [
+            (self.reservoir.is_some(), "reservoir"),
+            (self.memory_budget.is_some(), "memory_budget"),
+            (self.journal.is_some(), "undo_journal"),
+            (self.clone_min.is_some(), "min_index"),
+            (self.history.is_some(), "history"),
+            (!self.alerts.is_empty(), "alert"),
+            (self.amortized_rebalancing, "amortized_rebalancing"),
+            (self.sort_values.is_some(), "radix_sort"),
+        ];
+        if let Some(&(_, option)) = conflicts.iter().find(|(conflict, _)| *conflict) {
+            return Err(BuildError::NeedsExactStorage(option));
+        }
+        Ok(Some(KllSketch::with_k(self.percentile, k)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_validation() {
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .percentile(0)
+                .build()
+                .err(),
+            Some(BuildError::InvalidPercentile(0))
+        );
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .percentile(100)
+                .build()
+                .err(),
+            Some(BuildError::InvalidPercentile(100))
+        );
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .max_bucket_size(0)
+                .build()
+                .err(),
+            Some(BuildError::InvalidBucketSize(0))
+        );
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .reservoir(0)
+                .build()
+                .err(),
+            Some(BuildError::InvalidReservoirCapacity(0))
+        );
+    }
+
+    #[test]
+    fn test_builder_small_buckets() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(75)
+            .max_bucket_size(1)
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for value in (0..500i64).map(|i| (i * 7919) % 503) {
+            tracker.insert(value);
+            values.push(value);
+            values.sort_unstable();
+            assert_eq!(tracker.get_percentile(), values[(values.len() * 75) / 100]);
+            assert!(tracker.verify_bucket_offset());
+        }
+    }
+
+    #[test]
+    fn test_builder_amortized_rebalancing() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .amortized_rebalancing()
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for value in (0..2_000i64).map(|i| (i * 7919) % 2_003) {
+            tracker.insert(value);
+            values.push(value);
+
+            // The work was done by the insert, so the query has nothing left to do
+            assert!(!tracker.needs_rebalancing.get());
+            let (bucket_idx, _) = tracker.cursor();
+            let buckets = tracker.buckets.borrow();
+            assert!(buckets[bucket_idx].sorted && buckets[bucket_idx].len() <= 8);
+            drop(buckets);
+
+            values.sort_unstable();
+            assert_eq!(tracker.get_percentile(), values[(values.len() * 90) / 100]);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Building a tracker from data that is already at hand.
+//!
+//! Inserting values one at a time binary searches the buckets for each value and splits
+//! buckets as they fill. When all the data is available up front, the bucket structure can
+//! be built directly instead, and a sorted batch can be merged into existing buckets in a
+//! single walk.
+
+use crate::{to_rank, Bucket, PercentileTracker};
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Creates a tracker from values in ascending order, in O(n).
+    ///
+    /// The values are cut into full buckets that are already sorted, without searching for
+    /// each value's bucket or splitting any, so this suits warm-starting from a saved
+    /// snapshot.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let tracker = PercentileTracker::from_sorted_iter(90, 0..1_000u64);
+    /// assert_eq!(tracker.get_percentile(), 900);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if the values are not
+    /// in ascending order.
+    pub fn from_sorted_iter<I>(percentile: usize, values: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut tracker = Self::new(percentile);
+        let values: Vec<T> = values.into_iter().collect();
+        assert!(
+            values.windows(2).all(|pair| pair[0] <= pair[1]),
+            "Values passed to from_sorted_iter must be in ascending order"
+        );
+        tracker.load_runs(values, true);
+        tracker
+    }
+
+    /// Creates a tracker from a slice of values in ascending order, in O(n).
+    ///
+    /// See [`from_sorted_iter`](Self::from_sorted_iter).
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if the values are not
+    /// in ascending order.
+    pub fn from_sorted_slice(percentile: usize, values: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_sorted_iter(percentile, values.iter().cloned())
+    }
+
+    /// Creates a tracker that takes ownership of unsorted values, without copying them.
+    ///
+    /// The values are partitioned in place with repeated `select_nth_unstable` until every
+    /// run of the maximum bucket size holds a distinct range of values, which takes
+    /// O(n log(n / bucket size)), and each run then becomes a bucket. Buckets are only sorted
+    /// once a query needs them, as usual.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let latencies: Vec<u64> = (0..1_000).rev().collect();
+    /// let tracker = PercentileTracker::from_vec(latencies, 99);
+    /// assert_eq!(tracker.get_percentile(), 990);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn from_vec(mut values: Vec<T>, percentile: usize) -> Self {
+        let mut tracker = Self::new(percentile);
+        partition_runs(&mut values, tracker.max_bucket_size);
+        tracker.load_runs(values, false);
+        tracker
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Inserts a batch of values in ascending order.
+    ///
+    /// The buckets and the batch are walked together, so each bucket receives its run of
+    /// the batch without searching for every value, and a bucket that grew past the maximum
+    /// bucket size is cut into full buckets once at the end. The cursor is found again on
+    /// the next query.
+    ///
+    /// Trackers that sample, filter, journal or observe their inserts, keep a reservoir or
+    /// history, or have a memory budget insert each value as usual instead, since those
+    /// act on every insert. So do trackers that keep their values in a sketch.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut latencies = PercentileTracker::new(90);
+    /// for minute in 0..3u64 {
+    ///     let file: Vec<u64> = (0..1_000).map(|ms| ms + minute).collect();
+    ///     latencies.insert_sorted_batch(&file);
+    /// }
+    /// assert_eq!(latencies.len(), 3_000);
+    /// assert_eq!(latencies.get_percentile(), 901);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the values are not in ascending order.
+    pub fn insert_sorted_batch(&mut self, sorted: &[T]) {
+        assert!(
+            sorted.windows(2).all(|pair| pair[0] <= pair[1]),
+            "Values passed to insert_sorted_batch must be in ascending order"
+        );
+        if self.sampler.is_some()
+            || self.downsampler.is_some()
+            || self.range_filter.is_some()
+            || self.reservoir.is_some()
+            || self.journal.is_some()
+            || self.observers.is_some()
+            || self.history.is_some()
+            || self.has_sketch()
+        {
+            for value in sorted {
+                self.insert(value.clone());
+            }
+            return;
+        }
+        if sorted.is_empty() {
+            return;
+        }
+        self.added += sorted.len() as u64;
+        for value in sorted {
+            self.observe(value);
+        }
+
+        let max_bucket_size = self.max_bucket_size;
+        let old = std::mem::take(self.buckets.get_mut());
+        let mut merged = Vec::with_capacity(old.len() + sorted.len().div_ceil(max_bucket_size));
+        let mut rest = sorted;
+        let mut old = old.into_iter().peekable();
+        while let Some(mut bucket) = old.next() {
+            // Values below every bucket join the first, as with single inserts
+            let end = match old.peek() {
+                Some(next) => rest.partition_point(|value| value < next.min()),
+                None => rest.len(),
+            };
+            let (run, tail) = rest.split_at(end);
+            rest = tail;
+            if run.is_empty() {
+                merged.push(bucket);
+                continue;
+            }
+            let sorted = bucket.sorted && &run[0] >= bucket.max();
+            for value in run {
+                bucket.push(value.clone());
+            }
+            bucket.sorted = sorted;
+            if bucket.len() <= max_bucket_size {
+                merged.push(bucket);
+                continue;
+            }
+            let mut values = bucket.values;
+            if !sorted {
+                partition_runs(&mut values, max_bucket_size);
+            }
+            merged.extend(Bucket::cut_runs(values, max_bucket_size, sorted));
+        }
+        if merged.is_empty() {
+            merged = Bucket::cut_runs(rest.to_vec(), max_bucket_size, true);
+        }
+
+        if let Some(index) = self.min_index.get_mut() {
+            index.clear();
+            for (bucket_idx, bucket) in merged.iter().enumerate() {
+                index.insert(bucket_idx, bucket.min());
+            }
+        }
+        self.total_count = to_rank(self.count() + sorted.len());
+        *self.buckets.get_mut() = merged;
+        self.set_cursor(0, 0);
+        self.needs_rebalancing.set(true);
+        if self.amortized_rebalancing {
+            self.rebalance();
+        }
+    }
+}
+
+/// Reorders values so that each run of `run_len` values, counted from the start, holds no
+/// value larger than any value in a later run.
+fn partition_runs<T: Ord>(values: &mut [T], run_len: usize) {
+    if values.len() <= run_len {
+        return;
+    }
+    // Split on a run boundary near the middle, so both halves stay whole runs
+    let mid = (values.len() / run_len).div_ceil(2) * run_len;
+    values.select_nth_unstable(mid);
+    let (lower, upper) = values.split_at_mut(mid);
+    partition_runs(lower, run_len);
+    partition_runs(upper, run_len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::MAX_BUCKET_SIZE;
+
+    #[test]
+    fn test_from_sorted() {
+        let values: Vec<i64> = (0..1_000).map(|value| value / 3).collect();
+        let mut tracker = PercentileTracker::from_sorted_slice(75, &values);
+        let mut expected = PercentileTracker::new(75);
+        for &value in &values {
+            expected.insert(value);
+        }
+        assert_eq!(tracker.count(), 1_000);
+        assert_eq!(
+            tracker.buckets.borrow().len(),
+            1_000usize.div_ceil(MAX_BUCKET_SIZE)
+        );
+        assert_eq!(tracker.get_percentile(), expected.get_percentile());
+        assert!(tracker.verify_bucket_offset());
+        assert!((tracker.mean().unwrap() - expected.mean().unwrap()).abs() < 1e-9);
+        assert_eq!(tracker.max(), Some(333));
+
+        // The tracker carries on as if the values had been inserted
+        for value in (-100..0).rev() {
+            tracker.insert(value);
+            expected.insert(value);
+        }
+        assert_eq!(tracker.get_percentile(), expected.get_percentile());
+        assert!(tracker.iter_sorted().eq(expected.iter_sorted()));
+
+        let empty = PercentileTracker::from_sorted_iter(50, std::iter::empty::<u8>());
+        assert_eq!(empty.count(), 0);
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let mut rng = SplitMix64(3);
+        let values: Vec<u64> = (0..5_000).map(|_| rng.below(1_000)).collect();
+        let mut expected = PercentileTracker::new(90);
+        for &value in &values {
+            expected.insert(value);
+        }
+        let mut tracker = PercentileTracker::from_vec(values, 90);
+        assert_eq!(tracker.count(), 5_000);
+        {
+            let buckets = tracker.buckets.borrow();
+            assert!(buckets.iter().all(|bucket| bucket.len() <= MAX_BUCKET_SIZE));
+            assert!(buckets
+                .windows(2)
+                .all(|pair| pair[0].max() <= pair[1].min()));
+        }
+        assert_eq!(tracker.get_percentile(), expected.get_percentile());
+        assert!(tracker.verify_bucket_offset());
+        assert_eq!(tracker.min(), expected.min());
+        assert!(tracker.iter_sorted().eq(expected.iter_sorted()));
+
+        tracker.insert(2_000);
+        assert_eq!(tracker.max(), Some(2_000));
+        assert_eq!(PercentileTracker::<u8>::from_vec(Vec::new(), 50).count(), 0);
+    }
+
+    #[test]
+    fn test_partition_runs() {
+        for len in [0, 1, 63, 64, 65, 128, 129, 1_000] {
+            let mut values: Vec<usize> = (0..len).rev().collect();
+            partition_runs(&mut values, 64);
+            for (run, next) in values.chunks(64).zip(values.chunks(64).skip(1)) {
+                assert!(run.iter().max() < next.iter().min());
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_sorted_batch() {
+        let mut rng = SplitMix64(41);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(75)
+            .max_bucket_size(16)
+            .build()
+            .unwrap();
+        let mut expected = PercentileTracker::new(75);
+        for round in 0..50 {
+            let len = rng.below(300) as usize;
+            let mut batch: Vec<u64> = (0..len).map(|_| rng.below(10_000)).collect();
+            batch.sort_unstable();
+            tracker.insert_sorted_batch(&batch);
+            for &value in &batch {
+                expected.insert(value);
+            }
+            // Single inserts in between leave buckets unsorted
+            if round % 3 == 0 {
+                let value = rng.below(10_000);
+                tracker.insert(value);
+                expected.insert(value);
+            }
+            assert_eq!(tracker.count(), expected.count());
+            if tracker.count() > 0 {
+                assert_eq!(tracker.get_percentile(), expected.get_percentile());
+            }
+            assert!(tracker.verify_bucket_offset());
+        }
+        assert!(tracker
+            .buckets
+            .borrow()
+            .windows(2)
+            .all(|pair| pair[0].max() <= pair[1].min()));
+        assert!(tracker.iter_sorted().eq(expected.iter_sorted()));
+
+        // Sampling acts on every insert, so the batch goes through the usual path
+        let mut sampled = PercentileTracker::builder()
+            .sampling(crate::Sampling::Every(2))
+            .build()
+            .unwrap();
+        sampled.insert_sorted_batch(&[1, 2, 3, 4, 5]);
+        assert!(sampled.iter_sorted().eq(&[1, 3, 5]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ascending order")]
+    fn test_from_sorted_rejects_unsorted() {
+        PercentileTracker::from_sorted_slice(50, &[2, 1]);
+    }
+}
@@ -0,0 +1,214 @@
+//! In-memory checkpoints of a tracker's values.
+//!
+//! [`PercentileTracker::checkpoint`] saves a [`FrozenSnapshot`] into a ring buffer held by
+//! the tracker, and [`PercentileTracker::restore`] rolls the tracker back to one of them. This
+//! lets a periodic rollup capture its state without serializing it anywhere.
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::{FrozenSnapshot, PercentileTracker, PercentileTrackerBuilder};
+
+/// The number of checkpoints kept unless the builder sets another depth.
+pub(crate) const DEFAULT_CHECKPOINT_DEPTH: usize = 8;
+
+/// A saved snapshot, with the reservoir bookkeeping needed to resume sampling from it.
+struct Checkpoint<T> {
+    snapshot: FrozenSnapshot<T>,
+
+    /// The tracker's [`seen`](PercentileTracker::seen) count when the checkpoint was taken.
+    seen: u64,
+}
+
+/// The most recent checkpoints, newest first.
+pub(crate) struct Checkpoints<T> {
+    /// The number of checkpoints kept. Older checkpoints are discarded first.
+    depth: usize,
+
+    ring: VecDeque<Checkpoint<T>>,
+}
+
+impl<T> Checkpoints<T> {
+    pub(crate) fn new(depth: usize) -> Self {
+        Checkpoints {
+            depth,
+            ring: VecDeque::new(),
+        }
+    }
+
+    /// Returns the bytes allocated for the ring and the values of every checkpoint in it.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.ring.capacity() * size_of::<Checkpoint<T>>()
+            + self
+                .ring
+                .iter()
+                .map(|checkpoint| checkpoint.snapshot.heap_bytes())
+                .sum::<usize>()
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the number of checkpoints held, at most the configured depth.
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.ring.len()
+    }
+
+    /// Returns a checkpoint, where index 0 is the most recent, or `None` if there is no
+    /// checkpoint at that index.
+    pub fn checkpoint_at(&self, idx: usize) -> Option<&FrozenSnapshot<T>> {
+        self.checkpoints
+            .ring
+            .get(idx)
+            .map(|checkpoint| &checkpoint.snapshot)
+    }
+
+    /// Discards every checkpoint.
+    pub fn clear_checkpoints(&mut self) {
+        self.checkpoints.ring.clear();
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Saves a snapshot of every value into the tracker's ring of checkpoints, discarding the
+    /// oldest checkpoint if the ring is full.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(50);
+    /// for value in 0..100 {
+    ///     tracker.insert(value);
+    /// }
+    /// tracker.checkpoint();
+    /// for value in 1000..1100 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.get_percentile(), 1000);
+    ///
+    /// assert!(tracker.restore(0));
+    /// assert_eq!(tracker.get_percentile(), 50);
+    /// ```
+    pub fn checkpoint(&mut self) {
+        let checkpoint = Checkpoint {
+            snapshot: self.snapshot(),
+            seen: self.seen(),
+        };
+        let checkpoints = &mut self.checkpoints;
+        if checkpoints.ring.len() == checkpoints.depth {
+            checkpoints.ring.pop_back();
+        }
+        checkpoints.ring.push_front(checkpoint);
+    }
+
+    /// Replaces every value with those saved by a checkpoint, where index 0 is the most
+    /// recent. The checkpoint is kept, so it can be restored again.
+    ///
+    /// Returns false, leaving the tracker unchanged, if there is no checkpoint at that index.
+    pub fn restore(&mut self, idx: usize) -> bool {
+        let Some(checkpoint) = self.checkpoints.ring.get(idx) else {
+            return false;
+        };
+        let values = checkpoint.snapshot.values().to_vec();
+        let seen = checkpoint.seen;
+        self.load_runs(values, true);
+        if let Some(reservoir) = &mut self.reservoir {
+            reservoir.seen = seen;
+        }
+        true
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T> {
+    /// Sets the number of checkpoints kept by [`PercentileTracker::checkpoint`], 8 by default.
+    pub fn checkpoint_depth(mut self, depth: usize) -> Self {
+        self.checkpoint_depth = depth;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildError;
+
+    #[test]
+    fn test_checkpoint_ring() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .min_index()
+            .checkpoint_depth(3)
+            .build()
+            .unwrap();
+        assert!(!tracker.restore(0));
+        for round in 0..5i64 {
+            for value in 0..10 {
+                tracker.insert(round * 10 + value);
+            }
+            tracker.checkpoint();
+        }
+        assert_eq!(tracker.checkpoint_count(), 3);
+        assert_eq!(tracker.checkpoint_at(0).unwrap().len(), 50);
+        assert_eq!(tracker.checkpoint_at(2).unwrap().len(), 30);
+        assert!(tracker.checkpoint_at(3).is_none());
+
+        tracker.insert(-1);
+        assert!(tracker.restore(2));
+        assert_eq!(tracker.count(), 30);
+        assert_eq!(tracker.get_percentile(), 27);
+        assert_eq!(tracker.min(), Some(0));
+        assert_eq!(tracker.mean(), Some(14.5));
+        assert!(tracker.verify_bucket_offset());
+        assert_eq!(
+            tracker.min_index.borrow().as_ref().unwrap().mins(),
+            tracker
+                .buckets
+                .borrow()
+                .iter()
+                .map(|bucket| *bucket.min())
+                .collect::<Vec<_>>()
+        );
+
+        // Tracking carries on from the restored values
+        tracker.insert(-5);
+        tracker.insert(100);
+        assert_eq!(tracker.count(), 32);
+        assert_eq!(tracker.min(), Some(-5));
+        assert!(tracker.iter_sorted().is_sorted());
+
+        tracker.clear_checkpoints();
+        assert_eq!(tracker.checkpoint_count(), 0);
+    }
+
+    #[test]
+    fn test_restore_reservoir() {
+        let mut tracker = PercentileTracker::builder().reservoir(100).build().unwrap();
+        for value in 0..1_000u32 {
+            tracker.insert(value);
+        }
+        tracker.checkpoint();
+        for value in 0..1_000u32 {
+            tracker.insert(value);
+        }
+        assert!(tracker.restore(0));
+        assert_eq!(tracker.seen(), 1_000);
+        assert_eq!(tracker.count(), 100);
+    }
+
+    #[test]
+    fn test_checkpoint_depth_validation() {
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .checkpoint_depth(0)
+                .build()
+                .err(),
+            Some(BuildError::InvalidCheckpointDepth(0))
+        );
+    }
+}
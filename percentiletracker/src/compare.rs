@@ -0,0 +1,179 @@
+use crate::{Numeric, PercentileTracker};
+
+/// How one percentile differs between two trackers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PercentileDelta {
+    /// The percentile (0-100) compared.
+    pub percentile: f64,
+
+    /// The value in the tracker `compare` was called on.
+    pub baseline: f64,
+
+    /// The value in the tracker it was compared against.
+    pub candidate: f64,
+
+    /// `candidate - baseline`, so a positive delta means the candidate is higher.
+    pub delta: f64,
+}
+
+/// The differences between the distributions held by two trackers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributionDiff {
+    /// The number of values in the baseline tracker.
+    pub baseline_count: usize,
+
+    /// The number of values in the candidate tracker.
+    pub candidate_count: usize,
+
+    /// The baseline's tracked percentile and each of
+    /// [`DELTA_PERCENTILES`](crate::DELTA_PERCENTILES), in ascending order.
+    pub percentiles: Vec<PercentileDelta>,
+
+    /// The two-sample Kolmogorov-Smirnov statistic: the largest vertical distance between
+    /// the two empirical CDFs, from 0 for identical distributions to 1 for disjoint ones.
+    pub ks_statistic: f64,
+}
+
+impl DistributionDiff {
+    /// Returns the delta at a percentile, if it was one of the percentiles compared.
+    pub fn percentile(&self, percentile: f64) -> Option<&PercentileDelta> {
+        self.percentiles
+            .iter()
+            .find(|delta| delta.percentile == percentile)
+    }
+
+    /// Returns true if the Kolmogorov-Smirnov test rejects, at significance level `alpha`,
+    /// the hypothesis that both trackers sampled the same distribution.
+    ///
+    /// This uses the asymptotic critical value `sqrt(-ln(alpha / 2) / 2 * (n + m) / (n * m))`,
+    /// which is accurate once both trackers hold more than a few dozen values.
+    pub fn is_significant(&self, alpha: f64) -> bool {
+        let n = self.baseline_count as f64;
+        let m = self.candidate_count as f64;
+        let critical = (-(alpha / 2.0).ln() / 2.0 * (n + m) / (n * m)).sqrt();
+        self.ks_statistic > critical
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Compares the distribution in this tracker against another's, or returns `None` if
+    /// either is empty.
+    ///
+    /// This tracker is treated as the baseline, e.g. the old instances in a canary
+    /// deployment, and `other` as the candidate. Both trackers are sorted in full.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut old = PercentileTracker::<u64>::new(99);
+    /// let mut new = PercentileTracker::<u64>::new(99);
+    /// for latency in 0..1000 {
+    ///     old.insert(latency);
+    ///     new.insert(latency + 100);
+    /// }
+    /// let diff = old.compare(&new).unwrap();
+    /// assert_eq!(diff.percentile(99.0).unwrap().delta, 100.0);
+    /// assert!((diff.ks_statistic - 0.1).abs() < 1e-9);
+    /// assert!(diff.is_significant(0.05));
+    /// ```
+    pub fn compare(&self, other: &PercentileTracker<T>) -> Option<DistributionDiff> {
+        let baseline = self.snapshot();
+        let candidate = other.snapshot();
+        if baseline.is_empty() || candidate.is_empty() {
+            return None;
+        }
+
+        let percentiles = self
+            .compared_percentiles()
+            .into_iter()
+            .filter_map(|percentile| {
+                let baseline = baseline.value_at_percentile(percentile)?.to_f64();
+                let candidate = candidate.value_at_percentile(percentile)?.to_f64();
+                Some(PercentileDelta {
+                    percentile,
+                    baseline,
+                    candidate,
+                    delta: candidate - baseline,
+                })
+            })
+            .collect();
+
+        Some(DistributionDiff {
+            baseline_count: baseline.len(),
+            candidate_count: candidate.len(),
+            percentiles,
+            ks_statistic: ks_statistic(baseline.values(), candidate.values()),
+        })
+    }
+}
+
+/// Computes the largest distance between the empirical CDFs of two sorted, non-empty slices.
+///
+/// Both CDFs only change at sample values, so it's enough to evaluate them after each
+/// distinct value, stepping past every copy of it in both slices at once.
+fn ks_statistic<T: Ord>(a: &[T], b: &[T]) -> f64 {
+    let (mut i, mut j) = (0, 0);
+    let mut statistic: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let value = if a[i] <= b[j] { &a[i] } else { &b[j] };
+        while i < a.len() && a[i] == *value {
+            i += 1;
+        }
+        while j < b.len() && b[j] == *value {
+            j += 1;
+        }
+        let distance = (i as f64 / a.len() as f64 - j as f64 / b.len() as f64).abs();
+        statistic = statistic.max(distance);
+    }
+    statistic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ks_statistic() {
+        assert_eq!(ks_statistic(&[1, 2, 3], &[1, 2, 3]), 0.0);
+        assert_eq!(ks_statistic(&[1, 2], &[3, 4]), 1.0);
+        assert_eq!(ks_statistic(&[1, 2, 3, 4], &[3, 4]), 0.5);
+        assert_eq!(ks_statistic(&[1, 1, 2, 2], &[1, 2]), 0.0);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut baseline = PercentileTracker::<i64>::new(90);
+        let mut candidate = PercentileTracker::<i64>::new(50);
+        assert_eq!(baseline.compare(&candidate), None);
+        for value in 0..1000 {
+            baseline.insert(value);
+            candidate.insert(999 - value);
+        }
+        let diff = baseline.compare(&candidate).unwrap();
+        assert_eq!(diff.baseline_count, 1000);
+        assert_eq!(diff.ks_statistic, 0.0);
+        assert!(!diff.is_significant(0.05));
+        assert_eq!(
+            diff.percentiles
+                .iter()
+                .map(|delta| delta.percentile)
+                .collect::<Vec<_>>(),
+            vec![50.0, 90.0, 99.0, 99.9]
+        );
+        assert!(diff.percentiles.iter().all(|delta| delta.delta == 0.0));
+
+        // A slower tail on a tenth of requests
+        for value in 0..100 {
+            candidate.insert(5_000 + value);
+        }
+        let diff = baseline.compare(&candidate).unwrap();
+        let p99 = diff.percentile(99.0).unwrap();
+        assert_eq!((p99.baseline, p99.candidate), (990.0, 5_089.0));
+        assert!(diff.percentile(50.0).unwrap().delta > 0.0);
+        assert!((diff.ks_statistic - 100.0 / 1100.0).abs() < 1e-12);
+        assert!(diff.is_significant(0.05));
+    }
+}
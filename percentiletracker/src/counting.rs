@@ -0,0 +1,331 @@
+//! An exact counting backend for integers from a small domain.
+//!
+//! When every value falls in a narrow range, such as `u8` scores or HTTP status codes, a
+//! count per possible value is both smaller and faster than storing the values. Inserting is
+//! a single increment, and a rank is found by skipping whole blocks of counts with a second,
+//! coarser array of block totals before scanning one block.
+
+use std::ops::RangeInclusive;
+
+use crate::{rank_for_percentile, target_rank, ErrorBound, MergeError, QuantileEstimator};
+
+/// The largest number of distinct values a [`CountingTracker`] keeps a count for.
+pub const MAX_COUNTING_DOMAIN: usize = 1 << 24;
+
+/// The number of counts summed by each block total.
+const BLOCK_SIZE: usize = 256;
+
+/// Integer types that can be counted, by their offset from the bottom of a range.
+pub trait CountingKey: Copy + Ord {
+    /// The smallest value of the type.
+    const MIN: Self;
+
+    /// The largest value of the type.
+    const MAX: Self;
+
+    /// Converts the value to an `i128`, which holds every supported type losslessly.
+    fn to_i128(self) -> i128;
+
+    /// Converts back from a value produced by [`to_i128`](Self::to_i128).
+    fn from_i128(value: i128) -> Self;
+}
+
+macro_rules! impl_counting_key {
+    ($($t:ty),*) => {
+        $(
+            impl CountingKey for $t {
+                const MIN: Self = <$t>::MIN;
+                const MAX: Self = <$t>::MAX;
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn from_i128(value: i128) -> Self {
+                    <$t>::try_from(value).expect("Counted value is out of range for its type")
+                }
+            }
+        )*
+    };
+}
+
+impl_counting_key!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// An exact percentile tracker that keeps a count for every value in a bounded range.
+///
+/// Memory is one `u64` per value in the range, fixed at construction, and inserts are O(1).
+/// Values outside the range are clamped to its nearest end and counted in
+/// [`clamped`](Self::clamped), like saturation in the HDR histogram backend.
+///
+/// ```
+/// use percentiletracker::CountingTracker;
+///
+/// let mut scores = CountingTracker::<u8>::new(90);
+/// for score in 0..=200 {
+///     scores.insert(score);
+/// }
+/// assert_eq!(scores.get_percentile(), 180);
+///
+/// let mut statuses = CountingTracker::with_range(50, 100..=599u16);
+/// statuses.insert(200);
+/// statuses.insert(404);
+/// statuses.insert(503);
+/// assert_eq!(statuses.get_percentile(), 404);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountingTracker<T> {
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// The smallest value with a count.
+    lowest: T,
+
+    /// The largest value with a count.
+    highest: T,
+
+    /// The number of times each value in the range was inserted, indexed by offset from
+    /// `lowest`.
+    counts: Vec<u64>,
+
+    /// The sum of each run of `BLOCK_SIZE` counts.
+    blocks: Vec<u64>,
+
+    /// Total number of values inserted.
+    count: u64,
+
+    /// Number of values outside the range that were recorded at its nearest end.
+    clamped: u64,
+}
+
+impl<T> CountingTracker<T>
+where
+    T: CountingKey,
+{
+    /// Creates an empty tracker covering every value of `T`, e.g. for `u8` or `i16`.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if `T` has more than
+    /// [`MAX_COUNTING_DOMAIN`] values. Use [`with_range`](Self::with_range) for wider types.
+    pub fn new(percentile: usize) -> Self {
+        Self::with_range(percentile, T::MIN..=T::MAX)
+    }
+
+    /// Creates an empty tracker with a count for every value in `range`.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if the range is empty
+    /// or holds more than [`MAX_COUNTING_DOMAIN`] values.
+    pub fn with_range(percentile: usize, range: RangeInclusive<T>) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        let (lowest, highest) = range.into_inner();
+        assert!(lowest <= highest, "The counted range must not be empty");
+        let domain = highest.to_i128() - lowest.to_i128() + 1;
+        assert!(
+            domain <= MAX_COUNTING_DOMAIN as i128,
+            "The counted range holds {} values, more than the maximum of {}",
+            domain,
+            MAX_COUNTING_DOMAIN
+        );
+        let domain = domain as usize;
+        CountingTracker {
+            percentile,
+            lowest,
+            highest,
+            counts: vec![0; domain],
+            blocks: vec![0; domain.div_ceil(BLOCK_SIZE)],
+            count: 0,
+            clamped: 0,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the range of values with a count.
+    pub fn range(&self) -> RangeInclusive<T> {
+        self.lowest..=self.highest
+    }
+
+    /// Returns the number of values inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of values outside the range that were clamped into it.
+    pub fn clamped(&self) -> u64 {
+        self.clamped
+    }
+
+    /// Records a value, clamping it into the range.
+    pub fn insert(&mut self, value: T) {
+        let value = if value < self.lowest || value > self.highest {
+            self.clamped += 1;
+            value.clamp(self.lowest, self.highest)
+        } else {
+            value
+        };
+        let offset = (value.to_i128() - self.lowest.to_i128()) as usize;
+        self.counts[offset] += 1;
+        self.blocks[offset / BLOCK_SIZE] += 1;
+        self.count += 1;
+    }
+
+    /// Adds every value recorded by another tracker into this one.
+    ///
+    /// This tracker keeps its own percentile.
+    ///
+    /// # Panics
+    /// Panics if the trackers count different ranges.
+    pub fn merge(&mut self, other: &CountingTracker<T>) {
+        assert!(
+            self.range() == other.range(),
+            "Cannot merge counting trackers with different ranges"
+        );
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        for (block, other_block) in self.blocks.iter_mut().zip(&other.blocks) {
+            *block += other_block;
+        }
+        self.count += other.count;
+        self.clamped += other.clamped;
+    }
+
+    /// Returns the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile(&self) -> T {
+        assert!(
+            self.count > 0,
+            "Cannot query the percentile of an empty tracker"
+        );
+        let rank = target_rank(self.percentile, self.count as usize) as u64;
+        self.value_at_rank(rank)
+            .expect("The target rank is below the count")
+    }
+
+    /// Returns the value at a percentile (0-100), or `None` if empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns the value at a zero-based rank, or `None` if the rank is out of bounds.
+    ///
+    /// This skips whole blocks by their totals, then scans the counts of one block.
+    pub fn value_at_rank(&self, rank: u64) -> Option<T> {
+        if rank >= self.count {
+            return None;
+        }
+        let mut remaining = rank;
+        let mut block_idx = 0;
+        while remaining >= self.blocks[block_idx] {
+            remaining -= self.blocks[block_idx];
+            block_idx += 1;
+        }
+        let start = block_idx * BLOCK_SIZE;
+        let mut offset = start;
+        while remaining >= self.counts[offset] {
+            remaining -= self.counts[offset];
+            offset += 1;
+        }
+        Some(T::from_i128(self.lowest.to_i128() + offset as i128))
+    }
+}
+
+impl<T> QuantileEstimator<T> for CountingTracker<T>
+where
+    T: CountingKey,
+{
+    fn insert(&mut self, value: T) {
+        CountingTracker::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        CountingTracker::value_at_rank(self, rank as u64)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        if self.range() != other.range() {
+            return Err(MergeError::IncompatibleParameters);
+        }
+        CountingTracker::merge(self, other);
+        Ok(())
+    }
+
+    /// Values within the range are counted exactly. Clamped values are reported at the end
+    /// of the range they fell outside of.
+    fn error_bound(&self) -> ErrorBound {
+        ErrorBound::Exact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::ExactTracker;
+
+    #[test]
+    fn test_matches_exact_tracker() {
+        let mut rng = SplitMix64(31);
+        let mut counting = CountingTracker::<i16>::new(95);
+        let mut exact = ExactTracker::new(95);
+        for step in 0..5_000 {
+            let value = rng.below(2_000) as i16 - 1_000;
+            counting.insert(value);
+            exact.insert(value);
+            if step % 101 == 0 {
+                assert_eq!(counting.get_percentile(), exact.get_percentile());
+                assert_eq!(
+                    counting.value_at_percentile(12.5),
+                    exact.get_percentiles(&[12.5]).first().copied()
+                );
+            }
+        }
+        assert_eq!(counting.value_at_rank(0), exact.min().copied());
+        assert_eq!(counting.value_at_rank(4_999), exact.max().copied());
+        assert_eq!(counting.value_at_rank(5_000), None);
+    }
+
+    #[test]
+    fn test_range_clamping_and_merge() {
+        let mut left = CountingTracker::with_range(50, 10..=1_000u32);
+        left.insert(5);
+        left.insert(500);
+        left.insert(2_000);
+        assert_eq!(left.clamped(), 2);
+        assert_eq!(left.value_at_rank(0), Some(10));
+        assert_eq!(left.value_at_rank(2), Some(1_000));
+
+        let mut right = CountingTracker::with_range(90, 10..=1_000u32);
+        right.insert(600);
+        left.merge(&right);
+        assert_eq!(left.count(), 4);
+        assert_eq!(left.get_percentile(), 600);
+
+        let other = CountingTracker::with_range(50, 0..=1_000u32);
+        assert_eq!(
+            QuantileEstimator::merge(&mut left, &other),
+            Err(MergeError::IncompatibleParameters)
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! A DDSketch for `f64` samples with a relative-error guarantee.
+//!
+//! Samples are counted in logarithmically sized buckets: with relative accuracy `α`, bucket
+//! `i` covers `(γ^(i-1), γ^i]` where `γ = (1 + α) / (1 - α)`. Reporting each bucket by the
+//! midpoint `2γ^i / (γ + 1)` means every reported percentile is within a factor of `α` of the
+//! exact value at that rank, however widely the samples are spread. That suits latency
+//! distributions spanning microseconds to minutes: at 1% accuracy, that whole range fits in
+//! about a thousand buckets, where exact storage would keep every sample.
+//!
+//! Like [`TDigest`](crate::TDigest), sketches can be merged, provided they were created with
+//! the same relative accuracy.
+
+use crate::{rank_for_percentile, ErrorBound, MergeError, QuantileEstimator};
+
+/// The default relative accuracy of 1%.
+pub const DEFAULT_RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Samples with a magnitude below this are counted as zero.
+const MIN_INDEXABLE: f64 = 1e-9;
+
+/// Dense counts for a contiguous range of bucket indices.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Store {
+    /// The bucket index of `counts[0]`.
+    offset: i32,
+
+    /// The number of samples in each bucket.
+    counts: Vec<u64>,
+}
+
+impl Store {
+    /// Adds `count` samples to a bucket, growing the range of buckets if needed.
+    fn add(&mut self, index: i32, count: u64) {
+        if self.counts.is_empty() {
+            self.offset = index;
+            self.counts.push(0);
+        } else if index < self.offset {
+            let grow = (self.offset - index) as usize;
+            self.counts.splice(0..0, std::iter::repeat_n(0, grow));
+            self.offset = index;
+        } else if index >= self.offset + self.counts.len() as i32 {
+            self.counts.resize((index - self.offset) as usize + 1, 0);
+        }
+        self.counts[(index - self.offset) as usize] += count;
+    }
+
+    /// Iterates over the non-empty buckets as `(index, count)`, in ascending index order.
+    fn buckets(&self) -> impl DoubleEndedIterator<Item = (i32, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(move |(i, &count)| (self.offset + i as i32, count))
+    }
+}
+
+/// An approximate percentile tracker for `f64` samples with a relative-error guarantee.
+///
+/// ```
+/// use percentiletracker::DDSketch;
+///
+/// let mut sketch = DDSketch::new(99);
+/// for micros in 1..=100_000 {
+///     sketch.insert(micros as f64);
+/// }
+/// let p99 = sketch.get_percentile();
+/// assert!((p99 - 99_001.0).abs() <= 99_001.0 * 0.01);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DDSketch {
+    /// The guaranteed relative accuracy of reported values.
+    relative_accuracy: f64,
+
+    /// `ln(γ)`, cached since every insert needs it.
+    ln_gamma: f64,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// Buckets for positive samples.
+    positive: Store,
+
+    /// Buckets for negative samples, indexed by magnitude.
+    negative: Store,
+
+    /// The number of samples too close to zero to index.
+    zero_count: u64,
+
+    /// Total number of samples.
+    count: u64,
+}
+
+impl DDSketch {
+    /// Creates an empty sketch tracking the given percentile with 1% relative accuracy.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        Self::with_relative_accuracy(percentile, DEFAULT_RELATIVE_ACCURACY)
+    }
+
+    /// Creates an empty sketch with a custom relative accuracy.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if the relative
+    /// accuracy is not strictly between 0 and 1.
+    pub fn with_relative_accuracy(percentile: usize, relative_accuracy: f64) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "Relative accuracy must be between 0 and 1 exclusive, got {}",
+            relative_accuracy
+        );
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+        DDSketch {
+            relative_accuracy,
+            ln_gamma: gamma.ln(),
+            percentile,
+            positive: Store::default(),
+            negative: Store::default(),
+            zero_count: 0,
+            count: 0,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the guaranteed relative accuracy of reported values.
+    pub fn relative_accuracy(&self) -> f64 {
+        self.relative_accuracy
+    }
+
+    /// Returns the number of samples inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no samples have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of buckets allocated, which bounds the sketch's memory.
+    pub fn bucket_count(&self) -> usize {
+        self.positive.counts.len() + self.negative.counts.len()
+    }
+
+    /// Inserts a sample. NaN and infinite samples are ignored, since no bucket can hold them.
+    pub fn insert(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1;
+        if value > MIN_INDEXABLE {
+            let index = self.index(value);
+            self.positive.add(index, 1);
+        } else if value < -MIN_INDEXABLE {
+            let index = self.index(-value);
+            self.negative.add(index, 1);
+        } else {
+            self.zero_count += 1;
+        }
+    }
+
+    /// Merges every sample counted by another sketch into this one.
+    ///
+    /// This sketch keeps its own percentile.
+    ///
+    /// # Panics
+    /// Panics if the sketches have different relative accuracies, since their buckets don't line up.
+    pub fn merge(&mut self, other: &DDSketch) {
+        assert!(
+            self.is_compatible(other),
+            "Cannot merge sketches with relative accuracies {} and {}",
+            self.relative_accuracy,
+            other.relative_accuracy
+        );
+        for (index, count) in other.positive.buckets() {
+            self.positive.add(index, count);
+        }
+        for (index, count) in other.negative.buckets() {
+            self.negative.add(index, count);
+        }
+        self.zero_count += other.zero_count;
+        self.count += other.count;
+    }
+
+    /// Returns the estimated value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the sketch is empty.
+    pub fn get_percentile(&self) -> f64 {
+        self.value_at_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty sketch")
+    }
+
+    /// Returns the estimated value at an arbitrary percentile (0-100), or `None` if empty.
+    ///
+    /// The estimate is within the relative accuracy of the exact value at the same rank.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns the estimated value at a zero-based rank, or `None` if the rank is out of bounds.
+    fn value_at_rank(&self, rank: u64) -> Option<f64> {
+        if rank >= self.count {
+            return None;
+        }
+
+        // Negative values are ascending when their magnitudes are descending
+        let mut seen = 0;
+        for (index, count) in self.negative.buckets().rev() {
+            seen += count;
+            if rank < seen {
+                return Some(-self.value(index));
+            }
+        }
+        seen += self.zero_count;
+        if rank < seen {
+            return Some(0.0);
+        }
+        for (index, count) in self.positive.buckets() {
+            seen += count;
+            if rank < seen {
+                return Some(self.value(index));
+            }
+        }
+        unreachable!("Rank {} is within the {} samples", rank, self.count);
+    }
+
+    /// Returns true if another sketch's buckets line up with this one's.
+    fn is_compatible(&self, other: &DDSketch) -> bool {
+        self.relative_accuracy == other.relative_accuracy
+    }
+
+    /// Returns the bucket index for a positive magnitude.
+    fn index(&self, magnitude: f64) -> i32 {
+        (magnitude.ln() / self.ln_gamma).ceil() as i32
+    }
+
+    /// Returns the representative value of a bucket, the midpoint of its bounds in relative terms.
+    fn value(&self, index: i32) -> f64 {
+        let gamma = self.ln_gamma.exp();
+        2.0 * (index as f64 * self.ln_gamma).exp() / (gamma + 1.0)
+    }
+}
+
+impl QuantileEstimator<f64> for DDSketch {
+    fn insert(&mut self, value: f64) {
+        DDSketch::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<f64> {
+        DDSketch::value_at_rank(self, rank as u64)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        if !self.is_compatible(other) {
+            return Err(MergeError::IncompatibleParameters);
+        }
+        DDSketch::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        ErrorBound::Relative(self.relative_accuracy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ddsketch_relative_error() {
+        // Latencies from 1µs up to about 10 minutes, in seconds
+        let mut values: Vec<f64> = (0..50_000)
+            .map(|i| 1e-6 * 1.0004f64.powi((i * 7919) % 50_000))
+            .collect();
+        let mut sketch = DDSketch::new(99);
+        for &value in &values {
+            sketch.insert(value);
+        }
+        values.sort_by(f64::total_cmp);
+
+        assert!(sketch.bucket_count() <= 1100);
+        for percentile in [0.0, 1.0, 50.0, 90.0, 99.0, 99.9, 100.0] {
+            let exact = values[rank_for_percentile(percentile, values.len())];
+            let estimate = sketch.value_at_percentile(percentile).unwrap();
+            assert!(
+                (estimate - exact).abs() <= exact * sketch.relative_accuracy(),
+                "p{} estimate {} is too far from {}",
+                percentile,
+                estimate,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn test_ddsketch_negative_and_zero() {
+        let mut sketch = DDSketch::with_relative_accuracy(50, 0.02);
+        for value in [-100.0, -10.0, 0.0, 0.0, 10.0, f64::NAN] {
+            sketch.insert(value);
+        }
+        assert_eq!(sketch.count(), 5);
+        assert_eq!(sketch.get_percentile(), 0.0);
+        let min = sketch.value_at_percentile(0.0).unwrap();
+        assert!((min + 100.0).abs() <= 2.0);
+        let max = sketch.value_at_percentile(100.0).unwrap();
+        assert!((max - 10.0).abs() <= 0.2);
+    }
+
+    #[test]
+    fn test_ddsketch_merge() {
+        let mut merged = DDSketch::new(90);
+        let mut single = DDSketch::new(90);
+        for shard in 0..4 {
+            let mut sketch = DDSketch::new(50);
+            for i in 1..=1000 {
+                let value = (shard * 1000 + i) as f64;
+                sketch.insert(value);
+                single.insert(value);
+            }
+            merged.merge(&sketch);
+        }
+        assert_eq!(merged, single);
+        assert_eq!(DDSketch::new(90).value_at_percentile(50.0), None);
+    }
+}
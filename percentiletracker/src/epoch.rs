@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use crate::PercentileTracker;
+
+/// A tracker for the current epoch plus a fixed number of previous epochs.
+///
+/// Values go into the current epoch until [`rotate`](Self::rotate) is called, e.g. once a
+/// minute, which retires it into the list of previous epochs and starts an empty one. The
+/// oldest epoch is dropped once more than the configured number have been retired.
+///
+/// ```
+/// use percentiletracker::EpochTracker;
+///
+/// let mut latencies = EpochTracker::new(50, 2);
+/// for ms in [10u64, 20, 30] {
+///     latencies.insert(ms);
+/// }
+/// latencies.rotate();
+/// for ms in [100, 200, 300] {
+///     latencies.insert(ms);
+/// }
+/// assert_eq!(latencies.current().get_percentile(), 200);
+/// assert_eq!(latencies.previous(1).unwrap().get_percentile(), 20);
+/// assert_eq!(latencies.merged().get_percentile(), 100);
+/// ```
+pub struct EpochTracker<T>
+where
+    T: Ord,
+{
+    /// Builds the tracker for each new epoch.
+    factory: Box<dyn Fn() -> PercentileTracker<T> + Send + Sync>,
+
+    /// The epoch receiving values.
+    current: PercentileTracker<T>,
+
+    /// Retired epochs, most recent first.
+    previous: VecDeque<PercentileTracker<T>>,
+
+    /// The number of retired epochs kept.
+    retained: usize,
+}
+
+impl<T> EpochTracker<T>
+where
+    T: Ord,
+{
+    /// Creates a tracker for the given percentile that keeps `retained` previous epochs.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize, retained: usize) -> Self {
+        // Fail here rather than on the first rotation
+        drop(PercentileTracker::<T>::new(percentile));
+        Self::with_factory(retained, move || PercentileTracker::new(percentile))
+    }
+
+    /// Creates a tracker that keeps `retained` previous epochs and builds the tracker for
+    /// each epoch with `factory`.
+    pub fn with_factory<F>(retained: usize, factory: F) -> Self
+    where
+        F: Fn() -> PercentileTracker<T> + Send + Sync + 'static,
+    {
+        EpochTracker {
+            current: factory(),
+            factory: Box::new(factory),
+            previous: VecDeque::with_capacity(retained),
+            retained,
+        }
+    }
+
+    /// Inserts a value into the current epoch.
+    pub fn insert(&mut self, value: T) {
+        self.current.insert(value);
+    }
+
+    /// Retires the current epoch and starts an empty one, dropping the oldest retired epoch
+    /// if more than the configured number would be kept.
+    pub fn rotate(&mut self) {
+        let retired = std::mem::replace(&mut self.current, (self.factory)());
+        if self.retained == 0 {
+            return;
+        }
+        if self.previous.len() == self.retained {
+            self.previous.pop_back();
+        }
+        self.previous.push_front(retired);
+    }
+
+    /// Returns the epoch currently receiving values.
+    pub fn current(&self) -> &PercentileTracker<T> {
+        &self.current
+    }
+
+    /// Returns a retired epoch, where 1 is the most recently retired, or `None` if it isn't
+    /// kept. Epoch 0 is the current one.
+    pub fn previous(&self, n: usize) -> Option<&PercentileTracker<T>> {
+        match n {
+            0 => Some(&self.current),
+            n => self.previous.get(n - 1),
+        }
+    }
+
+    /// Returns the number of retired epochs currently kept.
+    pub fn retained(&self) -> usize {
+        self.previous.len()
+    }
+
+    /// Returns a tracker holding the values of the current epoch and every retired epoch.
+    ///
+    /// This copies every value, so it suits periodic reports rather than per-request queries.
+    pub fn merged(&self) -> PercentileTracker<T>
+    where
+        T: Clone,
+    {
+        self.merged_last(self.previous.len())
+    }
+
+    /// Returns a tracker holding the values of the current epoch and the `n` most recently
+    /// retired epochs, or every retired epoch if fewer are kept.
+    pub fn merged_last(&self, n: usize) -> PercentileTracker<T>
+    where
+        T: Clone,
+    {
+        let mut merged = (self.factory)();
+        for epoch in std::iter::once(&self.current).chain(self.previous.iter().take(n)) {
+            merged
+                .merge(epoch)
+                .expect("Every epoch is built with the same unit");
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Unit;
+
+    #[test]
+    fn test_epoch_rotation() {
+        let mut epochs = EpochTracker::with_factory(2, || {
+            PercentileTracker::builder()
+                .percentile(90)
+                .unit(Unit::Milliseconds)
+                .build()
+                .unwrap()
+        });
+        for epoch in 0..4i64 {
+            for value in 0..10 {
+                epochs.insert(epoch * 100 + value);
+            }
+            epochs.rotate();
+        }
+        epochs.insert(1_000);
+
+        assert_eq!(epochs.retained(), 2);
+        assert_eq!(epochs.current().count(), 1);
+        assert_eq!(epochs.previous(0).unwrap().count(), 1);
+        assert_eq!(epochs.previous(1).unwrap().min(), Some(300));
+        assert_eq!(epochs.previous(2).unwrap().min(), Some(200));
+        assert!(epochs.previous(3).is_none());
+
+        let merged = epochs.merged();
+        assert_eq!(merged.count(), 21);
+        assert_eq!(merged.min(), Some(200));
+        assert_eq!(merged.unit(), Unit::Milliseconds);
+        assert_eq!(epochs.merged_last(1).count(), 11);
+        assert_eq!(epochs.merged_last(0).get_percentile(), 1_000);
+    }
+
+    #[test]
+    fn test_no_retained_epochs() {
+        let mut epochs = EpochTracker::new(50, 0);
+        epochs.insert(1u8);
+        epochs.rotate();
+        assert_eq!(epochs.retained(), 0);
+        assert_eq!(epochs.current().count(), 0);
+        assert_eq!(epochs.merged().count(), 0);
+    }
+}
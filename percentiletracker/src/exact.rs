@@ -0,0 +1,268 @@
+//! The naive baseline: every value in one sorted `Vec`.
+//!
+//! [`ExactTracker`] answers the same queries as [`PercentileTracker`](crate::PercentileTracker)
+//! by binary searching a single sorted vector. Each insert shifts the values above it, so it
+//! costs O(n), but there is no bucket bookkeeping at all. That makes it the obvious oracle
+//! for downstream tests, and the faster choice for a few hundred values.
+
+use crate::{
+    rank_for_percentile, target_rank, BuildError, ErrorBound, MergeError, QuantileEstimator,
+};
+
+/// A percentile tracker that keeps every value in a single sorted `Vec`.
+///
+/// ```
+/// use percentiletracker::{ExactTracker, PercentileTracker};
+///
+/// let mut exact = ExactTracker::new(90);
+/// let mut tracker = PercentileTracker::new(90);
+/// for value in [17u64, 3, 99, 42, 8, 61, 25, 70, 5, 33] {
+///     exact.insert(value);
+///     tracker.insert(value);
+///     assert_eq!(exact.get_percentile(), tracker.get_percentile());
+/// }
+/// assert_eq!(exact.values(), &[3, 5, 8, 17, 25, 33, 42, 61, 70, 99]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExactTracker<T> {
+    /// Every inserted value in ascending order.
+    values: Vec<T>,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+}
+
+impl<T> ExactTracker<T>
+where
+    T: Ord,
+{
+    /// Creates an empty tracker for the given percentile.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        if !(1..=99).contains(&percentile) {
+            panic!("{}", BuildError::InvalidPercentile(percentile));
+        }
+        ExactTracker {
+            values: Vec::new(),
+            percentile,
+        }
+    }
+
+    /// Returns the percentile the tracker tracks.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Changes the tracked percentile, keeping every stored value.
+    pub fn set_percentile(&mut self, percentile: usize) -> Result<(), BuildError> {
+        if !(1..=99).contains(&percentile) {
+            return Err(BuildError::InvalidPercentile(percentile));
+        }
+        self.percentile = percentile;
+        Ok(())
+    }
+
+    /// Returns the number of values inserted.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns every inserted value in ascending order.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Inserts a value after any equal values already stored.
+    pub fn insert(&mut self, value: T) {
+        let position = self.values.partition_point(|stored| stored <= &value);
+        self.values.insert(position, value);
+    }
+
+    /// Removes one copy of a value, returning false if it wasn't stored.
+    pub fn remove(&mut self, value: &T) -> bool {
+        match self.values.binary_search(value) {
+            Ok(position) => {
+                self.values.remove(position);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Retrieves a reference to the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile_ref(&self) -> &T {
+        &self.values[target_rank(self.percentile, self.len())]
+    }
+
+    /// Retrieves the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile(&self) -> T
+    where
+        T: Clone,
+    {
+        self.get_percentile_ref().clone()
+    }
+
+    /// Returns the values at several percentiles (0-100), in the order requested, or an
+    /// empty vector if the tracker is empty.
+    pub fn get_percentiles(&self, percentiles: &[f64]) -> Vec<T>
+    where
+        T: Clone,
+    {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        percentiles
+            .iter()
+            .map(|&percentile| self.values[rank_for_percentile(percentile, self.len())].clone())
+            .collect()
+    }
+
+    /// Returns the value at a zero-based rank in sorted order, or `None` if the rank is out
+    /// of bounds.
+    pub fn value_at_rank(&self, rank: usize) -> Option<&T> {
+        self.values.get(rank)
+    }
+
+    /// Returns how many values are smaller than `x`, or at most `x` when `inclusive`.
+    pub fn count_below(&self, x: &T, inclusive: bool) -> usize {
+        if inclusive {
+            self.values.partition_point(|value| value <= x)
+        } else {
+            self.values.partition_point(|value| value < x)
+        }
+    }
+
+    /// Returns the percentile rank of `x`, with the same mid-rank convention as
+    /// [`PercentileTracker::percentile_of`](crate::PercentileTracker::percentile_of).
+    pub fn percentile_of(&self, x: &T) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let below = self.count_below(x, false);
+        let at_most = self.count_below(x, true);
+        100.0 * (below + at_most) as f64 / (2 * self.len()) as f64
+    }
+
+    /// Returns the smallest value, or `None` if empty.
+    pub fn min(&self) -> Option<&T> {
+        self.values.first()
+    }
+
+    /// Returns the largest value, or `None` if empty.
+    pub fn max(&self) -> Option<&T> {
+        self.values.last()
+    }
+}
+
+impl<T> ExactTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Adds every value from another tracker into this one, keeping this tracker's
+    /// percentile.
+    pub fn merge(&mut self, other: &ExactTracker<T>) {
+        self.values.extend(other.values.iter().cloned());
+        // Both halves are sorted, which the stable sort detects and merges in linear time
+        self.values.sort();
+    }
+}
+
+impl<T> Extend<T> for ExactTracker<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.values.extend(iter);
+        self.values.sort();
+    }
+}
+
+impl<T> QuantileEstimator<T> for ExactTracker<T>
+where
+    T: Clone + Ord,
+{
+    fn insert(&mut self, value: T) {
+        ExactTracker::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        ExactTracker::len(self)
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        ExactTracker::value_at_rank(self, rank).cloned()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        ExactTracker::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        ErrorBound::Exact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::PercentileTracker;
+
+    #[test]
+    fn test_matches_percentile_tracker() {
+        let mut rng = SplitMix64(11);
+        let mut exact = ExactTracker::new(95);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(95)
+            .max_bucket_size(16)
+            .build()
+            .unwrap();
+        for _ in 0..2_000 {
+            let value = rng.below(300);
+            exact.insert(value);
+            tracker.insert(value);
+            assert_eq!(exact.get_percentile(), tracker.get_percentile());
+            assert_eq!(exact.percentile_of(&150), tracker.percentile_of(&150));
+        }
+        assert_eq!(
+            exact.get_percentiles(&[0.0, 50.0, 99.9, 100.0]),
+            tracker.get_percentiles(&[0.0, 50.0, 99.9, 100.0])
+        );
+        assert_eq!(exact.min(), Some(&0));
+        assert_eq!(exact.max(), Some(&299));
+    }
+
+    #[test]
+    fn test_merge_and_remove() {
+        let mut left = ExactTracker::new(50);
+        left.extend([5, 1, 9]);
+        let mut right = ExactTracker::new(90);
+        right.extend([4, 8, 2]);
+        left.merge(&right);
+        assert_eq!(left.values(), &[1, 2, 4, 5, 8, 9]);
+        assert_eq!(left.get_percentile(), 5);
+
+        assert!(left.remove(&4));
+        assert!(!left.remove(&4));
+        assert_eq!(left.len(), 5);
+        assert_eq!(left.value_at_rank(2), Some(&5));
+        assert_eq!(
+            left.set_percentile(0),
+            Err(BuildError::InvalidPercentile(0))
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::Write;
+
+use crate::prometheus::quantile;
+use crate::{Numeric, PercentileTracker};
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Exports the summary statistics and the given percentiles (0-100) as a JSON object.
+    ///
+    /// Values are in the tracker's unit, which is included so the numbers can be interpreted.
+    /// The statistics are `null` and the quantile list empty if the tracker is empty.
+    ///
+    /// ```
+    /// use percentiletracker::{PercentileTracker, Unit};
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .unit(Unit::Milliseconds)
+    ///     .build()
+    ///     .unwrap();
+    /// for value in 1..=4 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(
+    ///     tracker.export_json(&[50.0, 99.0]),
+    ///     "{\"unit\":\"ms\",\"count\":4,\"min\":1,\"max\":4,\"mean\":2.5,\
+    ///      \"quantiles\":[{\"quantile\":0.5,\"value\":3},{\"quantile\":0.99,\"value\":4}]}"
+    /// );
+    /// ```
+    pub fn export_json(&self, percentiles: &[f64]) -> String {
+        let summary = self.summary_at(percentiles);
+        let mut out = String::from("{\"unit\":");
+        write_json_string(&mut out, self.unit.suffix());
+        let _ = write!(out, ",\"count\":{}", summary.count);
+        let _ = write!(out, ",\"min\":{}", json_number(summary.min.map(T::to_f64)));
+        let _ = write!(out, ",\"max\":{}", json_number(summary.max.map(T::to_f64)));
+        let _ = write!(out, ",\"mean\":{}", json_number(summary.mean));
+        out.push_str(",\"quantiles\":[");
+        for (i, (percentile, value)) in summary.percentiles.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"quantile\":{},\"value\":{}}}",
+                quantile(*percentile),
+                value.to_f64()
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Exports the summary statistics and the given percentiles (0-100) as CSV with a
+    /// `name,quantile,value` header, ready for a spreadsheet or dataframe.
+    ///
+    /// The rows are `count`, `min`, `max` and `mean`, followed by one row per percentile
+    /// named like `p99.9`. `min` and `max` carry quantiles 0 and 1, and `count` and `mean`
+    /// leave the quantile empty. Statistics of an empty tracker have empty values.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(90);
+    /// for value in 1..=4 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(
+    ///     tracker.export_csv(&[50.0]),
+    ///     "name,quantile,value\ncount,,4\nmin,0,1\nmax,1,4\nmean,,2.5\np50,0.5,3\n"
+    /// );
+    /// ```
+    pub fn export_csv(&self, percentiles: &[f64]) -> String {
+        let summary = self.summary_at(percentiles);
+        let csv_value =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let mut out = String::from("name,quantile,value\n");
+        let _ = writeln!(out, "count,,{}", summary.count);
+        let _ = writeln!(out, "min,0,{}", csv_value(summary.min.map(T::to_f64)));
+        let _ = writeln!(out, "max,1,{}", csv_value(summary.max.map(T::to_f64)));
+        let _ = writeln!(out, "mean,,{}", csv_value(summary.mean));
+        for (percentile, value) in &summary.percentiles {
+            let _ = writeln!(
+                out,
+                "p{},{},{}",
+                percentile,
+                quantile(*percentile),
+                value.to_f64()
+            );
+        }
+        out
+    }
+}
+
+/// Formats an optional number as JSON, using `null` for `None`.
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+/// Writes a string as a quoted JSON string.
+pub(crate) fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_empty() {
+        let tracker = PercentileTracker::<u32>::new(50);
+        assert_eq!(
+            tracker.export_json(&[50.0]),
+            "{\"unit\":\"\",\"count\":0,\"min\":null,\"max\":null,\"mean\":null,\"quantiles\":[]}"
+        );
+        assert_eq!(
+            tracker.export_csv(&[50.0]),
+            "name,quantile,value\ncount,,0\nmin,0,\nmax,1,\nmean,,\n"
+        );
+    }
+
+    #[test]
+    fn test_export_quantiles() {
+        let mut tracker = PercentileTracker::<i64>::new(50);
+        for value in -500..500 {
+            tracker.insert(value);
+        }
+        let csv = tracker.export_csv(&[99.9, 0.0, 99.99]);
+        assert!(csv.ends_with("p0,0,-500\np99.9,0.999,499\np99.99,0.9999,499\n"));
+        assert!(tracker
+            .export_json(&[99.9])
+            .ends_with("\"quantiles\":[{\"quantile\":0.999,\"value\":499}]}"));
+    }
+
+    #[test]
+    fn test_write_json_string() {
+        let mut out = String::new();
+        write_json_string(&mut out, "a\"b\\c\n");
+        assert_eq!(out, "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
@@ -0,0 +1,196 @@
+//! Variants of the core operations that report errors instead of panicking.
+//!
+//! [`insert`](PercentileTracker::insert), [`get_percentile`](PercentileTracker::get_percentile)
+//! and [`merge`](PercentileTracker::merge) panic on an empty tracker or when the count no
+//! longer fits its integer type. The `try_` variants check those cases up front and leave
+//! the tracker unchanged, for callers that must never unwind, such as FFI boundaries and
+//! audio threads.
+//!
+//! Only the tracker's own panics are replaced. Callbacks configured on the builder still
+//! run, and can still panic, as can a shadow oracle that finds a mismatch. Each method lists
+//! the hooks it may run under `# Panics`.
+
+use std::fmt;
+
+use crate::{from_rank, MergeError, PercentileTracker, Rank};
+
+/// An error returned by the `try_` variants of the core tracker operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PercentileError {
+    /// The tracker holds no values, so it has no percentile.
+    Empty,
+
+    /// The tracker can't hold any more values. This is `u32::MAX` values with the
+    /// `compact-indices` feature, and `usize::MAX` otherwise.
+    CapacityExceeded { capacity: usize },
+
+    /// The trackers couldn't be merged.
+    Merge(MergeError),
+}
+
+impl fmt::Display for PercentileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PercentileError::Empty => f.write_str("The tracker holds no values"),
+            PercentileError::CapacityExceeded { capacity } => {
+                write!(f, "The tracker can't hold more than {} values", capacity)
+            }
+            PercentileError::Merge(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PercentileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PercentileError::Merge(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MergeError> for PercentileError {
+    fn from(err: MergeError) -> Self {
+        PercentileError::Merge(err)
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Checks that the tracker could hold `additional` more values.
+    fn check_capacity(&self, additional: usize) -> Result<(), PercentileError> {
+        let capacity = from_rank(Rank::MAX);
+        // Subtracting from the capacity can't overflow, unlike adding to the count
+        if additional > capacity - self.count() {
+            return Err(PercentileError::CapacityExceeded { capacity });
+        }
+        Ok(())
+    }
+
+    /// Inserts a value, or returns an error and leaves the tracker unchanged if the tracker
+    /// is full.
+    ///
+    /// Only the capacity is checked up front. The value is then inserted by
+    /// [`insert`](Self::insert), which runs the same hooks.
+    ///
+    /// # Errors
+    /// Returns [`PercentileError::CapacityExceeded`] if the count would exceed the count
+    /// type.
+    ///
+    /// # Panics
+    /// Panics if a hook panics: the accepted range's callback, an observer, an
+    /// instrumentation hook, or, when the insert records a history point, an alert callback
+    /// or the shadow oracle.
+    pub fn try_insert(&mut self, value: T) -> Result<(), PercentileError> {
+        self.check_capacity(1)?;
+        self.insert(value);
+        Ok(())
+    }
+
+    /// Retrieves the value at the tracked percentile, or an error instead of panicking.
+    ///
+    /// ```
+    /// use percentiletracker::{PercentileError, PercentileTracker};
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(50);
+    /// assert_eq!(tracker.try_get_percentile(), Err(PercentileError::Empty));
+    /// tracker.try_insert(7).unwrap();
+    /// assert_eq!(tracker.try_get_percentile(), Ok(7));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`PercentileError::Empty`] if the tracker holds no values.
+    ///
+    /// # Panics
+    /// Panics if an alert callback or instrumentation hook panics, or if the shadow oracle
+    /// finds a mismatch.
+    pub fn try_get_percentile(&self) -> Result<T, PercentileError>
+    where
+        T: Clone,
+    {
+        if self.count() == 0 {
+            return Err(PercentileError::Empty);
+        }
+        Ok(self.get_percentile())
+    }
+
+    /// Adds every value from another tracker into this one, or returns an error and leaves
+    /// this tracker unchanged.
+    ///
+    /// The capacity check assumes every value of `other` is kept, so it's conservative for a
+    /// tracker with a reservoir.
+    ///
+    /// # Errors
+    /// Returns [`PercentileError::Merge`] if the trackers can't be combined, and
+    /// [`PercentileError::CapacityExceeded`] if the merged tracker would be too large.
+    pub fn try_merge(&mut self, other: &PercentileTracker<T>) -> Result<(), PercentileError>
+    where
+        T: Clone,
+    {
+        self.check_capacity(other.count())?;
+        self.merge(other)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{target_rank, Unit};
+
+    #[test]
+    fn test_try_operations() {
+        let mut tracker = PercentileTracker::new(90);
+        assert_eq!(tracker.try_get_percentile(), Err(PercentileError::Empty));
+        for value in 0..10u32 {
+            tracker.try_insert(value).unwrap();
+        }
+        assert_eq!(tracker.try_get_percentile(), Ok(9));
+
+        let mut other = PercentileTracker::new(50);
+        other.insert(20);
+        tracker.try_merge(&other).unwrap();
+        assert_eq!(tracker.try_get_percentile(), Ok(9));
+        assert_eq!(tracker.count(), 11);
+
+        let mut seconds = PercentileTracker::builder()
+            .unit(Unit::Seconds)
+            .build()
+            .unwrap();
+        let mut bytes = PercentileTracker::builder()
+            .unit(Unit::Bytes)
+            .build()
+            .unwrap();
+        bytes.insert(1u32);
+        let err = seconds.try_merge(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            PercentileError::Merge(MergeError::UnitMismatch { .. })
+        ));
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(seconds.count(), 0);
+    }
+
+    #[test]
+    fn test_capacity_checks() {
+        let mut tracker = PercentileTracker::new(99);
+        tracker.insert(1u8);
+        // Pretend the tracker is full without inserting billions of values
+        tracker.total_count = Rank::MAX;
+        assert_eq!(
+            tracker.try_insert(2),
+            Err(PercentileError::CapacityExceeded {
+                capacity: from_rank(Rank::MAX)
+            })
+        );
+        assert!(tracker.check_capacity(usize::MAX).is_err());
+        tracker.total_count = Rank::MAX - 1;
+        assert_eq!(tracker.try_insert(2), Ok(()));
+        assert_eq!(
+            tracker.get_target_pos(),
+            target_rank(99, from_rank(Rank::MAX))
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{rank_for_percentile, PercentileTracker};
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the value at a percentile (0-100) of only the values matching a predicate,
+    /// or `None` if no values match.
+    ///
+    /// The matches in each bucket are counted, and because the buckets partition the value
+    /// range only the bucket holding the requested rank needs to be sorted. This costs a full
+    /// pass over the values, so it's meant for ad-hoc questions rather than hot paths.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<i64>::new(50);
+    /// for value in -100..100 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.get_percentile_where(50.0, |&value| value > 0), Some(50));
+    /// ```
+    pub fn get_percentile_where(&self, percentile: f64, pred: impl Fn(&T) -> bool) -> Option<T> {
+        let mut buckets = self.buckets.borrow_mut();
+        let matches: Vec<usize> = buckets
+            .iter()
+            .map(|bucket| bucket.values.iter().filter(|value| pred(value)).count())
+            .collect();
+        let total: usize = matches.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = rank_for_percentile(percentile, total);
+        let mut offset = 0;
+        for (bucket, count) in buckets.iter_mut().zip(matches) {
+            if rank < offset + count {
+                self.sort_bucket(bucket);
+                return bucket
+                    .values
+                    .iter()
+                    .filter(|value| pred(value))
+                    .nth(rank - offset)
+                    .cloned();
+            }
+            offset += count;
+        }
+        unreachable!("Rank {} is within the {} matching values", rank, total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_percentile_where() {
+        let mut tracker = PercentileTracker::new(90);
+        let mut values = Vec::new();
+        for value in (0..1000i64).map(|i| (i * 7919) % 1009 - 500) {
+            tracker.insert(value);
+            values.push(value);
+        }
+        let expected_p90 = {
+            values.sort_unstable();
+            values[(values.len() * 90) / 100]
+        };
+        assert_eq!(tracker.get_percentile(), expected_p90);
+
+        let positive: Vec<_> = values.iter().copied().filter(|&v| v > 0).collect();
+        for percentile in [0.0, 50.0, 99.0, 100.0] {
+            assert_eq!(
+                tracker.get_percentile_where(percentile, |&value| value > 0),
+                Some(positive[rank_for_percentile(percentile, positive.len())])
+            );
+        }
+        assert_eq!(
+            tracker.get_percentile_where(50.0, |&value| value > 1000),
+            None
+        );
+        assert_eq!(tracker.get_percentile(), expected_p90);
+        assert!(tracker.verify_bucket_offset());
+    }
+}
@@ -0,0 +1,339 @@
+//! A Greenwald–Khanna summary answering any percentile within a chosen rank error.
+//!
+//! The summary keeps a sorted list of sample values, each with bounds on its rank among all
+//! inserted values. Neighbouring entries are combined whenever the combined bounds stay
+//! within `2 * epsilon * n`, so a query for rank `r` returns a value whose true rank is
+//! within `epsilon * n` of `r`. That guarantee is deterministic, unlike a random sample, and
+//! the summary holds `O(log(epsilon * n) / epsilon)` entries however the values arrive.
+//!
+//! Unlike the t-digest and DDSketch backends, it works for any `Ord` type, and only ever
+//! reports values that were inserted.
+
+use crate::{rank_for_percentile, ErrorBound, MergeError, QuantileEstimator};
+
+/// The default rank error, 0.1% of the number of values.
+pub const DEFAULT_EPSILON: f64 = 0.001;
+
+/// A sample value and the bounds on its rank.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry<T> {
+    value: T,
+
+    /// The minimum rank of this entry minus the minimum rank of the previous entry.
+    g: u64,
+
+    /// The maximum rank of this entry minus its minimum rank.
+    delta: u64,
+}
+
+/// An approximate percentile tracker with a deterministic rank error bound.
+///
+/// ```
+/// use percentiletracker::GkSummary;
+///
+/// let mut summary = GkSummary::new(99);
+/// for i in 0..100_000u64 {
+///     summary.insert((i * 7919) % 100_000);
+/// }
+/// let p99 = summary.get_percentile();
+/// assert!(p99.abs_diff(99_000) <= 100);
+/// assert!(summary.entry_count() < 2_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GkSummary<T> {
+    /// Entries in ascending order of value.
+    entries: Vec<Entry<T>>,
+
+    /// The maximum rank error as a fraction of the number of values.
+    epsilon: f64,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// Total number of values inserted.
+    count: u64,
+
+    /// Inserts since the entries were last compressed.
+    since_compress: u64,
+}
+
+impl<T> GkSummary<T>
+where
+    T: Ord + Clone,
+{
+    /// Creates an empty summary tracking the given percentile with the default epsilon.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        Self::with_epsilon(percentile, DEFAULT_EPSILON)
+    }
+
+    /// Creates an empty summary tracking the given percentile, answering every query within
+    /// `epsilon * n` ranks.
+    ///
+    /// Smaller epsilons keep more entries, improving accuracy at the cost of memory.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if epsilon is not
+    /// strictly between 0 and 0.5.
+    pub fn with_epsilon(percentile: usize, epsilon: f64) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        assert!(
+            epsilon > 0.0 && epsilon < 0.5,
+            "Epsilon must be between 0 and 0.5 exclusive, got {}",
+            epsilon
+        );
+        GkSummary {
+            entries: Vec::new(),
+            epsilon,
+            percentile,
+            count: 0,
+            since_compress: 0,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the maximum rank error as a fraction of the number of values.
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Returns the number of values inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of entries stored, which is the summary's memory.
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Inserts a value.
+    pub fn insert(&mut self, value: T) {
+        let position = self.entries.partition_point(|entry| entry.value <= value);
+        // A new minimum or maximum has an exact rank, anything else inherits the slack
+        let delta = if position == 0 || position == self.entries.len() {
+            0
+        } else {
+            self.band().saturating_sub(1)
+        };
+        self.entries.insert(position, Entry { value, g: 1, delta });
+        self.count += 1;
+        self.since_compress += 1;
+        if self.since_compress as f64 >= 1.0 / (2.0 * self.epsilon) {
+            self.compress();
+        }
+    }
+
+    /// Adds every value summarized by another summary into this one.
+    ///
+    /// The merged summary answers within the larger of the two epsilons, and adopts it. This
+    /// summary keeps its own percentile.
+    pub fn merge(&mut self, other: &GkSummary<T>) {
+        let left = std::mem::take(&mut self.entries);
+        let right = &other.entries;
+        let mut merged = Vec::with_capacity(left.len() + right.len());
+        let (mut i, mut j) = (0, 0);
+        // An entry's rank within its own summary is known up to its delta, and its rank among
+        // the other summary's values is known up to the span of the next entry there
+        while i < left.len() || j < right.len() {
+            let take_left = j == right.len() || (i < left.len() && left[i].value <= right[j].value);
+            let (entry, next_other) = if take_left {
+                i += 1;
+                (&left[i - 1], right.get(j))
+            } else {
+                j += 1;
+                (&other.entries[j - 1], left.get(i))
+            };
+            let slack = next_other.map_or(0, |next| (next.g + next.delta).saturating_sub(1));
+            merged.push(Entry {
+                value: entry.value.clone(),
+                g: entry.g,
+                delta: entry.delta + slack,
+            });
+        }
+        self.entries = merged;
+        self.count += other.count;
+        self.epsilon = self.epsilon.max(other.epsilon);
+        self.compress();
+    }
+
+    /// Returns the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the summary is empty.
+    pub fn get_percentile(&self) -> T {
+        self.value_at_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty summary")
+    }
+
+    /// Returns a value whose rank is within `epsilon * n` of a percentile (0-100), or `None`
+    /// if empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns a value whose rank is within `epsilon * n` of a zero-based rank, or `None` if
+    /// the rank is out of bounds.
+    pub fn value_at_rank(&self, rank: u64) -> Option<T> {
+        if rank >= self.count {
+            return None;
+        }
+        // The last entry whose maximum rank is within the allowed error above the target
+        let target = rank + 1;
+        let allowed = (self.epsilon * self.count as f64) as u64;
+        let mut min_rank = 0;
+        let mut answer = &self.entries[0];
+        for entry in &self.entries {
+            min_rank += entry.g;
+            if min_rank + entry.delta > target + allowed {
+                break;
+            }
+            answer = entry;
+        }
+        Some(answer.value.clone())
+    }
+
+    /// Returns the maximum rank span an entry may cover, `2 * epsilon * n`.
+    fn band(&self) -> u64 {
+        (2.0 * self.epsilon * self.count as f64) as u64
+    }
+
+    /// Combines each entry into its successor where the combined bounds stay within the band.
+    ///
+    /// The first and last entries are never removed, so the minimum and maximum stay exact.
+    fn compress(&mut self) {
+        self.since_compress = 0;
+        if self.entries.len() < 3 {
+            return;
+        }
+        let band = self.band();
+        let mut compressed: Vec<Entry<T>> = Vec::with_capacity(self.entries.len());
+        let last = self.entries.pop().expect("There are at least 3 entries");
+        // Walk from the largest value down, folding entries into the successor kept so far
+        let mut successor = last;
+        for entry in self.entries.drain(1..).rev() {
+            if entry.g + successor.g + successor.delta <= band {
+                successor.g += entry.g;
+            } else {
+                compressed.push(std::mem::replace(&mut successor, entry));
+            }
+        }
+        compressed.push(successor);
+        compressed.reverse();
+        self.entries.extend(compressed);
+    }
+}
+
+impl<T> QuantileEstimator<T> for GkSummary<T>
+where
+    T: Ord + Clone,
+{
+    fn insert(&mut self, value: T) {
+        GkSummary::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        GkSummary::value_at_rank(self, rank as u64)
+    }
+
+    /// Summaries with any epsilon can be merged, so this never fails.
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        GkSummary::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        ErrorBound::Rank(self.epsilon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    /// Asserts that every percentile answer's true rank is within the summary's bound.
+    fn assert_within_bound(summary: &GkSummary<u64>, sorted: &[u64]) {
+        let n = sorted.len() as f64;
+        let allowed = summary.epsilon() * n + 1.0;
+        for percentile in [0.0, 1.0, 25.0, 50.0, 90.0, 99.0, 99.9, 100.0] {
+            let value = summary.value_at_percentile(percentile).unwrap();
+            let target = rank_for_percentile(percentile, sorted.len()) as f64;
+            let lowest = sorted.partition_point(|&v| v < value) as f64;
+            let highest = sorted.partition_point(|&v| v <= value) as f64 - 1.0;
+            assert!(
+                lowest - allowed <= target && target <= highest + allowed,
+                "p{} answered {} with ranks {}..={}, target {}",
+                percentile,
+                value,
+                lowest,
+                highest,
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_rank_error_bound() {
+        let mut rng = SplitMix64(23);
+        let mut summary = GkSummary::with_epsilon(50, 0.01);
+        let mut values = Vec::new();
+        for _ in 0..50_000 {
+            let value = rng.below(1_000_000);
+            summary.insert(value);
+            values.push(value);
+        }
+        values.sort_unstable();
+        assert_within_bound(&summary, &values);
+        assert_eq!(summary.value_at_percentile(0.0), Some(values[0]));
+        assert_eq!(summary.value_at_percentile(100.0), values.last().copied());
+        assert!(summary.entry_count() < 1_000);
+        assert_eq!(summary.error_bound(), ErrorBound::Rank(0.01));
+    }
+
+    #[test]
+    fn test_merge_keeps_bound() {
+        let mut rng = SplitMix64(29);
+        let mut left = GkSummary::with_epsilon(90, 0.005);
+        let mut right = GkSummary::with_epsilon(10, 0.01);
+        let mut values = Vec::new();
+        for i in 0..40_000 {
+            // Skew the halves so they cover different ranges
+            let value = rng.below(100_000) + if i % 2 == 0 { 0 } else { 50_000 };
+            if i % 2 == 0 {
+                left.insert(value);
+            } else {
+                right.insert(value);
+            }
+            values.push(value);
+        }
+        left.merge(&right);
+        values.sort_unstable();
+        assert_eq!(left.count(), 40_000);
+        assert_eq!(left.epsilon(), 0.01);
+        assert_eq!(left.percentile(), 90);
+        assert_within_bound(&left, &values);
+    }
+}
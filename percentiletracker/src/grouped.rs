@@ -0,0 +1,220 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::PercentileTracker;
+
+/// A tracker and the last time it received a value.
+struct Group<T>
+where
+    T: Ord,
+{
+    tracker: PercentileTracker<T>,
+    last_insert: Instant,
+}
+
+/// One tracker per key, such as an endpoint, customer, or status code.
+///
+/// Trackers are created on the first insert for a key, and groups that stop receiving values
+/// can be dropped with [`evict_idle`](Self::evict_idle) so that short-lived keys don't
+/// accumulate forever.
+///
+/// ```
+/// use percentiletracker::GroupedPercentileTracker;
+///
+/// let mut latencies = GroupedPercentileTracker::new(50);
+/// for (endpoint, ms) in [("/login", 30u64), ("/search", 120), ("/login", 10), ("/login", 20)] {
+///     latencies.insert(endpoint, ms);
+/// }
+/// assert_eq!(latencies.get_percentile("/login"), Some(20));
+/// assert_eq!(latencies.get_percentile("/search"), Some(120));
+/// assert_eq!(latencies.get_percentile("/logout"), None);
+/// ```
+pub struct GroupedPercentileTracker<K, T>
+where
+    T: Ord,
+{
+    /// Builds the tracker for a newly seen key.
+    factory: Box<dyn Fn() -> PercentileTracker<T> + Send + Sync>,
+
+    /// The trackers, by key.
+    groups: HashMap<K, Group<T>>,
+}
+
+impl<K, T> GroupedPercentileTracker<K, T>
+where
+    K: Eq + Hash,
+    T: Ord,
+{
+    /// Creates an empty collection whose trackers track the given percentile.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        // Fail here rather than on the first insert
+        drop(PercentileTracker::<T>::new(percentile));
+        Self::with_factory(move || PercentileTracker::new(percentile))
+    }
+
+    /// Creates an empty collection that builds the tracker for each new key with `factory`.
+    ///
+    /// Use this to give every group the same builder configuration, e.g. a unit or a
+    /// reservoir to bound the memory of each group.
+    pub fn with_factory<F>(factory: F) -> Self
+    where
+        F: Fn() -> PercentileTracker<T> + Send + Sync + 'static,
+    {
+        GroupedPercentileTracker {
+            factory: Box::new(factory),
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Inserts a value into the tracker for a key, creating the tracker on first use.
+    pub fn insert(&mut self, key: K, value: T) {
+        self.insert_at(key, value, Instant::now());
+    }
+
+    fn insert_at(&mut self, key: K, value: T, now: Instant) {
+        let factory = &self.factory;
+        let group = self.groups.entry(key).or_insert_with(|| Group {
+            tracker: factory(),
+            last_insert: now,
+        });
+        group.tracker.insert(value);
+        group.last_insert = now;
+    }
+
+    /// Returns the number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns true if there are no groups.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Returns the tracker for a key, or `None` if the key has no values.
+    pub fn get<Q>(&self, key: &Q) -> Option<&PercentileTracker<T>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.groups.get(key).map(|group| &group.tracker)
+    }
+
+    /// Returns the tracker for a key mutably, or `None` if the key has no values.
+    ///
+    /// Values inserted through the returned tracker don't count as activity for
+    /// [`evict_idle`](Self::evict_idle).
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut PercentileTracker<T>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.groups.get_mut(key).map(|group| &mut group.tracker)
+    }
+
+    /// Returns the tracked percentile for a key, or `None` if the key has no values.
+    pub fn get_percentile<Q>(&self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        T: Clone,
+    {
+        self.get(key).map(PercentileTracker::get_percentile)
+    }
+
+    /// Removes a group, returning its tracker.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<PercentileTracker<T>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.groups.remove(key).map(|group| group.tracker)
+    }
+
+    /// Iterates over every group and its tracker, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &PercentileTracker<T>)> {
+        self.groups.iter().map(|(key, group)| (key, &group.tracker))
+    }
+
+    /// Iterates over the keys of every group, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.groups.keys()
+    }
+
+    /// Removes every group that hasn't received a value for at least `idle`, returning
+    /// them so their final state can be reported.
+    pub fn evict_idle(&mut self, idle: Duration) -> Vec<(K, PercentileTracker<T>)> {
+        self.evict_idle_at(idle, Instant::now())
+    }
+
+    fn evict_idle_at(&mut self, idle: Duration, now: Instant) -> Vec<(K, PercentileTracker<T>)> {
+        let (evicted, kept) = std::mem::take(&mut self.groups)
+            .into_iter()
+            .partition(|(_, group)| now.saturating_duration_since(group.last_insert) >= idle);
+        self.groups = kept;
+        evicted
+            .into_iter()
+            .map(|(key, group)| (key, group.tracker))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Unit;
+
+    #[test]
+    fn test_grouped_tracker() {
+        let mut grouped = GroupedPercentileTracker::with_factory(|| {
+            PercentileTracker::builder()
+                .percentile(90)
+                .unit(Unit::Milliseconds)
+                .build()
+                .unwrap()
+        });
+        assert!(grouped.is_empty());
+        for value in 0..100u64 {
+            grouped.insert(value % 4, value);
+        }
+        assert_eq!(grouped.len(), 4);
+        assert_eq!(grouped.get_percentile(&0), Some(88));
+        assert_eq!(grouped.get(&3).unwrap().unit(), Unit::Milliseconds);
+        let mut counts: Vec<_> = grouped
+            .iter()
+            .map(|(&key, tracker)| (key, tracker.count()))
+            .collect();
+        counts.sort();
+        assert_eq!(counts, vec![(0, 25), (1, 25), (2, 25), (3, 25)]);
+
+        grouped.get_mut(&1).unwrap().insert(1_000);
+        assert_eq!(grouped.get_percentile(&1), Some(93));
+        assert_eq!(grouped.remove(&2).map(|tracker| tracker.count()), Some(25));
+        assert!(grouped.get(&2).is_none());
+    }
+
+    #[test]
+    fn test_evict_idle() {
+        let mut grouped = GroupedPercentileTracker::new(50);
+        let start = Instant::now();
+        grouped.insert_at("old", 1i64, start);
+        grouped.insert_at("busy", 2, start);
+        grouped.insert_at("busy", 3, start + Duration::from_secs(50));
+
+        let evicted =
+            grouped.evict_idle_at(Duration::from_secs(60), start + Duration::from_secs(60));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, "old");
+        assert_eq!(evicted[0].1.get_percentile(), 1);
+        assert_eq!(grouped.keys().collect::<Vec<_>>(), vec![&"busy"]);
+
+        assert!(grouped.evict_idle(Duration::from_secs(3600)).is_empty());
+        assert_eq!(grouped.evict_idle(Duration::ZERO).len(), 1);
+        assert!(grouped.is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+//! An HDR-histogram style counting backend for bounded `u64` domains.
+//!
+//! Values are counted in buckets whose width grows with magnitude so that every value is
+//! recorded with a fixed number of significant decimal digits. With 3 significant digits,
+//! latencies from 1µs up to an hour fit in a few tens of thousands of counters: insertion is
+//! a couple of bit operations and an increment, and memory is fixed at construction no matter
+//! how many values are recorded. The price is that reported values are only exact to the
+//! configured precision.
+
+use crate::{rank_for_percentile, ErrorBound, MergeError, QuantileEstimator};
+
+/// A fixed-precision counting histogram for `u64` values in `0..=highest_trackable`.
+///
+/// ```
+/// use percentiletracker::HdrHistogram;
+///
+/// // Microsecond latencies up to an hour, with 3 significant digits
+/// let mut histogram = HdrHistogram::new(99, 3_600_000_000, 3);
+/// for micros in 1..=100_000 {
+///     histogram.insert(micros);
+/// }
+/// let p99 = histogram.get_percentile();
+/// assert!(p99.abs_diff(99_001) <= 99);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HdrHistogram {
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// The largest value that can be recorded without saturating.
+    highest_trackable: u64,
+
+    /// The number of significant decimal digits preserved (1-5).
+    significant_figures: u32,
+
+    /// `log2` of half the number of sub-buckets in each bucket.
+    sub_bucket_half_count_magnitude: u32,
+
+    /// A mask covering every sub-bucket index of the first bucket.
+    sub_bucket_mask: u64,
+
+    /// The number of values in each counting slot.
+    counts: Vec<u64>,
+
+    /// Total number of values recorded.
+    count: u64,
+
+    /// Number of values above `highest_trackable` that were recorded as `highest_trackable`.
+    saturated: u64,
+
+    /// Smallest value recorded.
+    min: u64,
+
+    /// Largest value recorded, after saturation.
+    max: u64,
+}
+
+impl HdrHistogram {
+    /// Creates an empty histogram.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, if `highest_trackable` is
+    /// less than 2, or if `significant_figures` is not between 1 and 5 inclusive.
+    pub fn new(percentile: usize, highest_trackable: u64, significant_figures: u32) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        assert!(
+            highest_trackable >= 2,
+            "Highest trackable value must be at least 2, got {}",
+            highest_trackable
+        );
+        assert!(
+            (1..=5).contains(&significant_figures),
+            "Significant figures must be between 1 and 5 inclusive, got {}",
+            significant_figures
+        );
+
+        // Enough sub-buckets that adjacent values at the bottom of a bucket differ by one unit
+        // in the last significant digit
+        let largest_single_unit = 2 * 10u64.pow(significant_figures);
+        let sub_bucket_count = largest_single_unit.next_power_of_two();
+        let sub_bucket_half_count_magnitude = sub_bucket_count.trailing_zeros() - 1;
+
+        let mut bucket_count = 1;
+        let mut smallest_untrackable = sub_bucket_count;
+        while smallest_untrackable <= highest_trackable {
+            bucket_count += 1;
+            if smallest_untrackable > u64::MAX / 2 {
+                break;
+            }
+            smallest_untrackable <<= 1;
+        }
+        let counts_len = (bucket_count + 1) * (sub_bucket_count as usize / 2);
+
+        HdrHistogram {
+            percentile,
+            highest_trackable,
+            significant_figures,
+            sub_bucket_half_count_magnitude,
+            sub_bucket_mask: sub_bucket_count - 1,
+            counts: vec![0; counts_len],
+            count: 0,
+            saturated: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the largest value that can be recorded without saturating.
+    pub fn highest_trackable(&self) -> u64 {
+        self.highest_trackable
+    }
+
+    /// Returns the number of significant decimal digits preserved.
+    pub fn significant_figures(&self) -> u32 {
+        self.significant_figures
+    }
+
+    /// Returns the number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no values have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of values that exceeded the trackable range.
+    pub fn saturated(&self) -> u64 {
+        self.saturated
+    }
+
+    /// Returns the number of counting slots, which fixes the histogram's memory.
+    pub fn slot_count(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Records a value.
+    ///
+    /// Values above [`highest_trackable`](Self::highest_trackable) are recorded as that value
+    /// and counted in [`saturated`](Self::saturated).
+    pub fn insert(&mut self, value: u64) {
+        let value = if value > self.highest_trackable {
+            self.saturated += 1;
+            self.highest_trackable
+        } else {
+            value
+        };
+        let index = self.counts_index(value);
+        self.counts[index] += 1;
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Adds every value recorded by another histogram into this one.
+    ///
+    /// This histogram keeps its own percentile.
+    ///
+    /// # Panics
+    /// Panics if the histograms have a different range or precision, since their slots don't line up.
+    pub fn merge(&mut self, other: &HdrHistogram) {
+        assert!(
+            self.is_compatible(other),
+            "Cannot merge histograms with different ranges or precisions"
+        );
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.saturated += other.saturated;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Returns the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the histogram is empty.
+    pub fn get_percentile(&self) -> u64 {
+        self.value_at_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty histogram")
+    }
+
+    /// Returns the value at an arbitrary percentile (0-100), or `None` if empty.
+    ///
+    /// The result is the highest value equivalent to the exact one at the configured
+    /// precision, clamped to the exact minimum and maximum recorded.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns the value at a zero-based rank, or `None` if the rank is out of bounds.
+    fn value_at_rank(&self, rank: u64) -> Option<u64> {
+        if rank >= self.count {
+            return None;
+        }
+        let mut seen = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if rank < seen {
+                let value = self.highest_equivalent_value(self.value_from_index(index));
+                return Some(value.clamp(self.min, self.max));
+            }
+        }
+        unreachable!("Rank {} is within the {} values", rank, self.count);
+    }
+
+    /// Returns true if another histogram's counting slots line up with this one's.
+    fn is_compatible(&self, other: &HdrHistogram) -> bool {
+        self.highest_trackable == other.highest_trackable
+            && self.significant_figures == other.significant_figures
+    }
+
+    /// Returns the bucket a value belongs to. Bucket `b` has sub-buckets of width `2^b`.
+    fn bucket_index(&self, value: u64) -> u32 {
+        let pow2_ceiling = 64 - (value | self.sub_bucket_mask).leading_zeros();
+        pow2_ceiling - (self.sub_bucket_half_count_magnitude + 1)
+    }
+
+    /// Returns the counting slot for a value.
+    fn counts_index(&self, value: u64) -> usize {
+        let bucket = self.bucket_index(value);
+        let sub_bucket = (value >> bucket) as usize;
+        let half_count = 1usize << self.sub_bucket_half_count_magnitude;
+        // Every bucket after the first shares its lower half of sub-buckets with the previous one
+        ((bucket as usize + 1) << self.sub_bucket_half_count_magnitude) + sub_bucket - half_count
+    }
+
+    /// Returns the lowest value that maps to a counting slot.
+    fn value_from_index(&self, index: usize) -> u64 {
+        let half_count = 1usize << self.sub_bucket_half_count_magnitude;
+        let mut bucket = (index >> self.sub_bucket_half_count_magnitude) as i64 - 1;
+        let mut sub_bucket = (index & (half_count - 1)) + half_count;
+        if bucket < 0 {
+            sub_bucket -= half_count;
+            bucket = 0;
+        }
+        (sub_bucket as u64) << bucket
+    }
+
+    /// Returns the highest value that shares a counting slot with `value`.
+    fn highest_equivalent_value(&self, value: u64) -> u64 {
+        let width = 1u64 << self.bucket_index(value);
+        value.saturating_add(width - 1)
+    }
+}
+
+impl QuantileEstimator<u64> for HdrHistogram {
+    fn insert(&mut self, value: u64) {
+        HdrHistogram::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<u64> {
+        HdrHistogram::value_at_rank(self, rank as u64)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        if !self.is_compatible(other) {
+            return Err(MergeError::IncompatibleParameters);
+        }
+        HdrHistogram::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        // Each value is counted in a slot spanning at most one unit in its last significant
+        // digit
+        ErrorBound::Relative(10f64.powi(-(self.significant_figures as i32)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdr_precision() {
+        let mut histogram = HdrHistogram::new(90, 3_600_000_000, 3);
+        let mut values: Vec<u64> = (0..100_000u64)
+            .map(|i| 1 + (i * 2_654_435_761) % 3_600_000_000)
+            .collect();
+        for &value in &values {
+            histogram.insert(value);
+        }
+        values.sort_unstable();
+
+        assert!(histogram.slot_count() < 50_000);
+        for percentile in [0.0, 1.0, 50.0, 90.0, 99.0, 99.9, 100.0] {
+            let exact = values[rank_for_percentile(percentile, values.len())];
+            let estimate = histogram.value_at_percentile(percentile).unwrap();
+            assert!(
+                estimate.abs_diff(exact) as f64 <= exact as f64 / 1000.0,
+                "p{} estimate {} is too far from {}",
+                percentile,
+                estimate,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn test_hdr_small_values_are_exact() {
+        let mut histogram = HdrHistogram::new(50, 1000, 2);
+        for value in 0..=100 {
+            histogram.insert(value);
+        }
+        assert_eq!(histogram.get_percentile(), 50);
+        assert_eq!(histogram.value_at_percentile(0.0), Some(0));
+        assert_eq!(histogram.value_at_percentile(100.0), Some(100));
+    }
+
+    #[test]
+    fn test_hdr_saturation_and_merge() {
+        let mut left = HdrHistogram::new(50, 1000, 2);
+        let mut right = HdrHistogram::new(99, 1000, 2);
+        left.insert(10);
+        right.insert(5000);
+        right.insert(20);
+        assert_eq!(right.saturated(), 1);
+        assert_eq!(right.value_at_percentile(100.0), Some(1000));
+
+        left.merge(&right);
+        assert_eq!(left.count(), 3);
+        assert_eq!(left.saturated(), 1);
+        assert_eq!(left.get_percentile(), 20);
+        assert_eq!(
+            HdrHistogram::new(50, 1000, 2).value_at_percentile(50.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Percentile time series.
+//!
+//! A tracker built with [`PercentileTrackerBuilder::history`] samples its tracked percentile
+//! as values arrive and keeps the most recent samples, so the same structure that aggregates
+//! a stream can also feed a sparkline of how the percentile moved over time.
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// When a tracker records its percentile into its history, and how much history it keeps.
+///
+/// A point is recorded as soon as either limit is reached. Both are only checked on insert,
+/// so a tracker that receives no values records no points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryPolicy {
+    /// Record once at least this many values have been inserted since the last point.
+    pub every_inserts: Option<u64>,
+
+    /// Record once at least this much time has passed since the last point.
+    pub interval: Option<Duration>,
+
+    /// The number of points kept. Older points are discarded first.
+    pub max_points: usize,
+}
+
+impl Default for HistoryPolicy {
+    fn default() -> Self {
+        HistoryPolicy {
+            every_inserts: None,
+            interval: Some(Duration::from_secs(1)),
+            max_points: 1024,
+        }
+    }
+}
+
+/// The tracked percentile at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryPoint<T> {
+    /// When the point was recorded.
+    pub at: Instant,
+
+    /// The number of values in the tracker when the point was recorded.
+    pub count: usize,
+
+    /// The tracked percentile when the point was recorded.
+    pub value: T,
+}
+
+/// The recorded points and the bookkeeping for when to record the next one.
+pub(crate) struct History<T> {
+    pub(crate) policy: HistoryPolicy,
+
+    /// Copies the percentile out of the tracker. Captured by the builder, since only there
+    /// is `T` known to be `Clone`.
+    clone: fn(&T) -> T,
+
+    /// The recorded points, oldest first.
+    points: VecDeque<HistoryPoint<T>>,
+
+    /// When the last point was recorded, or history was configured.
+    last_at: Instant,
+
+    /// Inserts since the last point was recorded.
+    inserts: u64,
+}
+
+impl<T> History<T> {
+    pub(crate) fn new(policy: HistoryPolicy, clone: fn(&T) -> T) -> Self {
+        History {
+            policy,
+            clone,
+            points: VecDeque::with_capacity(policy.max_points.min(1024)),
+            last_at: Instant::now(),
+            inserts: 0,
+        }
+    }
+
+    /// Counts an insert, and returns the current time if a point is due.
+    fn tick(&mut self) -> Option<Instant> {
+        self.inserts += 1;
+        let by_count = self
+            .policy
+            .every_inserts
+            .is_some_and(|every| self.inserts >= every);
+        if by_count {
+            return Some(Instant::now());
+        }
+        let interval = self.policy.interval?;
+        let now = Instant::now();
+        (now.duration_since(self.last_at) >= interval).then_some(now)
+    }
+
+    fn record(&mut self, at: Instant, count: usize, value: &T) {
+        if self.points.len() == self.policy.max_points {
+            self.points.pop_front();
+        }
+        self.points.push_back(HistoryPoint {
+            at,
+            count,
+            value: (self.clone)(value),
+        });
+        self.last_at = at;
+        self.inserts = 0;
+    }
+
+    /// Returns the bytes allocated for the recorded points.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.points.capacity() * size_of::<HistoryPoint<T>>()
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the recorded history, oldest point first.
+    ///
+    /// This is empty unless the tracker was built with [`PercentileTrackerBuilder::history`].
+    ///
+    /// ```
+    /// use percentiletracker::{HistoryPolicy, PercentileTracker};
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .percentile(90)
+    ///     .history(HistoryPolicy {
+    ///         every_inserts: Some(100),
+    ///         interval: None,
+    ///         max_points: 60,
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// for value in 0..300 {
+    ///     tracker.insert(value);
+    /// }
+    /// let sparkline: Vec<u64> = tracker.history().map(|point| point.value).collect();
+    /// assert_eq!(sparkline, vec![90, 180, 270]);
+    /// ```
+    pub fn history(&self) -> impl ExactSizeIterator<Item = &HistoryPoint<T>> + '_ {
+        self.history
+            .as_ref()
+            .map(|history| history.points.iter())
+            .unwrap_or_default()
+    }
+
+    /// Discards every recorded point, keeping history enabled.
+    pub fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.points.clear();
+        }
+    }
+
+    /// Counts an insert towards the history policy, recording a point if one is due.
+    pub(crate) fn tick_history(&mut self) {
+        let Some(at) = self.history.as_mut().and_then(History::tick) else {
+            return;
+        };
+        if self.count() == 0 {
+            return;
+        }
+        let count = self.count();
+        let mut history = self.history.take().expect("History was just ticked");
+        history.record(at, count, self.get_percentile_ref());
+        self.history = Some(history);
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Records the tracked percentile into a bounded time series as values are inserted.
+    ///
+    /// Recording a point rebalances the tracker, as a query would, and copies the percentile.
+    pub fn history(mut self, policy: HistoryPolicy) -> Self {
+        self.history = Some(History::new(policy, T::clone));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildError;
+
+    #[test]
+    fn test_history_by_count() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .history(HistoryPolicy {
+                every_inserts: Some(10),
+                interval: None,
+                max_points: 3,
+            })
+            .build()
+            .unwrap();
+        for value in 0..55i64 {
+            tracker.insert(value);
+        }
+        let points: Vec<_> = tracker
+            .history()
+            .map(|point| (point.count, point.value))
+            .collect();
+        assert_eq!(points, vec![(30, 15), (40, 20), (50, 25)]);
+        assert!(tracker
+            .history()
+            .zip(tracker.history().skip(1))
+            .all(|(earlier, later)| earlier.at <= later.at));
+
+        tracker.clear_history();
+        assert_eq!(tracker.history().len(), 0);
+        for value in 0..10 {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.history().len(), 1);
+    }
+
+    #[test]
+    fn test_history_by_interval() {
+        let mut tracker = PercentileTracker::builder()
+            .history(HistoryPolicy {
+                every_inserts: None,
+                interval: Some(Duration::ZERO),
+                max_points: 100,
+            })
+            .build()
+            .unwrap();
+        for value in 0..5u8 {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.history().len(), 5);
+        assert_eq!(PercentileTracker::<u8>::new(50).history().len(), 0);
+    }
+
+    #[test]
+    fn test_history_validation() {
+        let policy = HistoryPolicy {
+            max_points: 0,
+            ..HistoryPolicy::default()
+        };
+        assert_eq!(
+            PercentileTracker::<i64>::builder()
+                .history(policy)
+                .build()
+                .err(),
+            Some(BuildError::InvalidHistoryCapacity(0))
+        );
+    }
+}
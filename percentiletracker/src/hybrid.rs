@@ -0,0 +1,266 @@
+//! A tracker that is exact for small streams and switches to a sketch for large ones.
+//!
+//! [`HybridTracker`] stores every value in a [`PercentileTracker`] until it holds a chosen
+//! number of them, then moves them into a [`GkSummary`] and keeps summarizing from there.
+//! Most streams are small and get exact answers, while a runaway stream settles into the
+//! sketch's bounded memory instead of growing without limit.
+
+use crate::gk::{GkSummary, DEFAULT_EPSILON};
+use crate::{ErrorBound, MergeError, PercentileTracker, QuantileEstimator};
+
+/// Where a [`HybridTracker`]'s values are currently kept.
+enum Mode<T>
+where
+    T: Ord,
+{
+    /// Every value, exactly. Boxed since the tracker is much larger than a summary.
+    Exact(Box<PercentileTracker<T>>),
+
+    /// A summary with a bounded rank error.
+    Sketch(GkSummary<T>),
+}
+
+/// A percentile tracker that is exact up to a number of values, then approximate.
+///
+/// ```
+/// use percentiletracker::HybridTracker;
+///
+/// let mut latencies = HybridTracker::new(99, 10_000);
+/// for ms in 0..10_000u64 {
+///     latencies.insert(ms);
+/// }
+/// assert!(latencies.is_exact());
+/// assert_eq!(latencies.get_percentile(), 9_900);
+///
+/// for ms in 10_000..100_000 {
+///     latencies.insert(ms);
+/// }
+/// assert!(!latencies.is_exact());
+/// assert!(latencies.get_percentile().abs_diff(99_000) <= 100);
+/// ```
+pub struct HybridTracker<T>
+where
+    T: Ord,
+{
+    /// The current storage.
+    mode: Mode<T>,
+
+    /// The number of values above which the tracker switches to the sketch.
+    exact_limit: usize,
+
+    /// The rank error of the sketch once switched.
+    epsilon: f64,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+}
+
+impl<T> HybridTracker<T>
+where
+    T: Ord + Clone,
+{
+    /// Creates an empty tracker that stays exact for up to `exact_limit` values, and then
+    /// switches to a sketch with the default epsilon.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize, exact_limit: usize) -> Self {
+        Self::with_epsilon(percentile, exact_limit, DEFAULT_EPSILON)
+    }
+
+    /// Creates an empty tracker that stays exact for up to `exact_limit` values, and then
+    /// answers within `epsilon * n` ranks.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if epsilon is not
+    /// strictly between 0 and 0.5.
+    pub fn with_epsilon(percentile: usize, exact_limit: usize, epsilon: f64) -> Self {
+        // Validate the sketch's parameters now, rather than when the stream grows
+        GkSummary::<T>::with_epsilon(percentile, epsilon);
+        HybridTracker {
+            mode: Mode::Exact(Box::new(PercentileTracker::new(percentile))),
+            exact_limit,
+            epsilon,
+            percentile,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the number of values the tracker stays exact for.
+    pub fn exact_limit(&self) -> usize {
+        self.exact_limit
+    }
+
+    /// Returns true until the tracker has switched to the sketch.
+    pub fn is_exact(&self) -> bool {
+        matches!(self.mode, Mode::Exact(_))
+    }
+
+    /// Returns the number of values inserted.
+    pub fn len(&self) -> usize {
+        match &self.mode {
+            Mode::Exact(tracker) => tracker.len(),
+            Mode::Sketch(summary) => summary.count() as usize,
+        }
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts a value, switching to the sketch if the exact limit is exceeded.
+    pub fn insert(&mut self, value: T) {
+        match &mut self.mode {
+            Mode::Exact(tracker) => {
+                tracker.insert(value);
+                if tracker.len() > self.exact_limit {
+                    self.switch_to_sketch();
+                }
+            }
+            Mode::Sketch(summary) => summary.insert(value),
+        }
+    }
+
+    /// Adds every value from another tracker into this one, switching to the sketch if
+    /// either tracker already has or the combined count exceeds the exact limit.
+    pub fn merge(&mut self, other: &HybridTracker<T>) {
+        match &other.mode {
+            Mode::Exact(other_tracker) => match &mut self.mode {
+                Mode::Exact(tracker) => {
+                    tracker
+                        .merge(other_tracker)
+                        .expect("Hybrid trackers don't declare units");
+                    if tracker.len() > self.exact_limit {
+                        self.switch_to_sketch();
+                    }
+                }
+                Mode::Sketch(summary) => {
+                    for bucket in other_tracker.buckets.borrow().iter() {
+                        for value in &bucket.values {
+                            summary.insert(value.clone());
+                        }
+                    }
+                }
+            },
+            Mode::Sketch(other_summary) => {
+                self.switch_to_sketch();
+                if let Mode::Sketch(summary) = &mut self.mode {
+                    summary.merge(other_summary);
+                }
+            }
+        }
+    }
+
+    /// Returns the value at the tracked percentile, exact until the tracker has switched.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile(&self) -> T {
+        match &self.mode {
+            Mode::Exact(tracker) => tracker.get_percentile(),
+            Mode::Sketch(summary) => summary.get_percentile(),
+        }
+    }
+
+    /// Returns the value at a percentile (0-100), or `None` if empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        match &self.mode {
+            Mode::Exact(tracker) => QuantileEstimator::value_at_percentile(&**tracker, percentile),
+            Mode::Sketch(summary) => summary.value_at_percentile(percentile),
+        }
+    }
+
+    /// Moves every stored value into a sketch, if not already done.
+    fn switch_to_sketch(&mut self) {
+        if let Mode::Exact(tracker) = &mut self.mode {
+            let mut summary = GkSummary::with_epsilon(self.percentile, self.epsilon);
+            let tracker =
+                std::mem::replace(tracker, Box::new(PercentileTracker::new(self.percentile)));
+            for bucket in tracker.buckets.into_inner() {
+                for value in bucket.values {
+                    summary.insert(value);
+                }
+            }
+            self.mode = Mode::Sketch(summary);
+        }
+    }
+}
+
+impl<T> QuantileEstimator<T> for HybridTracker<T>
+where
+    T: Ord + Clone,
+{
+    fn insert(&mut self, value: T) {
+        HybridTracker::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        HybridTracker::len(self)
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        match &self.mode {
+            Mode::Exact(tracker) => tracker.value_at_rank(rank),
+            Mode::Sketch(summary) => summary.value_at_rank(rank as u64),
+        }
+    }
+
+    /// Hybrid trackers with any limits can be merged, so this never fails.
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        HybridTracker::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        match &self.mode {
+            Mode::Exact(_) => ErrorBound::Exact,
+            Mode::Sketch(summary) => QuantileEstimator::error_bound(summary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switches_at_limit() {
+        let mut tracker = HybridTracker::with_epsilon(50, 100, 0.01);
+        for value in 0..100u32 {
+            tracker.insert(value);
+        }
+        assert!(tracker.is_exact());
+        assert_eq!(tracker.error_bound(), ErrorBound::Exact);
+        tracker.insert(100);
+        assert!(!tracker.is_exact());
+        assert_eq!(tracker.len(), 101);
+        assert_eq!(tracker.error_bound(), ErrorBound::Rank(0.01));
+        assert!(tracker.get_percentile().abs_diff(50) <= 1);
+        assert_eq!(tracker.value_at_percentile(100.0), Some(100));
+    }
+
+    #[test]
+    fn test_merge_modes() {
+        let mut small = HybridTracker::new(50, 10);
+        let mut other = HybridTracker::new(50, 10);
+        for value in 0..6u32 {
+            small.insert(value);
+            other.insert(value + 6);
+        }
+        small.merge(&other);
+        assert!(!small.is_exact());
+        assert_eq!(small.len(), 12);
+
+        let mut exact = HybridTracker::new(50, 1_000);
+        exact.insert(3);
+        exact.merge(&small);
+        assert!(!exact.is_exact());
+        assert_eq!(exact.len(), 13);
+        assert_eq!(exact.get_percentile(), 5);
+    }
+}
@@ -0,0 +1,155 @@
+//! Hooks for observing the tracker's internal work.
+//!
+//! An [`Instrumentation`] installed with
+//! [`PercentileTrackerBuilder::instrumentation`](crate::PercentileTrackerBuilder::instrumentation)
+//! is called with a [`TrackerEvent`] every time the tracker splits or sorts a bucket or moves
+//! its percentile cursor, so the costs can be fed into an existing profiler. Without one,
+//! the tracker doesn't even read the clock.
+
+use std::time::{Duration, Instant};
+
+use crate::{Bucket, PercentileTracker, PercentileTrackerBuilder};
+
+/// Internal work performed by a tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackerEvent {
+    /// A bucket holding `len` values was split in two.
+    Split { len: usize, elapsed: Duration },
+
+    /// A bucket holding `len` values was sorted.
+    Sort { len: usize, elapsed: Duration },
+
+    /// The percentile cursor moved from one bucket index to another.
+    CursorMove { from: usize, to: usize },
+}
+
+/// Receives [`TrackerEvent`]s from a tracker.
+///
+/// This is implemented for closures, so a hook can be as simple as
+/// `|event| histogram.record(event)`.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use percentiletracker::{PercentileTracker, TrackerEvent};
+///
+/// let sorts = Arc::new(Mutex::new(0));
+/// let counter = Arc::clone(&sorts);
+/// let mut tracker = PercentileTracker::builder()
+///     .instrumentation(move |event| {
+///         if let TrackerEvent::Sort { .. } = event {
+///             *counter.lock().unwrap() += 1;
+///         }
+///     })
+///     .build()
+///     .unwrap();
+/// for value in [3u64, 1, 2] {
+///     tracker.insert(value);
+/// }
+/// tracker.get_percentile();
+/// assert_eq!(*sorts.lock().unwrap(), 1);
+/// ```
+pub trait Instrumentation: Send + Sync {
+    /// Called after the tracker performs some internal work.
+    fn record(&self, event: TrackerEvent);
+}
+
+impl<F> Instrumentation for F
+where
+    F: Fn(TrackerEvent) + Send + Sync,
+{
+    fn record(&self, event: TrackerEvent) {
+        self(event)
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Installs a hook that is called whenever the tracker splits or sorts a bucket or
+    /// moves its percentile cursor.
+    pub fn instrumentation(mut self, hook: impl Instrumentation + 'static) -> Self {
+        self.instrumentation = Some(Box::new(hook));
+        self
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Passes an event to the installed hook, if any.
+    pub(crate) fn record(&self, event: TrackerEvent) {
+        if let Some(hook) = &self.instrumentation {
+            hook.record(event);
+        }
+    }
+
+    /// Runs `work`, timing it and recording the event built from the elapsed time if a hook
+    /// is installed.
+    pub(crate) fn timed<R>(
+        &self,
+        work: impl FnOnce() -> R,
+        event: impl FnOnce(Duration) -> TrackerEvent,
+    ) -> R {
+        let Some(hook) = &self.instrumentation else {
+            return work();
+        };
+        let started = Instant::now();
+        let result = work();
+        hook.record(event(started.elapsed()));
+        result
+    }
+
+    /// Sorts a bucket with the tracker's sorting routine, if it isn't sorted already.
+    pub(crate) fn sort_bucket(&self, bucket: &mut Bucket<T>) {
+        if bucket.sorted {
+            return;
+        }
+        let len = bucket.len();
+        self.timed(
+            || bucket.ensure_sorted(self.sort_values),
+            |elapsed| TrackerEvent::Sort { len, elapsed },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_instrumentation_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .instrumentation(move |event| sink.lock().unwrap().push(event))
+            .build()
+            .unwrap();
+        for value in 0..100i64 {
+            tracker.insert(value);
+        }
+        assert!(events.lock().unwrap().is_empty());
+
+        assert_eq!(tracker.get_percentile(), 90);
+        let events = events.lock().unwrap();
+        let splits = events
+            .iter()
+            .filter(|event| matches!(event, TrackerEvent::Split { .. }))
+            .count();
+        // 100 values halve down to buckets of at most 8 in 4 splits
+        assert_eq!(splits, 4);
+        assert!(matches!(
+            events.first(),
+            Some(TrackerEvent::Split { len: 100, .. })
+        ));
+        assert!(events.contains(&TrackerEvent::CursorMove { from: 0, to: 3 }));
+        assert!(matches!(
+            events.last(),
+            Some(TrackerEvent::Sort { len, .. }) if *len <= 8
+        ));
+    }
+}
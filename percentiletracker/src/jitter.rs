@@ -0,0 +1,189 @@
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use crate::{PercentileTracker, Unit};
+
+/// Values whose difference from the previous value in a stream can be tracked.
+///
+/// The difference is always non-negative, so a drop and a rise of the same size count as the
+/// same jitter. Integers differ by their unsigned distance, and instants and durations by a
+/// [`Duration`].
+pub trait Successive: Copy {
+    /// The type of the difference between two values.
+    type Delta: Ord;
+
+    /// The unit of the differences, used when formatting reports.
+    const UNIT: Unit = Unit::None;
+
+    /// Returns the distance between this value and the one before it.
+    fn delta(self, previous: Self) -> Self::Delta;
+}
+
+macro_rules! impl_successive {
+    ($($t:ty => $d:ty),*) => {
+        $(
+            impl Successive for $t {
+                type Delta = $d;
+
+                fn delta(self, previous: Self) -> $d {
+                    self.abs_diff(previous)
+                }
+            }
+        )*
+    };
+}
+
+impl_successive!(
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize,
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => usize
+);
+
+impl Successive for Instant {
+    type Delta = Duration;
+    const UNIT: Unit = Unit::Nanoseconds;
+
+    fn delta(self, previous: Self) -> Duration {
+        if self >= previous {
+            self - previous
+        } else {
+            previous - self
+        }
+    }
+}
+
+impl Successive for Duration {
+    type Delta = Duration;
+    const UNIT: Unit = Unit::Nanoseconds;
+
+    fn delta(self, previous: Self) -> Duration {
+        self.abs_diff(previous)
+    }
+}
+
+/// A tracker of the differences between successive values of a stream, such as the
+/// inter-arrival jitter of packet timestamps.
+///
+/// Each recorded value is compared with the one before it, and the distance between them is
+/// inserted into the underlying [`PercentileTracker`], whose queries are all available
+/// through `Deref`. The first value only becomes the reference for the second.
+///
+/// ```
+/// use percentiletracker::JitterTracker;
+///
+/// // Arrival times in microseconds of packets sent every 20 ms
+/// let mut jitter = JitterTracker::new(99);
+/// for (seq, wobble) in [0, 150, -80, 30, 900, -20, 10].into_iter().enumerate() {
+///     jitter.record(seq as i64 * 20_000 + wobble);
+/// }
+/// assert_eq!(jitter.len(), 6);
+/// assert_eq!(jitter.get_percentile(), 20_870);
+/// ```
+pub struct JitterTracker<T>
+where
+    T: Successive,
+{
+    /// The last recorded value, or `None` before the first.
+    previous: Option<T>,
+
+    /// The differences between successive values.
+    tracker: PercentileTracker<T::Delta>,
+}
+
+impl<T> JitterTracker<T>
+where
+    T: Successive,
+{
+    /// Creates a tracker for the given percentile of the differences.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        match PercentileTracker::builder()
+            .percentile(percentile)
+            .unit(T::UNIT)
+            .build()
+        {
+            Ok(tracker) => JitterTracker {
+                previous: None,
+                tracker,
+            },
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Records the next value of the stream, inserting its distance from the previous one.
+    pub fn record(&mut self, value: T) {
+        if let Some(previous) = self.previous.replace(value) {
+            self.tracker.insert(value.delta(previous));
+        }
+    }
+
+    /// Returns the last recorded value.
+    pub fn previous(&self) -> Option<T> {
+        self.previous
+    }
+
+    /// Forgets the last recorded value, so the next one starts a new run without a
+    /// difference being inserted, e.g. after a gap in the stream.
+    pub fn restart(&mut self) {
+        self.previous = None;
+    }
+
+    /// Returns the underlying tracker of differences.
+    pub fn into_inner(self) -> PercentileTracker<T::Delta> {
+        self.tracker
+    }
+}
+
+impl<T> Deref for JitterTracker<T>
+where
+    T: Successive,
+{
+    type Target = PercentileTracker<T::Delta>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tracker
+    }
+}
+
+impl<T> DerefMut for JitterTracker<T>
+where
+    T: Successive,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tracker
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_tracker() {
+        let mut jitter = JitterTracker::new(50);
+        jitter.record(-5i32);
+        assert_eq!(jitter.len(), 0);
+        for value in [5, 2, 2, 10] {
+            jitter.record(value);
+        }
+        // Differences of 10, 3, 0 and 8
+        assert!(jitter.iter_sorted().eq(&[0u32, 3, 8, 10]));
+        assert_eq!(jitter.previous(), Some(10));
+
+        jitter.restart();
+        jitter.record(1_000);
+        assert_eq!(jitter.len(), 4);
+        jitter.record(999);
+        assert_eq!(jitter.min(), Some(0));
+        assert_eq!(jitter.count_of(&1), 1);
+
+        let start = Instant::now();
+        let mut arrivals = JitterTracker::new(99);
+        for ms in [0, 20, 45, 60] {
+            arrivals.record(start + Duration::from_millis(ms));
+        }
+        assert_eq!(arrivals.unit(), Unit::Nanoseconds);
+        assert_eq!(arrivals.get_percentile(), Duration::from_millis(25));
+    }
+}
@@ -0,0 +1,253 @@
+//! Undoing recent inserts.
+//!
+//! A tracker built with [`PercentileTrackerBuilder::undo_journal`] remembers its most recent
+//! inserts, so that measurements that turn out to be speculative can be taken back out with
+//! [`PercentileTracker::undo`] before they pollute the distribution.
+//!
+//! The journal keeps copies of the values rather than where they were stored, since splits
+//! move values between buckets after they're inserted. Undoing an insert removes one stored
+//! value equal to it, which is indistinguishable from removing the original.
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// The most recently inserted values, oldest first.
+pub(crate) struct Journal<T> {
+    /// The number of inserts that can be undone.
+    capacity: usize,
+
+    /// Copies inserted values into the journal. Captured by the builder, where `T: Clone`
+    /// is known.
+    clone: fn(&T) -> T,
+
+    entries: VecDeque<T>,
+}
+
+impl<T> Journal<T> {
+    pub(crate) fn new(capacity: usize, clone: fn(&T) -> T) -> Self {
+        Journal {
+            capacity,
+            clone,
+            entries: VecDeque::with_capacity(capacity.min(1024)),
+        }
+    }
+
+    /// Returns the number of inserts that can be undone.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Records an inserted value, forgetting the oldest one if the journal is full.
+    pub(crate) fn record(&mut self, value: &T) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((self.clone)(value));
+    }
+
+    /// Returns the bytes allocated for the recorded values.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.entries.capacity() * size_of::<T>()
+    }
+
+    /// Forgets every recorded insert.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Removes the last `n` inserted values, newest first, and returns how many were removed.
+    ///
+    /// Fewer than `n` are removed if the journal holds fewer inserts. Without
+    /// [`PercentileTrackerBuilder::undo_journal`] nothing is journaled, so nothing is removed.
+    ///
+    /// Values are matched by equality, like everything else the tracker stores. In reservoir
+    /// mode only values the reservoir kept are journaled, and undoing one that was evicted
+    /// since removes an equal stored value in its place if there is one, and is otherwise
+    /// skipped.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .undo_journal(16)
+    ///     .build()
+    ///     .unwrap();
+    /// for latency in [10, 20, 30] {
+    ///     tracker.insert(latency);
+    /// }
+    /// // The last request was cancelled, so its latency doesn't count
+    /// tracker.insert(5_000);
+    /// assert_eq!(tracker.undo(1), 1);
+    /// assert_eq!(tracker.max(), Some(30));
+    /// ```
+    pub fn undo(&mut self, n: usize) -> usize {
+        let mut removed = 0;
+        for _ in 0..n {
+            let Some(value) = self.journal.as_mut().and_then(|j| j.entries.pop_back()) else {
+                break;
+            };
+            if let Some((bucket_idx, index)) = self.find_value(&value) {
+                self.remove_from_bucket(bucket_idx, index);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Returns the number of inserts that can currently be undone.
+    pub fn undoable(&self) -> usize {
+        self.journal
+            .as_ref()
+            .map_or(0, |journal| journal.entries.len())
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Journals the last `capacity` inserts so that they can be removed with
+    /// [`PercentileTracker::undo`].
+    ///
+    /// Each insert also copies the value into the journal. [`build`](Self::build) fails if
+    /// `capacity` is zero.
+    pub fn undo_journal(mut self, capacity: usize) -> Self {
+        self.journal = Some(Journal::new(capacity, T::clone));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    #[test]
+    fn test_undo_restores_distribution() {
+        let mut rng = SplitMix64(7);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .min_index()
+            .undo_journal(100)
+            .build()
+            .unwrap();
+        let mut expected = PercentileTracker::new(90);
+        for _ in 0..500 {
+            let value = rng.below(50);
+            tracker.insert(value);
+            expected.insert(value);
+        }
+        tracker.get_percentile();
+        for _ in 0..80 {
+            tracker.insert(rng.below(1_000));
+        }
+        assert_eq!(tracker.undoable(), 100);
+
+        assert_eq!(tracker.undo(80), 80);
+        assert_eq!(tracker.count(), 500);
+        assert!(tracker.verify_bucket_offset());
+        assert_eq!(tracker.get_percentile(), expected.get_percentile());
+        assert!(tracker.iter_sorted().eq(expected.iter_sorted()));
+
+        // Only 20 journaled inserts remain
+        assert_eq!(tracker.undo(50), 20);
+        assert_eq!(tracker.count(), 480);
+        assert_eq!(tracker.undo(1), 0);
+    }
+
+    #[test]
+    fn test_undo_after_eviction() {
+        let mut tracker = PercentileTracker::builder()
+            .reservoir(1)
+            .undo_journal(100)
+            .build()
+            .unwrap();
+        for value in 0..100u32 {
+            tracker.insert(value);
+        }
+        // Only the one stored value is left to remove
+        assert_eq!(tracker.undo(100), 1);
+        assert!(tracker.is_empty());
+
+        let mut tracker = PercentileTracker::builder()
+            .reservoir(2)
+            .undo_journal(2)
+            .build()
+            .unwrap();
+        tracker.insert(5u32);
+        tracker.insert(5);
+        while tracker.count_of(&9) == 0 {
+            tracker.insert(9);
+        }
+        // The 9 evicted one of the 5s, but the other one stands in for it
+        assert_eq!(tracker.count_of(&5), 1);
+        assert_eq!(tracker.undo(2), 2);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_undo_without_journal() {
+        let mut tracker = PercentileTracker::new(50);
+        tracker.insert(1u8);
+        assert_eq!(tracker.undoable(), 0);
+        assert_eq!(tracker.undo(1), 0);
+        assert_eq!(tracker.count(), 1);
+    }
+
+    #[test]
+    fn test_undo_everything() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .undo_journal(8)
+            .build()
+            .unwrap();
+        tracker.insert(3u32);
+        tracker.insert(1);
+        tracker.get_percentile();
+        assert_eq!(tracker.undo(2), 2);
+        assert!(tracker.is_empty());
+        assert_eq!(
+            tracker.try_get_percentile(),
+            Err(crate::PercentileError::Empty)
+        );
+
+        // The emptied tracker carries on like a fresh one
+        tracker.insert(7);
+        assert_eq!(tracker.get_percentile(), 7);
+        assert!(tracker.verify_bucket_offset());
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_undo_everything_then_query() {
+        let mut tracker = PercentileTracker::builder()
+            .undo_journal(8)
+            .build()
+            .unwrap();
+        tracker.insert(3u32);
+        tracker.insert(1);
+        tracker.get_percentile();
+        tracker.undo(2);
+        // Panics like a query on a tracker that never held a value
+        tracker.get_percentile();
+    }
+
+    #[test]
+    fn test_empty_journal_rejected() {
+        assert_eq!(
+            PercentileTracker::<u8>::builder()
+                .undo_journal(0)
+                .build()
+                .err(),
+            Some(crate::BuildError::InvalidJournalCapacity(0))
+        );
+    }
+}
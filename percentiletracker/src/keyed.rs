@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::ops::Deref;
+
+use crate::PercentileTracker;
+
+/// Types that are ordered by a key extracted from them, rather than by implementing `Ord`.
+///
+/// ```
+/// use std::time::Duration;
+/// use percentiletracker::{ByKey, KeyedTracker, SortKey};
+///
+/// #[derive(Clone)]
+/// struct Request {
+///     path: String,
+///     latency: Duration,
+/// }
+///
+/// impl SortKey for Request {
+///     type Key = Duration;
+///
+///     fn sort_key(&self) -> Duration {
+///         self.latency
+///     }
+/// }
+///
+/// let mut tracker = KeyedTracker::new(50);
+/// for (path, ms) in [("/a", 30), ("/b", 10), ("/c", 20)] {
+///     tracker.insert(ByKey(Request {
+///         path: path.to_string(),
+///         latency: Duration::from_millis(ms),
+///     }));
+/// }
+/// assert_eq!(tracker.get_percentile().path, "/c");
+/// ```
+pub trait SortKey {
+    /// The key the values are ordered by.
+    type Key: Ord;
+
+    /// Extracts the key. This is called on every comparison, so it should be cheap.
+    fn sort_key(&self) -> Self::Key;
+}
+
+/// Wraps a value so it's ordered by its [`SortKey`], letting it be stored in a tracker.
+///
+/// Two wrapped values compare equal whenever their keys are equal, even if the values
+/// themselves differ.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByKey<T>(pub T);
+
+impl<T> ByKey<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ByKey<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: SortKey> PartialEq for ByKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.sort_key() == other.0.sort_key()
+    }
+}
+
+impl<T: SortKey> Eq for ByKey<T> {}
+
+impl<T: SortKey> PartialOrd for ByKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: SortKey> Ord for ByKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.sort_key().cmp(&other.0.sort_key())
+    }
+}
+
+/// A tracker of values ordered by their [`SortKey`], which keeps the whole values retrievable.
+pub type KeyedTracker<T> = PercentileTracker<ByKey<T>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Request {
+        id: u32,
+        latency_us: u64,
+    }
+
+    impl SortKey for Request {
+        type Key = u64;
+
+        fn sort_key(&self) -> u64 {
+            self.latency_us
+        }
+    }
+
+    #[test]
+    fn test_keyed_tracker() {
+        let mut tracker = KeyedTracker::new(90);
+        for id in 0..100 {
+            tracker.insert(ByKey(Request {
+                id,
+                latency_us: u64::from((id * 37) % 100),
+            }));
+        }
+        let p90 = tracker.get_percentile();
+        assert_eq!(p90.latency_us, 90);
+        assert_eq!(p90.into_inner().id, 90 * 73 % 100);
+
+        let worst: Vec<u32> = tracker.top_k(2).into_iter().map(|r| r.id).collect();
+        assert_eq!(worst, vec![99 * 73 % 100, 98 * 73 % 100]);
+    }
+}
@@ -0,0 +1,440 @@
+//! A KLL sketch answering any percentile within a rank error set by its size.
+//!
+//! The sketch keeps a stack of compactors. Inserted values go into the lowest one, and
+//! each value in compactor `h` stands for `2^h` inserted values. When the sketch holds more
+//! values than its capacity, the lowest full compactor is sorted and every second value,
+//! starting at a random one of the first two, is promoted to the compactor above while the
+//! rest are dropped. Compactors further below the top are given geometrically less room, so
+//! the sketch holds about `3k` values however many are inserted, and its rank error shrinks
+//! roughly as `1 / k`.
+//!
+//! Like the Greenwald–Khanna backend it works for any `Ord` type and only ever reports
+//! values that were inserted. It's also what a [`PercentileTracker`](crate::PercentileTracker)
+//! keeps its values in when built with [`Storage::Kll`](crate::Storage::Kll).
+
+use std::mem;
+
+use crate::rng::SplitMix64;
+use crate::{rank_for_percentile, ErrorBound, MergeError, QuantileEstimator};
+
+/// The default size, which keeps the rank error around 1.3%.
+pub const DEFAULT_K: usize = 200;
+
+/// The smallest supported size. Smaller compactors can't keep the rank error meaningful.
+pub const MIN_K: usize = 8;
+
+/// The seed used to choose which half of a compactor is promoted.
+const COMPACTION_SEED: u64 = 0x6B11;
+
+/// An approximate percentile tracker that holds `O(k)` values for any stream.
+///
+/// ```
+/// use percentiletracker::KllSketch;
+///
+/// let mut sketch = KllSketch::new(99);
+/// for i in 0..100_000u64 {
+///     sketch.insert((i * 7919) % 100_000);
+/// }
+/// let p99 = sketch.get_percentile();
+/// assert!(p99.abs_diff(99_000) <= 2_000);
+/// assert_eq!(sketch.min(), Some(&0));
+/// assert!(sketch.retained() < 1_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct KllSketch<T> {
+    /// Compactors from the lowest up. A value in compactor `h` stands for `2^h` inserts.
+    levels: Vec<Vec<T>>,
+
+    /// The smallest value inserted, kept outside the compactors so it's never dropped.
+    min: Option<T>,
+
+    /// The largest value inserted, once there are at least two.
+    max: Option<T>,
+
+    /// The size parameter, which is the capacity of the top compactor.
+    k: usize,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// Total number of values inserted.
+    count: u64,
+
+    /// Chooses which half of a compactor is promoted.
+    rng: SplitMix64,
+}
+
+impl<T> KllSketch<T>
+where
+    T: Ord,
+{
+    /// Creates an empty sketch tracking the given percentile with the default size.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        Self::with_k(percentile, DEFAULT_K)
+    }
+
+    /// Creates an empty sketch tracking the given percentile, whose top compactor holds `k`
+    /// values.
+    ///
+    /// Larger sizes keep more values, improving accuracy at the cost of memory.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if `k` is below
+    /// [`MIN_K`].
+    pub fn with_k(percentile: usize, k: usize) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        assert!(k >= MIN_K, "k must be at least {}, got {}", MIN_K, k);
+        KllSketch {
+            levels: vec![Vec::new()],
+            min: None,
+            max: None,
+            k,
+            percentile,
+            count: 0,
+            rng: SplitMix64(COMPACTION_SEED),
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the size parameter.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of values inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of values held, which is the sketch's memory.
+    pub fn retained(&self) -> usize {
+        let extremes = self.min.iter().count() + self.max.iter().count();
+        extremes + self.levels.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Returns the smallest value inserted, or `None` if empty. This is always exact.
+    pub fn min(&self) -> Option<&T> {
+        self.min.as_ref()
+    }
+
+    /// Returns the largest value inserted, or `None` if empty. This is always exact.
+    pub fn max(&self) -> Option<&T> {
+        self.max.as_ref().or(self.min.as_ref())
+    }
+
+    /// Returns true if sketches can be merged, i.e. they have the same size.
+    pub fn is_compatible(&self, other: &KllSketch<T>) -> bool {
+        self.k == other.k
+    }
+
+    /// Inserts a value.
+    pub fn insert(&mut self, value: T) {
+        self.count += 1;
+        let Some(min) = &mut self.min else {
+            self.min = Some(value);
+            return;
+        };
+        let Some(max) = &mut self.max else {
+            self.max = Some(if value < *min {
+                mem::replace(min, value)
+            } else {
+                value
+            });
+            return;
+        };
+        // A new extreme takes the place of the old one, which joins the compactors
+        let value = if value < *min {
+            mem::replace(min, value)
+        } else if value > *max {
+            mem::replace(max, value)
+        } else {
+            value
+        };
+        self.levels[0].push(value);
+        self.compress();
+    }
+
+    /// Returns a reference to a value whose rank is close to a zero-based rank, or `None`
+    /// if the rank is out of bounds.
+    pub fn value_ref_at_rank(&self, rank: u64) -> Option<&T> {
+        if rank >= self.count {
+            return None;
+        }
+        let mut weighted: Vec<(&T, u64)> = self
+            .min
+            .iter()
+            .chain(&self.max)
+            .map(|value| (value, 1))
+            .collect();
+        for (level, values) in self.levels.iter().enumerate() {
+            weighted.extend(values.iter().map(|value| (value, 1 << level)));
+        }
+        weighted.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        // Compaction preserves the total weight, so the walk always reaches the rank
+        let mut below = 0;
+        weighted.into_iter().find_map(|(value, weight)| {
+            below += weight;
+            (below > rank).then_some(value)
+        })
+    }
+
+    /// Returns the rank error the sketch stays within with 99% confidence.
+    ///
+    /// Until the first compaction every value is held and answers are exact. After that
+    /// this is `2.296 / k^0.9723`, the fit to measured errors published with the Apache
+    /// DataSketches implementation, e.g. about 1.3% for the default size.
+    pub fn error_bound(&self) -> ErrorBound {
+        if self.levels.len() == 1 {
+            return ErrorBound::Exact;
+        }
+        ErrorBound::Rank(2.296 / (self.k as f64).powf(0.9723))
+    }
+
+    /// Returns the capacity of a compactor, which shrinks by 2/3 per level below the top.
+    fn capacity(&self, level: usize) -> usize {
+        let depth = self.levels.len() - 1 - level;
+        let capacity = (self.k as f64 * (2.0f64 / 3.0).powi(depth as i32)).ceil() as usize;
+        capacity.max(2)
+    }
+
+    /// Compacts the lowest full compactor until the sketch fits within its capacity.
+    fn compress(&mut self) {
+        loop {
+            let held: usize = self.levels.iter().map(Vec::len).sum();
+            let capacity: usize = (0..self.levels.len())
+                .map(|level| self.capacity(level))
+                .sum();
+            if held <= capacity {
+                return;
+            }
+            // Some compactor is over its capacity, since all of them together are
+            let level = (0..self.levels.len())
+                .find(|&level| self.levels[level].len() >= self.capacity(level))
+                .expect("A compactor is full");
+            self.compact(level);
+        }
+    }
+
+    /// Promotes every second value of a compactor to the one above, dropping the others.
+    ///
+    /// An odd value out stays behind, so the total weight is unchanged.
+    fn compact(&mut self, level: usize) {
+        if level + 1 == self.levels.len() {
+            self.levels.push(Vec::new());
+        }
+        let mut values = mem::take(&mut self.levels[level]);
+        values.sort_unstable();
+        if values.len() % 2 == 1 {
+            let odd = values.pop().expect("An odd number of values is not empty");
+            self.levels[level].push(odd);
+        }
+        let offset = (self.rng.next() & 1) as usize;
+        let promoted = values.into_iter().skip(offset).step_by(2);
+        self.levels[level + 1].extend(promoted);
+    }
+}
+
+impl<T> KllSketch<T>
+where
+    T: Ord + Clone,
+{
+    /// Adds every value summarized by another sketch into this one.
+    ///
+    /// This sketch keeps its own percentile.
+    ///
+    /// # Panics
+    /// Panics if the sketches have a different size, since their error bounds differ.
+    pub fn merge(&mut self, other: &KllSketch<T>) {
+        assert!(
+            self.is_compatible(other),
+            "Cannot merge sketches with sizes {} and {}",
+            self.k,
+            other.k
+        );
+        for value in other.min.iter().chain(&other.max) {
+            self.insert(value.clone());
+        }
+        for (level, values) in other.levels.iter().enumerate() {
+            if level == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level].extend(values.iter().cloned());
+            self.count += (values.len() as u64) << level;
+        }
+        self.compress();
+    }
+
+    /// Returns the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the sketch is empty.
+    pub fn get_percentile(&self) -> T {
+        self.value_at_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty sketch")
+    }
+
+    /// Returns a value whose rank is close to a percentile (0-100), or `None` if empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns a value whose rank is close to a zero-based rank, or `None` if the rank is
+    /// out of bounds.
+    pub fn value_at_rank(&self, rank: u64) -> Option<T> {
+        self.value_ref_at_rank(rank).cloned()
+    }
+}
+
+impl<T> QuantileEstimator<T> for KllSketch<T>
+where
+    T: Ord + Clone,
+{
+    fn insert(&mut self, value: T) {
+        KllSketch::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        KllSketch::value_at_rank(self, rank as u64)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        if !self.is_compatible(other) {
+            return Err(MergeError::IncompatibleParameters);
+        }
+        KllSketch::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        KllSketch::error_bound(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that every percentile answer's true rank is within twice the sketch's bound,
+    /// which the 99% confidence bound stays within for these seeded inputs.
+    fn assert_within_bound(sketch: &KllSketch<u64>, sorted: &[u64]) {
+        let ErrorBound::Rank(error) = sketch.error_bound() else {
+            panic!("the sketch has compacted");
+        };
+        let n = sorted.len() as f64;
+        let allowed = 2.0 * error * n + 1.0;
+        for percentile in [0.0, 1.0, 25.0, 50.0, 90.0, 99.0, 99.9, 100.0] {
+            let value = sketch.value_at_percentile(percentile).unwrap();
+            let target = rank_for_percentile(percentile, sorted.len()) as f64;
+            let lowest = sorted.partition_point(|&v| v < value) as f64;
+            let highest = sorted.partition_point(|&v| v <= value) as f64 - 1.0;
+            assert!(
+                lowest - allowed <= target && target <= highest + allowed,
+                "p{} answered {} with ranks {}..={}, target {}",
+                percentile,
+                value,
+                lowest,
+                highest,
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_until_compacted() {
+        let mut sketch = KllSketch::with_k(50, 64);
+        for value in (0..60u64).rev() {
+            sketch.insert(value);
+        }
+        assert_eq!(sketch.error_bound(), ErrorBound::Exact);
+        assert_eq!(sketch.retained(), 60);
+        for rank in 0..60 {
+            assert_eq!(sketch.value_at_rank(rank), Some(rank));
+        }
+        assert_eq!(sketch.value_at_rank(60), None);
+        assert_eq!(sketch.get_percentile(), 30);
+    }
+
+    #[test]
+    fn test_rank_error_bound() {
+        let mut rng = SplitMix64(31);
+        let mut sketch = KllSketch::new(50);
+        let mut values = Vec::new();
+        for _ in 0..200_000 {
+            let value = rng.below(1_000_000);
+            sketch.insert(value);
+            values.push(value);
+        }
+        values.sort_unstable();
+        assert_eq!(sketch.count(), 200_000);
+        assert_within_bound(&sketch, &values);
+        assert_eq!(sketch.min(), values.first());
+        assert_eq!(sketch.max(), values.last());
+        assert_eq!(sketch.value_at_percentile(0.0), Some(values[0]));
+        assert_eq!(sketch.value_at_percentile(100.0), values.last().copied());
+        assert!(sketch.retained() <= 3 * DEFAULT_K + 64);
+    }
+
+    #[test]
+    fn test_merge_keeps_bound() {
+        let mut rng = SplitMix64(37);
+        let mut left = KllSketch::with_k(90, 100);
+        let mut right = KllSketch::with_k(10, 100);
+        let mut values = Vec::new();
+        for i in 0..80_000 {
+            // Skew the halves so they cover different ranges
+            let value = rng.below(100_000) + if i % 2 == 0 { 0 } else { 50_000 };
+            if i % 2 == 0 {
+                left.insert(value);
+            } else {
+                right.insert(value);
+            }
+            values.push(value);
+        }
+        left.merge(&right);
+        values.sort_unstable();
+        assert_eq!(left.count(), 80_000);
+        assert_eq!(left.percentile(), 90);
+        assert_eq!(left.min(), values.first());
+        assert_eq!(left.max(), values.last());
+        assert_within_bound(&left, &values);
+
+        let mut other = KllSketch::with_k(90, 200);
+        assert_eq!(
+            QuantileEstimator::merge(&mut other, &left),
+            Err(MergeError::IncompatibleParameters)
+        );
+    }
+
+    #[test]
+    fn test_single_value() {
+        let mut sketch = KllSketch::new(99);
+        assert_eq!(sketch.value_at_rank(0), None);
+        sketch.insert("only");
+        assert_eq!(sketch.min(), Some(&"only"));
+        assert_eq!(sketch.max(), Some(&"only"));
+        assert_eq!(sketch.get_percentile(), "only");
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use crate::{PercentileTracker, Unit};
+
+/// A tracker of [`Duration`]s, with helpers for timing code.
+///
+/// Every query of the underlying [`PercentileTracker`] is available through `Deref`, and
+/// values are displayed scaled to a readable unit.
+///
+/// ```
+/// use std::time::Duration;
+/// use percentiletracker::LatencyTracker;
+///
+/// let mut latencies = LatencyTracker::new(99);
+/// latencies.record(Duration::from_micros(1500));
+/// {
+///     let _timer = latencies.start();
+///     // ... the work being timed ...
+/// }
+/// assert_eq!(latencies.count_of(&Duration::from_micros(1500)), 1);
+/// println!("{}", latencies); // e.g. "p99=1.50 ms (n=2)"
+/// ```
+pub struct LatencyTracker {
+    tracker: PercentileTracker<Duration>,
+}
+
+impl LatencyTracker {
+    /// Creates a tracker for the given percentile.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        match PercentileTracker::builder()
+            .percentile(percentile)
+            .unit(Unit::Nanoseconds)
+            .build()
+        {
+            Ok(tracker) => LatencyTracker { tracker },
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Records a duration.
+    pub fn record(&mut self, duration: Duration) {
+        self.tracker.insert(duration);
+    }
+
+    /// Records the time elapsed since `start`, and returns it.
+    pub fn record_since(&mut self, start: Instant) -> Duration {
+        let elapsed = start.elapsed();
+        self.record(elapsed);
+        elapsed
+    }
+
+    /// Starts a timer that records the elapsed time when it's stopped or dropped.
+    pub fn start(&mut self) -> LatencyTimer<'_> {
+        LatencyTimer {
+            tracker: self,
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the underlying tracker.
+    pub fn into_inner(self) -> PercentileTracker<Duration> {
+        self.tracker
+    }
+}
+
+impl Deref for LatencyTracker {
+    type Target = PercentileTracker<Duration>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tracker
+    }
+}
+
+impl DerefMut for LatencyTracker {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tracker
+    }
+}
+
+/// Displays the tracked percentile scaled to a readable unit, e.g. `p99=1.50 ms (n=1000)`.
+impl fmt::Display for LatencyTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tracker.fmt(f)
+    }
+}
+
+/// A running timer from [`LatencyTracker::start`]. The elapsed time is recorded once, when
+/// the timer is stopped or dropped.
+pub struct LatencyTimer<'a> {
+    tracker: &'a mut LatencyTracker,
+    start: Instant,
+}
+
+impl LatencyTimer<'_> {
+    /// Stops the timer, recording and returning the elapsed time.
+    pub fn stop(self) -> Duration {
+        let elapsed = self.start.elapsed();
+        // Skip the drop handler so the time isn't recorded twice
+        let mut timer = std::mem::ManuallyDrop::new(self);
+        timer.tracker.record(elapsed);
+        elapsed
+    }
+}
+
+impl Drop for LatencyTimer<'_> {
+    fn drop(&mut self) {
+        self.tracker.record_since(self.start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_tracker() {
+        let mut latencies = LatencyTracker::new(90);
+        assert_eq!(latencies.to_string(), "p90=- (n=0)");
+
+        for micros in 1..=10 {
+            latencies.record(Duration::from_micros(micros * 250));
+        }
+        assert_eq!(latencies.get_percentile(), Duration::from_micros(2500));
+        assert_eq!(latencies.to_string(), "p90=2.50 ms (n=10)");
+
+        let stopped = latencies.start().stop();
+        {
+            let _timer = latencies.start();
+        }
+        latencies.record_since(Instant::now());
+        // Other timings can land on the same duration, so only the stopped one is certain
+        assert!(latencies.count_of(&stopped) >= 1);
+        assert_eq!(latencies.into_inner().memory_usage().len, 13);
+    }
+}
@@ -1,12 +1,219 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ord;
 
+mod accuracy;
+#[cfg(feature = "background")]
+mod actor;
+mod alert;
+mod anomaly;
+mod arc;
+mod backend;
+#[cfg(feature = "background")]
+mod background;
+mod budget;
+mod builder;
+mod bulk;
+mod checkpoint;
+mod compare;
+mod counting;
+#[cfg(feature = "ddsketch")]
+pub mod ddsketch;
+mod epoch;
+mod exact;
+mod export;
+mod fallible;
+mod filter;
+#[cfg(feature = "gk")]
+pub mod gk;
+mod grouped;
+#[cfg(feature = "hdr")]
+pub mod hdr;
+mod history;
+#[cfg(feature = "gk")]
+mod hybrid;
+mod instrument;
+mod jitter;
+mod journal;
+mod keyed;
+#[cfg(feature = "kll")]
+pub mod kll;
+mod latency;
+mod median;
+mod memory;
+mod merge;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+mod min_index;
+mod moments;
+mod numeric;
+mod observer;
+#[cfg(feature = "shadow-oracle")]
+mod oracle;
+mod outliers;
+mod persistent;
+mod prometheus;
+mod quantile;
+#[cfg(feature = "radix-sort")]
+mod radix;
+mod range;
+mod rank;
+mod render;
+mod replica;
+mod reservoir;
+mod rng;
+mod sampling;
+mod shared;
+mod snapshot;
+#[cfg(feature = "soak")]
+pub mod soak;
+#[cfg(feature = "tracing")]
+pub mod spans;
+mod sparse;
+#[cfg(feature = "spill")]
+pub mod spill;
+mod split;
+mod stats;
+mod storage;
+#[cfg(feature = "num-traits")]
+mod sum;
+mod summary;
+mod tail;
+#[cfg(feature = "tdigest")]
+pub mod tdigest;
+mod units;
+mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use accuracy::ErrorBound;
+#[cfg(feature = "background")]
+pub use actor::ActorHandle;
+pub use alert::{AlertEvent, Crossing};
+pub use anomaly::{Anomaly, DEFAULT_ANOMALY_WARM_UP};
+pub use arc::ArcTracker;
+pub use backend::QuantileEstimator;
+/// The former name of [`QuantileEstimator`], kept so existing code still compiles.
+#[doc(hidden)]
+pub use backend::QuantileEstimator as QuantileBackend;
+#[cfg(feature = "background")]
+pub use background::BackgroundTracker;
+pub use builder::{BuildError, PercentileTrackerBuilder};
+pub use compare::{DistributionDiff, PercentileDelta};
+pub use counting::{CountingKey, CountingTracker, MAX_COUNTING_DOMAIN};
+#[cfg(feature = "ddsketch")]
+pub use ddsketch::DDSketch;
+pub use epoch::EpochTracker;
+pub use exact::ExactTracker;
+pub use fallible::PercentileError;
+#[cfg(feature = "gk")]
+pub use gk::GkSummary;
+pub use grouped::GroupedPercentileTracker;
+#[cfg(feature = "hdr")]
+pub use hdr::HdrHistogram;
+pub use history::{HistoryPoint, HistoryPolicy};
+#[cfg(feature = "gk")]
+pub use hybrid::HybridTracker;
+pub use instrument::{Instrumentation, TrackerEvent};
+pub use jitter::{JitterTracker, Successive};
+pub use keyed::{ByKey, KeyedTracker, SortKey};
+#[cfg(feature = "kll")]
+pub use kll::KllSketch;
+pub use latency::{LatencyTimer, LatencyTracker};
+pub use median::MedianTracker;
+pub use memory::MemoryUsage;
+pub use merge::MergeError;
+#[cfg(feature = "metrics")]
+pub use metrics::{MetricsHub, MetricsResponse};
+pub use numeric::Numeric;
+pub use observer::InsertEvent;
+pub use persistent::PersistentTracker;
+pub use prometheus::PrometheusFormat;
+pub use quantile::QuantileMethod;
+#[cfg(feature = "radix-sort")]
+pub use radix::RadixKey;
+pub use range::RangePolicy;
+pub use replica::{ReadReplica, RefreshPolicy};
+pub use sampling::Sampling;
+pub use shared::SharedPercentileTracker;
+pub use snapshot::{FrozenSnapshot, QuantileMovement, SummaryDelta, DELTA_PERCENTILES};
+#[cfg(feature = "tracing")]
+pub use spans::{SpanDurationLayer, SpanDurations, SpanPercentiles};
+pub use sparse::SparseTracker;
+#[cfg(feature = "spill")]
+pub use spill::{SpillValue, SpillingTracker};
+pub use stats::{BucketInfo, TrackerStats};
+pub use storage::Storage;
+pub use summary::Summary;
+pub use tail::TailTracker;
+#[cfg(feature = "tdigest")]
+pub use tdigest::TDigest;
+pub use units::{Dimension, Unit};
+pub use verify::InvariantViolation;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmTracker;
+
+// The default maximum bucket size.
 // This was handtuned over a few timing runs. It's not perfect, but it's good enough.
 // Also confusingly, this number seems to not have much impact if it isn't pathological.
 // I haven't tested but I suspect it's because other operations dominate the runtime.
 const MAX_BUCKET_SIZE: usize = 64;
 
-/// A container for a subset of values with a common property - all values are greater than or equal to min_value.
+/// The integer type used internally for value counts and the percentile cursor.
+///
+/// With the `compact-indices` feature this is a `u32`, which halves the size of the
+/// bookkeeping on 64-bit targets at the cost of limiting a tracker to `u32::MAX` values.
+#[cfg(feature = "compact-indices")]
+type Rank = u32;
+#[cfg(not(feature = "compact-indices"))]
+type Rank = usize;
+
+/// Converts a count or position into the internal [`Rank`] type.
+///
+/// # Panics
+/// Panics if the value doesn't fit, which can only happen with the `compact-indices` feature.
+fn to_rank(value: usize) -> Rank {
+    Rank::try_from(value).expect("Tracker size exceeds the capacity of its rank type")
+}
+
+/// Converts an internal [`Rank`] back into a `usize`.
+// The cast is only a no-op without the compact-indices feature
+#[allow(clippy::unnecessary_cast)]
+fn from_rank(rank: Rank) -> usize {
+    rank as usize
+}
+
+/// Returns the bucket at an index the caller has checked against the bucket list's length.
+///
+/// With the `unsafe-fast` feature the bounds check is skipped, and only checked in debug
+/// builds. Only pass indices proven in bounds where they're computed, never the cursor or
+/// anything else that depends on the tracker's invariants, since a bug elsewhere or an
+/// inconsistent `Ord` would then be undefined behaviour.
+#[inline(always)]
+fn bucket_at<T: Ord>(buckets: &[Bucket<T>], idx: usize) -> &Bucket<T> {
+    #[cfg(feature = "unsafe-fast")]
+    {
+        debug_assert!(idx < buckets.len());
+        // SAFETY: callers only pass indices checked against the length of the same list
+        unsafe { buckets.get_unchecked(idx) }
+    }
+    #[cfg(not(feature = "unsafe-fast"))]
+    &buckets[idx]
+}
+
+/// Mutable version of [`bucket_at`].
+#[inline(always)]
+fn bucket_at_mut<T: Ord>(buckets: &mut [Bucket<T>], idx: usize) -> &mut Bucket<T> {
+    #[cfg(feature = "unsafe-fast")]
+    {
+        debug_assert!(idx < buckets.len());
+        // SAFETY: callers only pass indices checked against the length of the same list
+        unsafe { buckets.get_unchecked_mut(idx) }
+    }
+    #[cfg(not(feature = "unsafe-fast"))]
+    &mut buckets[idx]
+}
+
+/// A container for a subset of values with a common property - all values are greater than or equal to its minimum.
 ///
 /// The bucket structure enables efficient percentile calculation by:
 /// - Grouping values with similar magnitudes together
@@ -14,17 +221,21 @@ const MAX_BUCKET_SIZE: usize = 64;
 /// - Tracking minimum values to enable binary search across buckets
 ///
 /// Buckets store their values in a vector and track whether the values are sorted.
-/// They also cache the minimum value for efficient bucket location.
+/// They also track where their minimum and maximum values are, so that both can be read in
+/// O(1) without requiring `T: Clone`.
 struct Bucket<T>
 where
-    T: Clone + Ord,
+    T: Ord,
 {
-    /// The minimum value in this bucket, cached for efficient comparisons.
-    min_value: T,
-
     /// The collection of values stored in this bucket.
     values: Vec<T>,
 
+    /// Index of the minimum value in `values`, used for efficient bucket location.
+    min_idx: usize,
+
+    /// Index of the maximum value in `values`.
+    max_idx: usize,
+
     /// Flag indicating whether the values are currently sorted.
     /// This allows us to avoid unnecessary sorting operations.
     sorted: bool,
@@ -32,7 +243,7 @@ where
 
 impl<T> Bucket<T>
 where
-    T: Clone + Ord,
+    T: Ord,
 {
     /// Creates a new bucket containing a single value.
     ///
@@ -43,17 +254,60 @@ where
     /// * `value` - The initial value to store in the bucket
     fn new(value: T) -> Self {
         Bucket {
-            min_value: value.clone(),
             values: vec![value],
+            min_idx: 0,
+            max_idx: 0,
             sorted: true,
         }
     }
 
+    /// Creates a bucket holding a run of values.
+    ///
+    /// # Parameters
+    /// * `values` - The values to store in the bucket, at least one
+    /// * `sorted` - Whether the values are already in ascending order
+    fn from_run(values: Vec<T>, sorted: bool) -> Self {
+        let mut bucket = Bucket {
+            max_idx: values.len() - 1,
+            values,
+            min_idx: 0,
+            sorted,
+        };
+        if !sorted {
+            bucket.locate_extremes();
+        }
+        bucket
+    }
+
+    /// Cuts values that are partitioned into runs of `run_len` into one bucket per run.
+    ///
+    /// # Parameters
+    /// * `values` - The values, each run holding no value larger than any in a later run
+    /// * `run_len` - The number of values in every run but the last
+    /// * `sorted` - Whether the values are already in ascending order
+    fn cut_runs(mut values: Vec<T>, run_len: usize, sorted: bool) -> Vec<Self> {
+        // Cut from the back, so each split only moves the run being cut off
+        let mut buckets = Vec::with_capacity(values.len().div_ceil(run_len));
+        while !values.is_empty() {
+            let start = (values.len() - 1) / run_len * run_len;
+            buckets.push(Bucket::from_run(values.split_off(start), sorted));
+        }
+        buckets.reverse();
+        buckets
+    }
+
     /// Returns the minimum value stored in this bucket.
     ///
-    /// This is an O(1) operation as the minimum value is cached.
+    /// This is an O(1) operation as the position of the minimum value is tracked.
     fn min(&self) -> &T {
-        &self.min_value
+        &self.values[self.min_idx]
+    }
+
+    /// Returns the maximum value stored in this bucket.
+    ///
+    /// This is an O(1) operation as the position of the maximum value is tracked.
+    fn max(&self) -> &T {
+        &self.values[self.max_idx]
     }
 
     /// Returns the number of values stored in this bucket.
@@ -63,36 +317,72 @@ where
 
     /// Adds a new value to this bucket.
     ///
-    /// After pushing a new value, the bucket is marked as unsorted.
-    /// Note that this does not update the minimum value of the bucket, which must be
-    /// done separately if needed.
+    /// After pushing a new value, the bucket is marked as unsorted, and the tracked minimum
+    /// and maximum are updated if the value is a new extreme.
     ///
     /// # Parameters
     /// * `num` - The value to add to the bucket
     fn push(&mut self, num: T) {
+        if &num < self.min() {
+            self.min_idx = self.values.len();
+        } else if &num > self.max() {
+            self.max_idx = self.values.len();
+        }
         self.values.push(num);
         self.sorted = false;
     }
 
-    /// Updates the minimum value of this bucket.
+    /// Removes the value at the given index, replacing it with the last value.
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    fn swap_remove(&mut self, index: usize) -> T {
+        let last = self.values.len() - 1;
+        let removed = self.values.swap_remove(index);
+        self.sorted = false;
+        if index == self.min_idx || index == self.max_idx {
+            self.locate_extremes();
+        } else {
+            if self.min_idx == last {
+                self.min_idx = index;
+            }
+            if self.max_idx == last {
+                self.max_idx = index;
+            }
+        }
+        removed
+    }
+
+    /// Adds a new value to this bucket, keeping it sorted if it already is.
     ///
-    /// This method only updates the cached minimum value and does not check if the
-    /// provided value is actually the minimum value in the bucket.
+    /// This is O(bucket size) for a sorted bucket, which is cheaper than sorting it again later.
     ///
     /// # Parameters
-    /// * `new_min` - The new minimum value to set
-    fn update_min_value(&mut self, new_min: T) {
-        self.min_value = new_min;
+    /// * `num` - The value to add to the bucket
+    fn insert_sorted(&mut self, num: T) {
+        if !self.sorted {
+            self.push(num);
+            return;
+        }
+        let position = self.values.partition_point(|value| value <= &num);
+        self.values.insert(position, num);
+        self.min_idx = 0;
+        self.max_idx = self.values.len() - 1;
     }
 
     /// Ensures that the values in this bucket are sorted.
     ///
     /// If the bucket is already marked as sorted, this is a no-op. Otherwise,
     /// it sorts the values in the bucket and marks it as sorted.
-    fn ensure_sorted(&mut self) {
+    ///
+    /// # Parameters
+    /// * `sort_values` - The tracker's sorting routine
+    fn ensure_sorted(&mut self, sort_values: fn(&mut [T])) {
         if !self.sorted {
-            self.values.sort_unstable();
+            sort_values(&mut self.values);
             self.sorted = true;
+            self.min_idx = 0;
+            self.max_idx = self.values.len() - 1;
         }
     }
 
@@ -107,6 +397,36 @@ where
         &self.values[index]
     }
 
+    /// Partitions the values so that the value at `index` is the one that would be there if
+    /// the bucket were sorted, and returns it.
+    ///
+    /// This is a no-op on a sorted bucket, and otherwise leaves the bucket unsorted.
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    fn select_nth(&mut self, index: usize) -> &T {
+        if !self.sorted {
+            self.values.select_nth_unstable(index);
+            self.locate_extremes();
+        }
+        &self.values[index]
+    }
+
+    /// Finds the positions of the minimum and maximum values after the values were reordered.
+    fn locate_extremes(&mut self) {
+        let mut min_idx = 0;
+        let mut max_idx = 0;
+        for (idx, value) in self.values.iter().enumerate() {
+            if value < &self.values[min_idx] {
+                min_idx = idx;
+            } else if value > &self.values[max_idx] {
+                max_idx = idx;
+            }
+        }
+        self.min_idx = min_idx;
+        self.max_idx = max_idx;
+    }
+
     /// Splits this bucket at its median value, returning a new bucket containing the upper half.
     ///
     /// This method uses the `select_nth_unstable` algorithm to efficiently find the median
@@ -123,24 +443,42 @@ where
         let mid_idx = self.values.len() / 2;
         self.values.select_nth_unstable(mid_idx);
 
-        // Get the pivot value (the element at the middle position)
-        let pivot_value = self.values[mid_idx].clone();
-
-        // Split at the pivot position
+        // Split at the pivot position, which leaves the pivot as the first value of the upper half
         let upper_values = self.values.split_off(mid_idx);
 
         // Mark this bucket as unsorted
         self.sorted = false;
+        self.locate_extremes();
 
         // Create and return the new bucket
-        Bucket {
-            min_value: pivot_value,
+        let mut upper = Bucket {
             values: upper_values,
+            min_idx: 0,
+            max_idx: 0,
             sorted: false,
-        }
+        };
+        upper.locate_extremes();
+        upper
     }
 }
 
+/// Converts a percentile in the range 0-100 into a zero-based rank among `count` sorted values.
+///
+/// This uses the same nearest-rank rule as the tracked percentile, clamped so that the
+/// 100th percentile maps to the largest value.
+fn rank_for_percentile(percentile: f64, count: usize) -> usize {
+    let rank = (percentile * count as f64 / 100.0) as usize;
+    rank.min(count.saturating_sub(1))
+}
+
+/// Returns the zero-based rank of an integer percentile (0-100) among `count` sorted values.
+///
+/// The product is widened to `u128`, so it can't overflow for any count. The result is at
+/// most `count`, so narrowing it back is lossless.
+fn target_rank(percentile: usize, count: usize) -> usize {
+    (percentile as u128 * count as u128 / 100) as usize
+}
+
 /// A data structure for efficiently tracking percentiles of a stream of values.
 ///
 /// PercentileTracker maintains a collection of buckets that partition the data space,
@@ -154,31 +492,101 @@ where
 /// When buckets grow too large, they are split to maintain performance characteristics.
 pub struct PercentileTracker<T>
 where
-    T: Clone + Ord,
+    T: Ord,
 {
     /// Collection of buckets that store the values in partitioned ranges.
     buckets: RefCell<Vec<Bucket<T>>>,
 
     /// Total number of values inserted into the tracker.
-    total_count: usize,
+    total_count: Rank,
+
+    /// Number of values ever added, including any that sampling, a memory budget or a
+    /// reservoir discarded. Unlike the stored count, this never decreases.
+    added: u64,
 
     /// Index of the bucket that currently contains the percentile value.
-    percentile_bucket_idx: RefCell<usize>,
+    percentile_bucket_idx: Cell<Rank>,
 
     /// Number of values in all buckets before the percentile bucket.
     /// This is used to calculate the offset into the percentile bucket.
-    percentile_bucket_offset: RefCell<usize>,
+    percentile_bucket_offset: Cell<Rank>,
 
     /// The percentile to track (0-100)
     percentile: usize,
 
+    /// The size above which the percentile bucket is split.
+    max_bucket_size: usize,
+
+    /// The unit of the stored values, used when formatting them for reports.
+    unit: Unit,
+
+    /// How interpolated percentile queries estimate values.
+    quantile_method: QuantileMethod,
+
+    /// Sampling state if the tracker records only 1 in N inserts.
+    sampler: Option<sampling::Sampler>,
+
+    /// Downsampling state if the tracker has a memory budget.
+    downsampler: Option<budget::Downsampler>,
+
+    /// Sampling state if the tracker keeps a bounded reservoir instead of every value.
+    reservoir: Option<reservoir::Reservoir>,
+
+    /// Sorts the values of a bucket, `sort_unstable` unless the builder chose otherwise.
+    sort_values: fn(&mut [T]),
+
+    /// A flat copy of the bucket minimums, if the builder enabled it.
+    min_index: RefCell<Option<min_index::MinIndex<T>>>,
+
+    /// Running mean and variance, seeded the first time they're queried.
+    moments: RefCell<Option<moments::Moments<T>>>,
+
+    /// Running sum of the values, seeded the first time it's queried.
+    #[cfg(feature = "num-traits")]
+    sum: RefCell<Option<sum::RunningSum<T>>>,
+
+    /// A sorted copy of every value that queries are checked against, if enabled.
+    #[cfg(feature = "shadow-oracle")]
+    oracle: Option<oracle::ShadowOracle<T>>,
+
+    /// Receives events about the tracker's internal work, if installed.
+    instrumentation: Option<Box<dyn instrument::Instrumentation>>,
+
+    /// The most recent checkpoints of the stored values.
+    checkpoints: checkpoint::Checkpoints<T>,
+
+    /// The recorded percentile time series, if the builder enabled it.
+    history: Option<history::History<T>>,
+
+    /// The accepted range of inserted values, if the builder set one.
+    range_filter: Option<range::RangeFilter<T>>,
+
+    /// Callbacks fired when the tracked percentile crosses a threshold.
+    alerts: Vec<alert::Alert<T>>,
+
+    /// The percentile and warm-up count used by `insert_and_check`.
+    anomaly_threshold: anomaly::AnomalyThreshold,
+
+    /// Callbacks run on every kept insert, if any are registered.
+    observers: Option<observer::Observers<T>>,
+
+    /// Copies of the most recent inserts, if the builder enabled undo.
+    journal: Option<journal::Journal<T>>,
+
+    /// The sketch holding the values instead of the buckets, if the builder chose one.
+    #[cfg(feature = "kll")]
+    sketch: Option<kll::KllSketch<T>>,
+
+    /// Whether each insert rebalances immediately instead of leaving it to the next query.
+    amortized_rebalancing: bool,
+
     /// Flag to track if rebalancing is needed
-    needs_rebalancing: RefCell<bool>,
+    needs_rebalancing: Cell<bool>,
 }
 
 impl<T> PercentileTracker<T>
 where
-    T: Clone + Ord,
+    T: Ord,
 {
     /// Creates a new, empty PercentileTracker.
     ///
@@ -186,80 +594,198 @@ where
     ///
     /// # Parameters
     /// * `percentile` - The percentile to track (0-100)
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
     pub fn new(percentile: usize) -> Self {
+        match Self::builder().percentile(percentile).build() {
+            Ok(tracker) => tracker,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Returns a builder for configuring a tracker with non-default options.
+    pub fn builder() -> PercentileTrackerBuilder<T> {
+        PercentileTrackerBuilder::new()
+    }
+
+    /// Returns the percentile the tracker tracks.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the number of values stored in the tracker.
+    ///
+    /// This counts every value kept, so it excludes values dropped by sampling, the accepted
+    /// range or a full reservoir.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::new(99);
+    /// assert!(tracker.is_empty());
+    /// for latency_ms in [12u64, 48, 7] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.len(), 3);
+    /// ```
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return sketch.count() as usize;
+        }
+        self.count()
+    }
+
+    /// Returns true if the tracker holds no values, so it has no percentile to query.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Changes the tracked percentile, keeping every stored value.
+    ///
+    /// The cursor walks to the new percentile on the next rebalance, which only visits the
+    /// buckets between the old and new positions, so switching a dashboard from p95 to p99
+    /// costs about as much as one query.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::new(95);
+    /// for latency_ms in 0..1_000u64 {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.get_percentile(), 950);
+    /// tracker.set_percentile(99).unwrap();
+    /// assert_eq!(tracker.get_percentile(), 990);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`BuildError::InvalidPercentile`] and leaves the tracker unchanged if the
+    /// percentile is not between 1 and 99 inclusive.
+    pub fn set_percentile(&mut self, percentile: usize) -> Result<(), BuildError> {
         if !(1..=99).contains(&percentile) {
-            panic!(
-                "Percentile must be between 1 and 99 inclusive, got {}",
-                percentile
-            );
+            return Err(BuildError::InvalidPercentile(percentile));
         }
-        PercentileTracker {
-            buckets: RefCell::new(Vec::new()),
-            total_count: 0,
-            percentile_bucket_idx: RefCell::new(0),
-            percentile_bucket_offset: RefCell::new(0),
-            percentile,
-            needs_rebalancing: RefCell::new(false),
+        self.percentile = percentile;
+        if self.count() > 0 {
+            self.needs_rebalancing.set(true);
+            if self.amortized_rebalancing {
+                self.rebalance();
+            }
         }
+        Ok(())
     }
 
     /// Inserts a new value into the tracker.
     ///
     /// This method only handles the insertion of the value into the appropriate bucket
-    /// without rebalancing or sorting. Rebalancing will happen lazily when get_percentile is called.
+    /// without rebalancing or sorting. Rebalancing will happen lazily when get_percentile is called,
+    /// unless the tracker was built with
+    /// [`amortized_rebalancing`](PercentileTrackerBuilder::amortized_rebalancing).
     ///
     /// # Parameters
     /// * `num` - The value to insert
     ///
     /// # Edge Cases
     /// - If this is the first value inserted, it becomes the target percentile
+    /// - In reservoir mode the value may be discarded, or replace a random stored value
     pub fn insert(&mut self, num: T) {
+        self.added += 1;
+        if !self.admit_sample() || !self.admit_under_budget() {
+            return;
+        }
+        let Some(num) = self.screen(num) else {
+            return;
+        };
+        if self.admit_to_reservoir() {
+            if let Some(journal) = &mut self.journal {
+                journal.record(&num);
+            }
+            let observed = self
+                .observers
+                .as_ref()
+                .map(|observers| (observers.clone)(&num));
+            self.insert_admitted(num);
+            self.enforce_budget();
+            if let Some(value) = observed {
+                self.notify_observers(value);
+            }
+        }
+        if self.history.is_some() {
+            self.tick_history();
+        }
+    }
+
+    /// Places a value that the reservoir, if any, decided to keep.
+    fn insert_admitted(&mut self, num: T) {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &mut self.sketch {
+            sketch.insert(num);
+            return;
+        }
+        self.observe(&num);
+
         let mut buckets = self.buckets.borrow_mut();
         if buckets.is_empty() {
+            if let Some(index) = self.min_index.get_mut() {
+                index.insert(0, &num);
+            }
             buckets.push(Bucket::new(num));
-            self.total_count += 1;
+            self.total_count = to_rank(self.count() + 1);
             return;
         }
 
-        let bucket_idx = match buckets.binary_search_by(|bucket| bucket.min().cmp(&num)) {
+        let search = match self.min_index.get_mut() {
+            Some(index) => index.search(&num),
+            None => buckets.binary_search_by(|bucket| bucket.min().cmp(&num)),
+        };
+        let bucket_idx = match search {
             Ok(idx) => idx,
             Err(idx) => idx,
         };
-        self.total_count += 1;
+        self.total_count = to_rank(self.count() + 1);
 
         // Handle insertion
-        let inserted_into;
-        if bucket_idx >= buckets.len() {
-            if let Some(last_bucket) = buckets.last_mut() {
-                last_bucket.push(num);
-                inserted_into = buckets.len() - 1;
-            } else {
-                buckets.push(Bucket::new(num));
-                inserted_into = 0;
+        let inserted_into = if bucket_idx >= buckets.len() {
+            buckets.len() - 1
+        } else if bucket_idx == 0 && bucket_at(&buckets, 0).min() > &num {
+            // Lower than the first bucket, so it goes in the first bucket and becomes its new minimum
+            if let Some(index) = self.min_index.get_mut() {
+                index.update(0, &num);
             }
-        } else if bucket_idx == 0 && buckets[bucket_idx].min() > &num {
-            // Lower than the first bucket, so we need to add to the first bucket and update the min value
-            inserted_into = 0;
-            buckets[inserted_into].push(num.clone());
-            buckets[inserted_into].update_min_value(num);
-        } else if &num == buckets[bucket_idx].min() {
-            inserted_into = bucket_idx;
-            buckets[inserted_into].push(num);
+            0
+        } else if &num == bucket_at(&buckets, bucket_idx).min() {
+            bucket_idx
         } else if bucket_idx == 0 {
-            // This scenario should be captured by the above conditions
+            // This scenario should be captured by the above conditions, unless `Ord` is
+            // inconsistent, so it stays checked even with the `unsafe-fast` feature
             panic!();
         } else {
-            inserted_into = bucket_idx - 1;
-            buckets[inserted_into].push(num);
-        }
+            bucket_idx - 1
+        };
 
-        let current_percentile_bucket_idx = *self.percentile_bucket_idx.borrow();
+        let (current_percentile_bucket_idx, current_percentile_bucket_offset) = self.cursor();
+        if self.amortized_rebalancing && inserted_into == current_percentile_bucket_idx {
+            // Keep the critical bucket sorted so queries don't have to sort it
+            bucket_at_mut(&mut buckets, inserted_into).insert_sorted(num);
+        } else {
+            bucket_at_mut(&mut buckets, inserted_into).push(num);
+        }
         if inserted_into < current_percentile_bucket_idx {
-            *self.percentile_bucket_offset.borrow_mut() += 1;
+            self.set_cursor(
+                current_percentile_bucket_idx,
+                current_percentile_bucket_offset + 1,
+            );
         }
 
         // Mark that rebalancing is needed
-        *self.needs_rebalancing.borrow_mut() = true;
+        self.needs_rebalancing.set(true);
+
+        if self.amortized_rebalancing {
+            drop(buckets);
+            self.rebalance();
+        }
     }
 
     /// Performs all necessary rebalancing operations to ensure the percentile can be computed correctly.
@@ -271,16 +797,22 @@ where
     ///
     /// This is called lazily by get_percentile() when needed.
     fn rebalance(&self) {
-        if !*self.needs_rebalancing.borrow() {
+        if !self.needs_rebalancing.get() {
             return;
         }
 
         let mut buckets = self.buckets.borrow_mut();
+        if buckets.is_empty() {
+            // Removals can empty a tracker, which leaves nothing to rebalance
+            self.set_cursor(0, 0);
+            self.needs_rebalancing.set(false);
+            return;
+        }
 
         // Update indices to point to new percentile position
         let target_pos = self.get_target_pos();
-        let mut percentile_bucket_idx = *self.percentile_bucket_idx.borrow();
-        let mut percentile_bucket_offset = *self.percentile_bucket_offset.borrow();
+        let (mut percentile_bucket_idx, mut percentile_bucket_offset) = self.cursor();
+        let initial_bucket_idx = percentile_bucket_idx;
 
         if target_pos >= percentile_bucket_offset {
             let mut offset_into_bucket = target_pos - percentile_bucket_offset;
@@ -297,13 +829,19 @@ where
         }
 
         // Store updated indices
-        *self.percentile_bucket_idx.borrow_mut() = percentile_bucket_idx;
-        *self.percentile_bucket_offset.borrow_mut() = percentile_bucket_offset;
+        self.set_cursor(percentile_bucket_idx, percentile_bucket_offset);
 
         // Handle bucket splitting if necessary
-        while buckets[percentile_bucket_idx].len() > MAX_BUCKET_SIZE {
+        while buckets[percentile_bucket_idx].len() > self.max_bucket_size {
             // Split the bucket
-            let new_bucket = buckets[percentile_bucket_idx].split_at_median();
+            let len = buckets[percentile_bucket_idx].len();
+            let new_bucket = self.timed(
+                || buckets[percentile_bucket_idx].split_at_median(),
+                |elapsed| instrument::TrackerEvent::Split { len, elapsed },
+            );
+            if let Some(index) = self.min_index.borrow_mut().as_mut() {
+                index.insert(percentile_bucket_idx + 1, new_bucket.min());
+            }
             buckets.insert(percentile_bucket_idx + 1, new_bucket);
 
             // Update indices after split if needed
@@ -314,16 +852,153 @@ where
                 percentile_bucket_idx += 1;
 
                 // Update stored indices
-                *self.percentile_bucket_idx.borrow_mut() = percentile_bucket_idx;
-                *self.percentile_bucket_offset.borrow_mut() = percentile_bucket_offset;
+                self.set_cursor(percentile_bucket_idx, percentile_bucket_offset);
             }
         }
 
+        if percentile_bucket_idx != initial_bucket_idx {
+            self.record(instrument::TrackerEvent::CursorMove {
+                from: initial_bucket_idx,
+                to: percentile_bucket_idx,
+            });
+        }
+
         // Ensure the critical bucket is sorted
-        buckets[percentile_bucket_idx].ensure_sorted();
+        self.sort_bucket(&mut buckets[percentile_bucket_idx]);
+
+        if !self.alerts.is_empty() {
+            self.check_alerts(
+                buckets[percentile_bucket_idx].get_value_at(target_pos - percentile_bucket_offset),
+            );
+        }
 
         // Mark rebalancing as complete
-        *self.needs_rebalancing.borrow_mut() = false;
+        self.needs_rebalancing.set(false);
+    }
+
+    /// Returns the total number of values in the tracker.
+    fn count(&self) -> usize {
+        from_rank(self.total_count)
+    }
+
+    /// Returns the percentile cursor as `(bucket index, values before that bucket)`.
+    fn cursor(&self) -> (usize, usize) {
+        (
+            from_rank(self.percentile_bucket_idx.get()),
+            from_rank(self.percentile_bucket_offset.get()),
+        )
+    }
+
+    /// Stores a new percentile cursor.
+    fn set_cursor(&self, bucket_idx: usize, bucket_offset: usize) {
+        self.percentile_bucket_idx.set(to_rank(bucket_idx));
+        self.percentile_bucket_offset.set(to_rank(bucket_offset));
+    }
+
+    /// Replaces every stored value with values that are partitioned into runs.
+    ///
+    /// Every run of `max_bucket_size` values, counted from the start, must hold values no
+    /// larger than those of the next run, and becomes a bucket. With `sorted` the values
+    /// are in ascending order, so the buckets are already sorted and the next query only has
+    /// to walk the cursor.
+    ///
+    /// The values are then downsampled if they don't fit within the memory budget. See
+    /// [`load_buckets`](Self::load_buckets).
+    fn load_runs(&mut self, values: Vec<T>, sorted: bool) {
+        let buckets = Bucket::cut_runs(values, self.max_bucket_size, sorted);
+        self.load_buckets(buckets);
+        self.remeasure_budget();
+        self.enforce_budget();
+    }
+
+    /// Replaces every stored value with a list of non-empty, non-overlapping buckets in
+    /// ascending order.
+    ///
+    /// Running statistics are reseeded on their next query, and the undo journal is cleared
+    /// since it describes the old values. This doesn't count as inserting the values, so the
+    /// reservoir's count of values seen is left to the caller.
+    fn load_buckets(&mut self, buckets: Vec<Bucket<T>>) {
+        if let Some(index) = self.min_index.get_mut() {
+            index.clear();
+            for (bucket_idx, bucket) in buckets.iter().enumerate() {
+                index.insert(bucket_idx, bucket.min());
+            }
+        }
+        #[cfg(feature = "shadow-oracle")]
+        if let Some(oracle) = &mut self.oracle {
+            oracle.reset(buckets.iter().flat_map(|bucket| &bucket.values));
+        }
+        *self.moments.get_mut() = None;
+        #[cfg(feature = "num-traits")]
+        {
+            *self.sum.get_mut() = None;
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+
+        self.total_count = to_rank(buckets.iter().map(Bucket::len).sum());
+        // An empty tracker is left in the same state as a freshly built one
+        self.needs_rebalancing.set(!buckets.is_empty());
+        *self.buckets.get_mut() = buckets;
+        self.set_cursor(0, 0);
+    }
+
+    /// Removes the value at an index within a bucket, keeping the cursor consistent.
+    ///
+    /// # Panics
+    /// Panics if the bucket or index is out of bounds.
+    fn remove_from_bucket(&mut self, bucket_idx: usize, index: usize) -> T {
+        let (mut cursor_idx, mut cursor_offset) = self.cursor();
+        let buckets = self.buckets.get_mut();
+
+        let bucket = &mut buckets[bucket_idx];
+        let removed = bucket.swap_remove(index);
+        if bucket_idx < cursor_idx {
+            cursor_offset -= 1;
+        }
+
+        let min_index = self.min_index.get_mut();
+        if bucket.values.is_empty() {
+            buckets.remove(bucket_idx);
+            if let Some(index) = min_index {
+                index.remove(bucket_idx);
+            }
+            if bucket_idx < cursor_idx {
+                cursor_idx -= 1;
+            } else if cursor_idx >= buckets.len() {
+                // The critical bucket was the last one, restart the cursor walk from the front
+                cursor_idx = 0;
+                cursor_offset = 0;
+            }
+        } else if let Some(index) = min_index {
+            index.update(bucket_idx, bucket.min());
+        }
+
+        self.total_count = to_rank(self.count() - 1);
+        self.set_cursor(cursor_idx, cursor_offset);
+        self.needs_rebalancing.set(true);
+        self.forget(&removed);
+        removed
+    }
+
+    /// Finds a stored value equal to `value`, as `(bucket index, index within the bucket)`.
+    ///
+    /// Equal values can straddle a split, so every bucket whose range covers the value is
+    /// searched, starting from the last.
+    fn find_value(&self, value: &T) -> Option<(usize, usize)> {
+        let buckets = self.buckets.borrow();
+        let end = buckets.partition_point(|bucket| bucket.min() <= value);
+        (0..end)
+            .rev()
+            .take_while(|&bucket_idx| buckets[bucket_idx].max() >= value)
+            .find_map(|bucket_idx| {
+                let index = buckets[bucket_idx]
+                    .values
+                    .iter()
+                    .position(|stored| stored == value)?;
+                Some((bucket_idx, index))
+            })
     }
 
     /// Calculates the position of the target percentile in the overall dataset.
@@ -334,7 +1009,65 @@ where
     /// # Returns
     /// The zero-based index of the target percentile value
     fn get_target_pos(&self) -> usize {
-        (self.percentile * self.total_count) / 100
+        target_rank(self.percentile, self.count())
+    }
+
+    /// Returns the value at the given zero-based rank in the sorted order of all values.
+    ///
+    /// The owning bucket is located by walking the bucket lengths, and only that bucket
+    /// is sorted. This does not move the percentile cursor.
+    ///
+    /// # Panics
+    /// Panics if the rank is out of bounds.
+    fn select_rank(&self, rank: usize) -> T
+    where
+        T: Clone,
+    {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut offset = 0;
+        for bucket in buckets.iter_mut() {
+            if rank < offset + bucket.len() {
+                self.sort_bucket(bucket);
+                return bucket.get_value_at(rank - offset).clone();
+            }
+            offset += bucket.len();
+        }
+        panic!(
+            "Rank {} is out of bounds for a tracker holding {} values",
+            rank,
+            self.count()
+        );
+    }
+
+    /// Returns the values at several zero-based ranks, which must be in ascending order.
+    ///
+    /// The buckets are walked once, sorting only those that hold one of the ranks.
+    ///
+    /// # Panics
+    /// Panics if a rank is out of bounds.
+    fn select_ranks(&self, ranks: &[usize]) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut selected = Vec::with_capacity(ranks.len());
+        let mut ranks = ranks.iter().copied().peekable();
+        let mut offset = 0;
+        for bucket in buckets.iter_mut() {
+            while let Some(rank) = ranks.next_if(|&rank| rank < offset + bucket.len()) {
+                self.sort_bucket(bucket);
+                selected.push(bucket.get_value_at(rank - offset).clone());
+            }
+            offset += bucket.len();
+        }
+        if let Some(rank) = ranks.next() {
+            panic!(
+                "Rank {} is out of bounds for a tracker holding {} values",
+                rank,
+                self.count()
+            );
+        }
+        selected
     }
 
     /// Retrieves the current target percentile value.
@@ -352,17 +1085,152 @@ where
     where
         T: Clone,
     {
+        #[cfg(feature = "kll")]
+        if self.sketch.is_some() {
+            return self
+                .value_at_rank(target_rank(self.percentile, self.len()))
+                .expect("Cannot query the percentile of an empty tracker");
+        }
+
         // First ensure proper rebalancing
         self.rebalance();
 
         let target_pos = self.get_target_pos();
-        let percentile_bucket_idx = *self.percentile_bucket_idx.borrow();
-        let percentile_bucket_offset = *self.percentile_bucket_offset.borrow();
+        let (percentile_bucket_idx, percentile_bucket_offset) = self.cursor();
         let offset_into_bucket = target_pos - percentile_bucket_offset;
 
-        self.buckets.borrow()[percentile_bucket_idx]
+        let value = self.buckets.borrow()[percentile_bucket_idx]
             .get_value_at(offset_into_bucket)
-            .clone()
+            .clone();
+        #[cfg(feature = "shadow-oracle")]
+        self.check_oracle(&value);
+        value
+    }
+
+    /// Inserts a value and returns the value at the tracked percentile afterwards.
+    ///
+    /// This is the same as [`insert`](Self::insert) followed by
+    /// [`get_percentile`](Self::get_percentile), but the insert moves the cursor and keeps
+    /// its bucket split and sorted as it goes, as with amortized rebalancing. A caller that
+    /// queries after every insert then never pays for a lazy rebalance of many inserts.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty afterwards, i.e. the value was dropped by sampling or
+    /// the accepted range before any other value was kept.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::new(50);
+    /// assert_eq!(tracker.insert_and_get(10), 10);
+    /// assert_eq!(tracker.insert_and_get(30), 30);
+    /// assert_eq!(tracker.insert_and_get(20), 20);
+    /// ```
+    pub fn insert_and_get(&mut self, num: T) -> T
+    where
+        T: Clone,
+    {
+        let amortized_rebalancing = self.amortized_rebalancing;
+        self.amortized_rebalancing = true;
+        self.insert(num);
+        self.amortized_rebalancing = amortized_rebalancing;
+        self.get_percentile()
+    }
+
+    /// Returns the values at several percentiles (0-100), in the order requested, or an
+    /// empty vector if the tracker is empty.
+    ///
+    /// The tracker is rebalanced once, then every percentile is answered in a single walk
+    /// over the buckets that sorts each bucket holding one of them at most once. That is
+    /// much cheaper than a query per percentile for summary endpoints.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::new(50);
+    /// for latency_ms in (1..=1_000u64).rev() {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.get_percentiles(&[99.0, 50.0, 99.9]), vec![991, 501, 1_000]);
+    /// ```
+    pub fn get_percentiles(&mut self, percentiles: &[f64]) -> Vec<T>
+    where
+        T: Clone,
+    {
+        #[cfg(feature = "kll")]
+        if self.sketch.is_some() {
+            return percentiles
+                .iter()
+                .filter_map(|&percentile| {
+                    self.value_at_rank(rank_for_percentile(percentile, self.len()))
+                })
+                .collect();
+        }
+        if self.count() == 0 {
+            return Vec::new();
+        }
+        self.rebalance();
+
+        let mut ranks: Vec<(usize, usize)> = percentiles
+            .iter()
+            .map(|&percentile| rank_for_percentile(percentile, self.count()))
+            .enumerate()
+            .map(|(idx, rank)| (rank, idx))
+            .collect();
+        ranks.sort_unstable();
+        let sorted_ranks: Vec<usize> = ranks.iter().map(|&(rank, _)| rank).collect();
+
+        let mut values: Vec<Option<T>> = vec![None; percentiles.len()];
+        for ((_, idx), value) in ranks.into_iter().zip(self.select_ranks(&sorted_ranks)) {
+            values[idx] = Some(value);
+        }
+        values
+            .into_iter()
+            .map(|value| value.expect("Every requested percentile was selected"))
+            .collect()
+    }
+
+    /// Retrieves a reference to the current target percentile value.
+    ///
+    /// This works for values that are `Ord` but not `Clone`, and avoids a clone for values
+    /// that are expensive to copy. It takes `&mut self` because the reference points into
+    /// the tracker's storage, which is reorganized by later queries.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// // A handle that can't be cloned, ordered by its id
+    /// #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Connection(u64);
+    ///
+    /// let mut tracker = PercentileTracker::new(50);
+    /// for id in [7, 3, 5] {
+    ///     tracker.insert(Connection(id));
+    /// }
+    /// assert_eq!(tracker.get_percentile_ref().0, 5);
+    /// ```
+    ///
+    /// # Returns
+    /// A reference to the value at the target percentile position
+    pub fn get_percentile_ref(&mut self) -> &T {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            let rank = target_rank(self.percentile, self.len()) as u64;
+            return sketch
+                .value_ref_at_rank(rank)
+                .expect("Cannot query the percentile of an empty tracker");
+        }
+        self.rebalance();
+
+        let target_pos = self.get_target_pos();
+        let (percentile_bucket_idx, percentile_bucket_offset) = self.cursor();
+        let offset_into_bucket = target_pos - percentile_bucket_offset;
+
+        #[cfg(feature = "shadow-oracle")]
+        self.check_oracle(
+            self.buckets.borrow()[percentile_bucket_idx].get_value_at(offset_into_bucket),
+        );
+        self.buckets.get_mut()[percentile_bucket_idx].get_value_at(offset_into_bucket)
     }
 
     /// Prints debug statistics about the current state of the tracker.
@@ -375,7 +1243,8 @@ where
     /// - Number of buckets
     /// - Whether the bucket offset is correct
     ///
-    /// This is useful for debugging and performance analysis.
+    /// This is useful for debugging and performance analysis. Use [`stats`](Self::stats)
+    /// to get the same information as data.
     #[allow(dead_code)]
     pub fn print_stats(&self)
     where
@@ -384,16 +1253,11 @@ where
         // Ensure rebalancing is done before printing stats
         self.rebalance();
 
-        eprintln!("Total count: {}", self.total_count);
+        eprintln!("Total count: {}", self.count());
         eprintln!("Percentile tracked: {}", self.percentile);
-        eprintln!(
-            "Percentile bucket idx: {}",
-            *self.percentile_bucket_idx.borrow()
-        );
-        eprintln!(
-            "Percentile bucket offset: {}",
-            *self.percentile_bucket_offset.borrow()
-        );
+        let (percentile_bucket_idx, percentile_bucket_offset) = self.cursor();
+        eprintln!("Percentile bucket idx: {}", percentile_bucket_idx);
+        eprintln!("Percentile bucket offset: {}", percentile_bucket_offset);
         eprintln!("Percentile: {}", self.get_percentile());
         eprintln!("Buckets: {:?}", self.buckets.borrow().len());
         if self.verify_bucket_offset() {
@@ -408,21 +1272,22 @@ where
     /// This is a debug function that verifies that the bucket offset is correct.
     /// It is used to verify the correctness of the implementation.
     ///
-    /// Because of how the buckets are chunked, this is effectively O(1). Even with a tiny MAX_BUCKET_SIZE
+    /// Because of how the buckets are chunked, this is effectively O(1). Even with a tiny max_bucket_size
     /// And a huge input, there are only a couple hundred buckets.
     #[allow(dead_code)]
     pub fn verify_bucket_offset(&self) -> bool {
         // Ensure rebalancing is done before verification
         self.rebalance();
 
+        let (percentile_bucket_idx, percentile_bucket_offset) = self.cursor();
         let sum: usize = self
             .buckets
             .borrow()
             .iter()
-            .take(*self.percentile_bucket_idx.borrow())
+            .take(percentile_bucket_idx)
             .map(|bucket| bucket.len())
             .sum();
-        sum == *self.percentile_bucket_offset.borrow()
+        sum == percentile_bucket_offset
     }
 }
 
@@ -635,4 +1500,139 @@ mod tests {
         // For example, NaN != NaN and NaN is neither less than nor greater than any value.
         // To use with floating point, you would need a wrapper type with a custom Ord implementation.
     }
+
+    #[test]
+    fn test_values_without_clone() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Handle(i64);
+
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for value in (0..300i64).map(|i| (i * 7919) % 307) {
+            tracker.insert(Handle(value));
+            values.push(value);
+            values.sort_unstable();
+            assert_eq!(
+                tracker.get_percentile_ref(),
+                &Handle(values[(values.len() * 90) / 100])
+            );
+        }
+
+        let mut sampled = PercentileTracker::builder().reservoir(50).build().unwrap();
+        for value in 0..1_000i64 {
+            sampled.insert(Handle(value));
+        }
+        assert_eq!(sampled.count(), 50);
+        assert!(sampled.verify_bucket_offset());
+        sampled.get_percentile_ref();
+    }
+
+    #[test]
+    fn test_target_rank_near_overflow() {
+        for count in 0..1_000 {
+            for percentile in [0, 1, 50, 99, 100] {
+                assert_eq!(target_rank(percentile, count), percentile * count / 100);
+            }
+        }
+        // The first count whose product with 99 doesn't fit in a usize
+        let boundary = usize::MAX / 99 + 1;
+        assert!(boundary.checked_mul(99).is_none());
+        assert_eq!(
+            target_rank(99, boundary),
+            (99 * (boundary as u128) / 100) as usize
+        );
+        assert_eq!(target_rank(50, usize::MAX), usize::MAX / 2);
+        assert_eq!(target_rank(100, usize::MAX), usize::MAX);
+        assert_eq!(
+            target_rank(99, usize::MAX),
+            usize::MAX - usize::MAX / 100 - 1
+        );
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for value in (0..2_000i64).map(|i| (i * 7919) % 2_003) {
+            values.push(value);
+            // Plain inserts in between leave work for the next combined call
+            if value % 5 == 0 {
+                tracker.insert(value);
+                continue;
+            }
+            let percentile = tracker.insert_and_get(value);
+            assert!(!tracker.needs_rebalancing.get());
+            values.sort_unstable();
+            assert_eq!(percentile, values[(values.len() * 90) / 100]);
+        }
+        assert!(!tracker.amortized_rebalancing);
+        assert!(tracker.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_set_percentile() {
+        for amortized in [false, true] {
+            let mut builder = PercentileTracker::builder().max_bucket_size(8);
+            if amortized {
+                builder = builder.amortized_rebalancing();
+            }
+            let mut tracker = builder.build().unwrap();
+            tracker.set_percentile(10).unwrap();
+            let mut values = Vec::new();
+            for (i, value) in (0..2_000i64).map(|i| (i * 7919) % 2_003).enumerate() {
+                tracker.insert(value);
+                values.push(value);
+                if i % 250 == 0 {
+                    let percentile = 1 + i % 99;
+                    tracker.set_percentile(percentile).unwrap();
+                    values.sort_unstable();
+                    assert_eq!(tracker.percentile(), percentile);
+                    assert_eq!(
+                        tracker.get_percentile(),
+                        calculate_percentile(&values, percentile)
+                    );
+                    assert!(tracker.verify_bucket_offset());
+                }
+            }
+            assert_eq!(
+                tracker.set_percentile(100),
+                Err(BuildError::InvalidPercentile(100))
+            );
+            assert_eq!(tracker.percentile(), 1 + 1_750 % 99);
+        }
+    }
+
+    #[test]
+    fn test_get_percentiles() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .build()
+            .unwrap();
+        assert!(tracker.get_percentiles(&[50.0]).is_empty());
+
+        let mut values = Vec::new();
+        for value in (0..3_000i64).map(|i| (i * 7919) % 3_001) {
+            tracker.insert(value);
+            values.push(value);
+        }
+        values.sort_unstable();
+        let percentiles = [99.9, 0.0, 50.0, 90.0, 50.0, 100.0, 12.5];
+        let expected: Vec<i64> = percentiles
+            .iter()
+            .map(|&p| values[rank_for_percentile(p, values.len())])
+            .collect();
+        assert_eq!(tracker.get_percentiles(&percentiles), expected);
+        assert!(tracker.verify_bucket_offset());
+        assert_eq!(tracker.get_percentile(), calculate_percentile(&values, 90));
+        assert!(tracker.get_percentiles(&[]).is_empty());
+    }
 }
@@ -0,0 +1,155 @@
+//! A tracker specialized for the median, using the classic two-heap scheme.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// A tracker of the median, the value [`PercentileTracker::new(50)`](crate::PercentileTracker)
+/// would report.
+///
+/// The values below the median are kept in a max-heap and the rest in a min-heap, so the
+/// median is always the top of the upper heap. Inserts are O(log n) with no rebalancing left
+/// for queries, which is simpler and faster than the bucketed tracker when the median is
+/// the only percentile needed. For an even count this is the upper of the two middle values,
+/// matching the nearest-rank rule of the other trackers.
+///
+/// ```
+/// use percentiletracker::MedianTracker;
+///
+/// let mut latencies = MedianTracker::new();
+/// assert_eq!(latencies.median(), None);
+/// for ms in [40u64, 10, 30, 20] {
+///     latencies.insert(ms);
+/// }
+/// assert_eq!(latencies.median(), Some(&30));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MedianTracker<T>
+where
+    T: Ord,
+{
+    /// The `n / 2` smallest values, largest on top.
+    lower: BinaryHeap<T>,
+
+    /// The remaining values, smallest on top. Its top is the median.
+    upper: BinaryHeap<Reverse<T>>,
+}
+
+impl<T> Default for MedianTracker<T>
+where
+    T: Ord,
+{
+    fn default() -> Self {
+        MedianTracker {
+            lower: BinaryHeap::new(),
+            upper: BinaryHeap::new(),
+        }
+    }
+}
+
+impl<T> MedianTracker<T>
+where
+    T: Ord,
+{
+    /// Creates an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of values inserted.
+    pub fn len(&self) -> usize {
+        self.lower.len() + self.upper.len()
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.upper.is_empty()
+    }
+
+    /// Inserts a value in O(log n).
+    pub fn insert(&mut self, value: T) {
+        match self.upper.peek() {
+            Some(Reverse(median)) if value < *median => self.lower.push(value),
+            _ => self.upper.push(Reverse(value)),
+        }
+        // Restore `lower.len() == len / 2`, which at most one move does after one insert
+        let target = self.len() / 2;
+        if self.lower.len() > target {
+            let moved = self.lower.pop().expect("The lower heap is over its size");
+            self.upper.push(Reverse(moved));
+        } else if self.lower.len() < target {
+            let Reverse(moved) = self.upper.pop().expect("The upper heap holds the rest");
+            self.lower.push(moved);
+        }
+    }
+
+    /// Returns the median, or `None` if no values have been inserted.
+    pub fn median(&self) -> Option<&T> {
+        self.upper.peek().map(|Reverse(median)| median)
+    }
+
+    /// Returns the median, or `None` if no values have been inserted.
+    ///
+    /// This is the same as [`median`](Self::median), named like the other trackers' query.
+    pub fn get_percentile(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.median().cloned()
+    }
+
+    /// Adds every value from another tracker into this one.
+    pub fn merge(&mut self, other: &MedianTracker<T>)
+    where
+        T: Clone,
+    {
+        for value in other.lower.iter() {
+            self.insert(value.clone());
+        }
+        for Reverse(value) in other.upper.iter() {
+            self.insert(value.clone());
+        }
+    }
+}
+
+impl<T> Extend<T> for MedianTracker<T>
+where
+    T: Ord,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::PercentileTracker;
+
+    #[test]
+    fn test_matches_percentile_tracker() {
+        let mut rng = SplitMix64(3);
+        let mut median = MedianTracker::new();
+        let mut tracker = PercentileTracker::new(50);
+        for _ in 0..2_000 {
+            let value = rng.below(100);
+            median.insert(value);
+            tracker.insert(value);
+            assert_eq!(median.get_percentile(), Some(tracker.get_percentile()));
+        }
+        assert_eq!(median.len(), 2_000);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut left: MedianTracker<i32> = MedianTracker::new();
+        left.extend([5, 1, 9]);
+        let mut right = MedianTracker::new();
+        right.extend([4, 8, 2, 7]);
+        left.merge(&right);
+        assert_eq!(left.len(), 7);
+        assert_eq!(left.median(), Some(&5));
+    }
+}
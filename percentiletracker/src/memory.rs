@@ -0,0 +1,180 @@
+use std::mem::size_of;
+
+use crate::alert::Alert;
+use crate::history::History;
+use crate::journal::Journal;
+#[cfg(feature = "shadow-oracle")]
+use crate::oracle::ShadowOracle;
+use crate::{Bucket, PercentileTracker};
+
+/// An approximate breakdown of the memory held by a tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// Number of buckets in the tracker.
+    pub bucket_count: usize,
+
+    /// Number of values stored across all buckets.
+    pub len: usize,
+
+    /// Number of values the buckets can hold without reallocating.
+    pub capacity: usize,
+
+    /// Approximate total bytes, including the tracker itself, all bucket allocations, and the
+    /// copies of values held by checkpoints, the undo journal, the history, alerts and the
+    /// shadow oracle.
+    ///
+    /// Heap memory owned by the values themselves (e.g. the contents of a `String`) is not
+    /// counted, and neither are observer, alert and instrumentation callbacks.
+    pub bytes: usize,
+}
+
+impl MemoryUsage {
+    /// Returns the number of value slots that are allocated but unused.
+    pub fn slack(&self) -> usize {
+        self.capacity - self.len
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Reports approximately how much memory the tracker is using.
+    ///
+    /// This walks the buckets, which are few even for very large trackers, so it is cheap
+    /// enough to call periodically from a monitoring loop.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let buckets = self.buckets.borrow();
+        MemoryUsage {
+            bucket_count: buckets.len(),
+            len: buckets.iter().map(|bucket| bucket.len()).sum(),
+            capacity: buckets.iter().map(|bucket| bucket.values.capacity()).sum(),
+            bytes: self.stored_bytes() + self.owned_copy_bytes(),
+        }
+    }
+
+    /// Returns the bytes of the tracker itself, its buckets and the minimum index, which is
+    /// what a memory budget limits.
+    pub(crate) fn stored_bytes(&self) -> usize {
+        let buckets = self.buckets.borrow();
+        let capacity: usize = buckets.iter().map(|bucket| bucket.values.capacity()).sum();
+        let index_capacity = self
+            .min_index
+            .borrow()
+            .as_ref()
+            .map_or(0, |index| index.capacity());
+        size_of::<Self>()
+            + buckets.capacity() * size_of::<Bucket<T>>()
+            + (capacity + index_capacity) * size_of::<T>()
+    }
+
+    /// Returns the bytes allocated outside the buckets for copies of values.
+    fn owned_copy_bytes(&self) -> usize {
+        #[cfg(feature = "shadow-oracle")]
+        let oracle_bytes = self.oracle.as_ref().map_or(0, ShadowOracle::heap_bytes);
+        #[cfg(not(feature = "shadow-oracle"))]
+        let oracle_bytes = 0;
+        self.checkpoints.heap_bytes()
+            + self.alerts.capacity() * size_of::<Alert<T>>()
+            + self.journal.as_ref().map_or(0, Journal::heap_bytes)
+            + self.history.as_ref().map_or(0, History::heap_bytes)
+            + oracle_bytes
+    }
+
+    /// Reserves capacity for at least `additional` more values.
+    ///
+    /// Room is reserved in the bucket list for the buckets those values are expected to
+    /// split into, and each existing bucket reserves a share proportional to its current
+    /// size, assuming new values follow the distribution seen so far. An empty tracker has
+    /// no buckets yet, so only the bucket list is reserved.
+    pub fn reserve(&mut self, additional: usize) {
+        let count = self.count();
+        let buckets = self.buckets.get_mut();
+        buckets.reserve(additional.div_ceil(self.max_bucket_size));
+        if count == 0 {
+            return;
+        }
+        for bucket in buckets.iter_mut() {
+            let share = (additional as u128 * bucket.len() as u128).div_ceil(count as u128);
+            bucket.values.reserve(share as usize);
+        }
+    }
+
+    /// Releases any capacity that isn't holding values, e.g. after a burst of inserts.
+    pub fn shrink_to_fit(&mut self) {
+        let buckets = self.buckets.get_mut();
+        for bucket in buckets.iter_mut() {
+            bucket.values.shrink_to_fit();
+        }
+        buckets.shrink_to_fit();
+        if let Some(index) = self.min_index.get_mut() {
+            index.shrink_to_fit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_BUCKET_SIZE;
+
+    #[test]
+    fn test_memory_usage() {
+        let empty = PercentileTracker::<u64>::new(90).memory_usage();
+        assert_eq!(empty.bucket_count, 0);
+        assert_eq!(empty.len, 0);
+        assert_eq!(empty.bytes, size_of::<PercentileTracker<u64>>());
+
+        let mut tracker = PercentileTracker::<u64>::new(90);
+        for value in 0..1000 {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        let usage = tracker.memory_usage();
+        assert_eq!(usage.len, 1000);
+        assert!(usage.bucket_count > 1);
+        assert!(usage.capacity >= usage.len);
+        assert!(usage.bytes >= usage.capacity * size_of::<u64>());
+    }
+
+    #[test]
+    fn test_memory_usage_counts_copies() {
+        let mut plain = PercentileTracker::<u64>::new(90);
+        let mut journaled = PercentileTracker::<u64>::builder()
+            .percentile(90)
+            .undo_journal(1_000)
+            .build()
+            .unwrap();
+        for value in 0..1000 {
+            plain.insert(value);
+            journaled.insert(value);
+        }
+        let bucket_bytes = plain.memory_usage().bytes;
+        let journal_bytes = journaled.memory_usage().bytes - bucket_bytes;
+        assert!(journal_bytes >= 1000 * size_of::<u64>());
+
+        plain.checkpoint();
+        assert!(plain.memory_usage().bytes >= bucket_bytes + 1000 * size_of::<u64>());
+    }
+
+    #[test]
+    fn test_reserve_and_shrink_to_fit() {
+        let mut tracker = PercentileTracker::<u64>::new(90);
+        tracker.reserve(1000);
+        assert!(tracker.buckets.borrow().capacity() >= 1000 / MAX_BUCKET_SIZE);
+
+        for value in 0..1000 {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        tracker.reserve(10_000);
+        let reserved = tracker.memory_usage();
+        assert!(reserved.capacity >= 11_000);
+
+        tracker.shrink_to_fit();
+        let shrunk = tracker.memory_usage();
+        assert_eq!(shrunk.slack(), 0);
+        assert!(shrunk.bytes < reserved.bytes);
+        assert_eq!(tracker.get_percentile(), 900);
+    }
+}
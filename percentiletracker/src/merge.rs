@@ -0,0 +1,357 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use crate::{PercentileTracker, Unit};
+
+/// An error returned by [`PercentileTracker::merge`] when two trackers can't be combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// Both trackers declare a unit, and the units differ.
+    ///
+    /// The values are opaque to the tracker, so they can't be converted into a common unit.
+    UnitMismatch { left: Unit, right: Unit },
+
+    /// The backends were configured with parameters that make their summaries incompatible,
+    /// e.g. sketches with different accuracies.
+    IncompatibleParameters,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::UnitMismatch { left, right } => write!(
+                f,
+                "Cannot merge a tracker in {:?} into a tracker in {:?}",
+                right, left
+            ),
+            MergeError::IncompatibleParameters => {
+                f.write_str("Cannot merge backends configured with incompatible parameters")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Adds every value from another tracker into this one.
+    ///
+    /// The trackers don't need to share a configuration. The merged tracker keeps tracking
+    /// its own percentile with its own bucket size, and the cursor is recomputed lazily on the
+    /// next query. If only `other` declares a unit, this tracker adopts it.
+    ///
+    /// The values were already admitted by `other`, so they're added as they are: this
+    /// tracker's sampling, memory budget thinning and accepted range don't apply to them, and
+    /// they aren't reported to observers, journaled for undo, or counted by the history. Only
+    /// a reservoir still decides which values to keep, since it bounds the tracker's size.
+    /// Inserts journaled before the merge can still be undone.
+    ///
+    /// A tracker that keeps its values in a sketch takes in another sketch of the same size,
+    /// or every value of an exact tracker.
+    ///
+    /// # Errors
+    /// Returns [`MergeError::UnitMismatch`] if both trackers declare different units, and
+    /// [`MergeError::IncompatibleParameters`] if `other` keeps its values in a sketch that
+    /// this tracker can't take in. This tracker is left unchanged in either case.
+    pub fn merge(&mut self, other: &PercentileTracker<T>) -> Result<(), MergeError> {
+        let unit = merged_unit(self.unit, other.unit)?;
+        #[cfg(feature = "kll")]
+        if let Some(other_sketch) = &other.sketch {
+            let sketch = self
+                .sketch
+                .as_mut()
+                .filter(|sketch| sketch.is_compatible(other_sketch))
+                .ok_or(MergeError::IncompatibleParameters)?;
+            sketch.merge(other_sketch);
+            self.unit = unit;
+            self.added += other_sketch.count();
+            return Ok(());
+        }
+        self.unit = unit;
+        self.added += other.count() as u64;
+        for bucket in other.buckets.borrow().iter() {
+            for value in &bucket.values {
+                if self.admit_to_reservoir() {
+                    self.insert_admitted(value.clone());
+                }
+            }
+        }
+        self.enforce_budget();
+        Ok(())
+    }
+
+    /// Adds every value from many trackers into this one in a single pass.
+    ///
+    /// Each tracker's values are copied out in sorted order and all of them, along with this
+    /// tracker's own values, are combined with a k-way merge into full, sorted buckets. That
+    /// costs O(n log k) for n values across k trackers, instead of inserting every value one
+    /// by one as repeated calls to [`merge`](Self::merge) would. A tracker in reservoir mode
+    /// has to sample every value, and one that keeps its values in a sketch has no buckets
+    /// to build, so both fall back to merging the trackers one by one.
+    ///
+    /// As with [`merge`](Self::merge), the values are added without being admitted again,
+    /// and the undo journal keeps the inserts made before the merge.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let shards: Vec<_> = (0..4u64)
+    ///     .map(|shard| {
+    ///         let mut tracker = PercentileTracker::new(99);
+    ///         for value in 0..250 {
+    ///             tracker.insert(value * 4 + shard);
+    ///         }
+    ///         tracker
+    ///     })
+    ///     .collect();
+    /// let mut total = PercentileTracker::new(99);
+    /// total.merge_all(&shards).unwrap();
+    /// assert_eq!(total.get_percentile(), 990);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`MergeError::UnitMismatch`] if the trackers declare different units, and
+    /// [`MergeError::IncompatibleParameters`] if one of them keeps its values in a sketch
+    /// that this tracker can't take in. This tracker is left unchanged in either case.
+    pub fn merge_all<'a, I>(&mut self, others: I) -> Result<(), MergeError>
+    where
+        I: IntoIterator<Item = &'a PercentileTracker<T>>,
+        T: 'a,
+    {
+        let others: Vec<_> = others.into_iter().collect();
+        let mut unit = self.unit;
+        for other in &others {
+            unit = merged_unit(unit, other.unit)?;
+        }
+        // A sketch can only be taken in by a sketch of the same size
+        let storage = self.storage();
+        if others
+            .iter()
+            .any(|other| other.has_sketch() && other.storage() != storage)
+        {
+            return Err(MergeError::IncompatibleParameters);
+        }
+        self.unit = unit;
+
+        if self.reservoir.is_some() || self.has_sketch() {
+            for other in others {
+                self.merge(other)?;
+            }
+            return Ok(());
+        }
+
+        // This tracker's values can be moved rather than copied, since it's rebuilt anyway
+        let mut own = Vec::with_capacity(self.count());
+        for mut bucket in std::mem::take(self.buckets.get_mut()) {
+            bucket.ensure_sorted(self.sort_values);
+            own.append(&mut bucket.values);
+        }
+        let mut streams = vec![own.into_iter()];
+        streams.extend(
+            others
+                .iter()
+                .map(|other| other.snapshot().into_values().into_iter()),
+        );
+
+        let total = streams.iter().map(|stream| stream.len()).sum();
+        self.added += (total - streams[0].len()) as u64;
+        let mut merged = Vec::with_capacity(total);
+        let mut heads: BinaryHeap<_> = streams
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, stream)| Some(Reverse((stream.next()?, idx))))
+            .collect();
+        while let Some(Reverse((value, idx))) = heads.pop() {
+            merged.push(value);
+            if let Some(next) = streams[idx].next() {
+                heads.push(Reverse((next, idx)));
+            }
+        }
+        // Rebuilding the buckets clears the journal, but the journaled values are still stored
+        let journal = self.journal.take();
+        self.load_runs(merged, true);
+        self.journal = journal;
+        Ok(())
+    }
+}
+
+/// Returns the unit of a tracker merged from trackers in two units.
+fn merged_unit(left: Unit, right: Unit) -> Result<Unit, MergeError> {
+    if left == Unit::None {
+        Ok(right)
+    } else if right != Unit::None && right != left {
+        Err(MergeError::UnitMismatch { left, right })
+    } else {
+        Ok(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::Sampling;
+
+    #[test]
+    fn test_merge_different_configs() {
+        let mut left = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        let mut right = PercentileTracker::builder()
+            .percentile(50)
+            .unit(Unit::Milliseconds)
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for value in (0..300i64).map(|i| (i * 7919) % 307) {
+            if value % 3 == 0 {
+                left.insert(value);
+            } else {
+                right.insert(value);
+            }
+            values.push(value);
+        }
+        left.get_percentile();
+        right.get_percentile();
+
+        left.merge(&right).unwrap();
+        values.sort_unstable();
+        assert_eq!(left.get_percentile(), values[(values.len() * 90) / 100]);
+        assert!(left.verify_bucket_offset());
+        assert_eq!(left.unit(), Unit::Milliseconds);
+        assert_eq!(
+            right.memory_usage().len,
+            values.iter().filter(|value| *value % 3 != 0).count()
+        );
+    }
+
+    #[test]
+    fn test_merge_all() {
+        let mut rng = SplitMix64(5);
+        let mut shards: Vec<_> = (0..16)
+            .map(|_| {
+                PercentileTracker::builder()
+                    .percentile(90)
+                    .max_bucket_size(8)
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        let mut total = PercentileTracker::builder()
+            .percentile(50)
+            .min_index()
+            .build()
+            .unwrap();
+        let mut expected = PercentileTracker::new(50);
+        for i in 0..5_000 {
+            let value = rng.below(2_000);
+            match i % 17 {
+                16 => total.insert(value),
+                shard => shards[shard].insert(value),
+            }
+            expected.insert(value);
+        }
+        total.get_percentile();
+
+        total.merge_all(&shards).unwrap();
+        assert_eq!(total.count(), 5_000);
+        assert_eq!(total.get_percentile(), expected.get_percentile());
+        assert!(total.verify_bucket_offset());
+        assert!(total.iter_sorted().eq(expected.iter_sorted()));
+        assert_eq!(shards[3].count(), 5_000 / 17);
+
+        total.insert(3_000);
+        assert_eq!(total.max(), Some(3_000));
+    }
+
+    #[test]
+    fn test_merge_all_units() {
+        let mut left = PercentileTracker::<u64>::new(50);
+        left.insert(1);
+        let mut millis = PercentileTracker::builder()
+            .unit(Unit::Milliseconds)
+            .build()
+            .unwrap();
+        millis.insert(2);
+        let mut bytes = PercentileTracker::builder()
+            .unit(Unit::Bytes)
+            .build()
+            .unwrap();
+        bytes.insert(3);
+
+        assert_eq!(
+            left.merge_all([&millis, &bytes]),
+            Err(MergeError::UnitMismatch {
+                left: Unit::Milliseconds,
+                right: Unit::Bytes
+            })
+        );
+        assert_eq!(left.count(), 1);
+        assert_eq!(left.unit(), Unit::None);
+
+        let mut sampled = PercentileTracker::builder().reservoir(10).build().unwrap();
+        sampled.merge_all([&left, &millis]).unwrap();
+        assert_eq!(sampled.count(), 2);
+        assert_eq!(sampled.unit(), Unit::Milliseconds);
+    }
+
+    #[test]
+    fn test_merge_skips_admission() {
+        let mut other = PercentileTracker::new(50);
+        for value in 0..100u32 {
+            other.insert(value);
+        }
+        let mut sampled = PercentileTracker::builder()
+            .percentile(50)
+            .sampling(Sampling::Every(10))
+            .undo_journal(4)
+            .build()
+            .unwrap();
+        sampled.insert(1_000);
+        sampled.merge(&other).unwrap();
+        assert_eq!(sampled.len(), 101);
+        assert_eq!(sampled.get_percentile(), 50);
+
+        // Only the insert made before the merge is journaled
+        assert_eq!(sampled.undo(4), 1);
+        assert_eq!(sampled.max(), Some(99));
+
+        // One in ten of these is kept
+        for _ in 0..10 {
+            sampled.insert(2_000);
+        }
+        sampled.merge_all([&other]).unwrap();
+        assert_eq!(sampled.len(), 201);
+        assert_eq!(sampled.undo(4), 1);
+        assert_eq!(sampled.len(), 200);
+        assert!(sampled.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_merge_unit_mismatch() {
+        let mut left = PercentileTracker::<u64>::builder()
+            .unit(Unit::Nanoseconds)
+            .build()
+            .unwrap();
+        left.insert(1);
+        let mut right = PercentileTracker::<u64>::builder()
+            .unit(Unit::Bytes)
+            .build()
+            .unwrap();
+        right.insert(2);
+        assert_eq!(
+            left.merge(&right),
+            Err(MergeError::UnitMismatch {
+                left: Unit::Nanoseconds,
+                right: Unit::Bytes
+            })
+        );
+        assert_eq!(left.get_percentile(), 1);
+    }
+}
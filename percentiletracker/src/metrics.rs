@@ -0,0 +1,243 @@
+//! An in-process metrics endpoint built from the crate's pieces.
+//!
+//! [`MetricsHub`] keeps one tracker per metric name, rolls them over into tumbling windows,
+//! and renders the last completed window as Prometheus exposition text or JSON from a single
+//! HTTP-handler-shaped [`MetricsHub::render`] call. Wrap it in a `Mutex` and share it between
+//! the code recording samples and the `/metrics` handler.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::export::write_json_string;
+use crate::prometheus::quantile;
+use crate::{
+    FrozenSnapshot, Numeric, PercentileTracker, PrometheusFormat, Unit, DELTA_PERCENTILES,
+};
+
+/// The content type of Prometheus text exposition format 0.0.4.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The content type of the JSON rendering.
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// The rendered body of a metrics request, ready to be written to an HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsResponse {
+    /// The value for the `Content-Type` header.
+    pub content_type: &'static str,
+
+    /// The response body.
+    pub body: String,
+}
+
+/// A collection of named trackers reported over tumbling windows.
+///
+/// Samples are recorded into the current window. Once the window length has elapsed, every
+/// tracker is frozen into a snapshot and replaced with an empty one, so reports always cover
+/// exactly one complete window rather than the whole lifetime of the process.
+pub struct MetricsHub<T>
+where
+    T: Clone + Ord,
+{
+    /// The length of each window.
+    window: Duration,
+
+    /// When the current window started.
+    window_start: Instant,
+
+    /// The percentile each tracker is built with.
+    percentile: usize,
+
+    /// The unit of every recorded sample.
+    unit: Unit,
+
+    /// Trackers for the current window, by metric name.
+    live: BTreeMap<String, PercentileTracker<T>>,
+
+    /// Snapshots of the last completed window, by metric name.
+    completed: BTreeMap<String, FrozenSnapshot<T>>,
+}
+
+impl<T> MetricsHub<T>
+where
+    T: Numeric,
+{
+    /// Creates a hub reporting windows of the given length, with samples in the given unit.
+    pub fn new(window: Duration, unit: Unit) -> Self {
+        MetricsHub {
+            window,
+            window_start: Instant::now(),
+            percentile: 99,
+            unit,
+            live: BTreeMap::new(),
+            completed: BTreeMap::new(),
+        }
+    }
+
+    /// Records a sample for a metric, creating the metric on first use.
+    pub fn record(&mut self, name: &str, value: T) {
+        self.rotate_if_due();
+        if let Some(tracker) = self.live.get_mut(name) {
+            tracker.insert(value);
+            return;
+        }
+        let mut tracker = PercentileTracker::builder()
+            .percentile(self.percentile)
+            .unit(self.unit)
+            .build()
+            .expect("The hub's tracker configuration is valid");
+        tracker.insert(value);
+        self.live.insert(name.to_string(), tracker);
+    }
+
+    /// Ends the current window immediately, making it the one that is reported.
+    ///
+    /// Metrics that received no samples in the window are dropped from reports.
+    pub fn rotate(&mut self) {
+        self.completed = std::mem::take(&mut self.live)
+            .into_iter()
+            .map(|(name, tracker)| (name, tracker.snapshot()))
+            .collect();
+        self.window_start = Instant::now();
+    }
+
+    /// Returns the snapshot of a metric from the last completed window.
+    pub fn completed(&self, name: &str) -> Option<&FrozenSnapshot<T>> {
+        self.completed.get(name)
+    }
+
+    /// Renders the last completed window for an HTTP request with the given `Accept` header.
+    ///
+    /// Clients asking for `application/json` get JSON, and everything else, including
+    /// Prometheus scrapers, gets Prometheus text.
+    pub fn render(&mut self, accept: &str) -> MetricsResponse {
+        self.rotate_if_due();
+        if accept.contains(JSON_CONTENT_TYPE) {
+            MetricsResponse {
+                content_type: JSON_CONTENT_TYPE,
+                body: self.render_json(),
+            }
+        } else {
+            MetricsResponse {
+                content_type: PROMETHEUS_CONTENT_TYPE,
+                body: self.render_prometheus(),
+            }
+        }
+    }
+
+    /// Renders each metric as a Prometheus summary with the [`DELTA_PERCENTILES`] quantiles.
+    ///
+    /// Values are converted to seconds for time units, following Prometheus conventions.
+    pub fn render_prometheus(&self) -> String {
+        self.completed
+            .iter()
+            .map(|(name, snapshot)| {
+                snapshot.to_prometheus(
+                    name,
+                    PrometheusFormat::Summary {
+                        percentiles: &DELTA_PERCENTILES,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Renders each metric's count and [`DELTA_PERCENTILES`] quantiles as a JSON object.
+    pub fn render_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"window_ms\":{},\"unit\":\"{}\",\"metrics\":{{",
+            self.window.as_millis(),
+            self.unit
+        );
+        for (i, (name, snapshot)) in self.completed.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, name);
+            let _ = write!(out, ":{{\"count\":{},\"quantiles\":{{", snapshot.len());
+            for (j, percentile) in DELTA_PERCENTILES.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let value = snapshot
+                    .value_at_percentile(*percentile)
+                    .map(|v| v.to_f64());
+                match value {
+                    Some(value) => {
+                        let _ = write!(out, "\"{}\":{}", quantile(*percentile), value);
+                    }
+                    None => {
+                        let _ = write!(out, "\"{}\":null", quantile(*percentile));
+                    }
+                }
+            }
+            out.push_str("}}");
+        }
+        out.push_str("}}");
+        out
+    }
+
+    /// Rotates the window if its length has elapsed.
+    fn rotate_if_due(&mut self) {
+        if self.window_start.elapsed() >= self.window {
+            self.rotate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_completed_window() {
+        let mut hub = MetricsHub::new(Duration::from_secs(3600), Unit::Milliseconds);
+        for value in 1..=100u64 {
+            hub.record("http.latency", value);
+        }
+        hub.record("db_latency", 5);
+
+        // Nothing is reported until the first window completes
+        let response = hub.render("text/plain");
+        assert_eq!(response.content_type, PROMETHEUS_CONTENT_TYPE);
+        assert!(response.body.is_empty());
+
+        hub.rotate();
+        let body = hub.render("*/*").body;
+        assert!(body.contains("# TYPE http_latency_seconds summary\n"));
+        assert!(body.contains("http_latency_seconds{quantile=\"0.5\"} 0.051\n"));
+        assert!(body.contains("http_latency_seconds{quantile=\"0.999\"} 0.1\n"));
+        assert!(body.contains("http_latency_seconds_count 100\n"));
+        assert!(body.contains("http_latency_seconds_sum 5.05"));
+        assert!(body.contains("db_latency_seconds_count 1\n"));
+        assert_eq!(hub.completed("http.latency").unwrap().len(), 100);
+
+        let json = hub.render("application/json");
+        assert_eq!(json.content_type, JSON_CONTENT_TYPE);
+        assert!(json
+            .body
+            .starts_with("{\"window_ms\":3600000,\"unit\":\"ms\",\"metrics\":{"));
+        assert!(json
+            .body
+            .contains("\"http.latency\":{\"count\":100,\"quantiles\":{\"0.5\":51,"));
+
+        // A window without samples reports nothing
+        hub.rotate();
+        assert_eq!(
+            hub.render_json(),
+            "{\"window_ms\":3600000,\"unit\":\"ms\",\"metrics\":{}}"
+        );
+    }
+
+    #[test]
+    fn test_window_rotates_automatically() {
+        let mut hub = MetricsHub::new(Duration::ZERO, Unit::None);
+        hub.record("requests", 1u32);
+        hub.record("requests", 2u32);
+        assert_eq!(hub.completed("requests").unwrap().values(), &[1]);
+        assert!(hub.render("").body.contains("requests_count 1\n"));
+    }
+}
@@ -0,0 +1,122 @@
+//! A flat copy of the bucket minimums for locating buckets on insert.
+//!
+//! Inserting binary searches the buckets by their minimum value. Each probe reads a `Bucket`,
+//! whose minimum lives in the bucket's own allocation, so a search over thousands of buckets
+//! touches two cache lines per probe. Keeping the minimums side by side in one `Vec<T>`
+//! makes the probes read a single contiguous array instead. This needs `T: Clone` to copy
+//! the minimums, so it's opt-in through
+//! [`PercentileTrackerBuilder::min_index`](crate::PercentileTrackerBuilder::min_index).
+
+use crate::PercentileTrackerBuilder;
+
+/// The minimum of every bucket, in bucket order.
+pub(crate) struct MinIndex<T> {
+    /// The minimum value of each bucket.
+    mins: Vec<T>,
+
+    /// Copies a bucket's minimum into the index. Captured by the builder, where `T: Clone` is known.
+    clone: fn(&T) -> T,
+}
+
+impl<T> MinIndex<T>
+where
+    T: Ord,
+{
+    /// Creates an empty index.
+    pub(crate) fn new(clone: fn(&T) -> T) -> Self {
+        MinIndex {
+            mins: Vec::new(),
+            clone,
+        }
+    }
+
+    /// Binary searches the minimums, with the same result as searching the buckets themselves.
+    pub(crate) fn search(&self, value: &T) -> Result<usize, usize> {
+        self.mins.binary_search(value)
+    }
+
+    /// Records the minimum of a bucket inserted at `bucket_idx`.
+    pub(crate) fn insert(&mut self, bucket_idx: usize, min: &T) {
+        self.mins.insert(bucket_idx, (self.clone)(min));
+    }
+
+    /// Records a new minimum for an existing bucket.
+    pub(crate) fn update(&mut self, bucket_idx: usize, min: &T) {
+        self.mins[bucket_idx] = (self.clone)(min);
+    }
+
+    /// Forgets the minimum of a removed bucket.
+    pub(crate) fn remove(&mut self, bucket_idx: usize) {
+        self.mins.remove(bucket_idx);
+    }
+
+    /// Forgets every minimum.
+    pub(crate) fn clear(&mut self) {
+        self.mins.clear();
+    }
+
+    /// Returns the number of minimums the index can hold without reallocating.
+    pub(crate) fn capacity(&self) -> usize {
+        self.mins.capacity()
+    }
+
+    /// Releases unused capacity.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.mins.shrink_to_fit();
+    }
+
+    /// Returns the recorded minimums, for checking them against the buckets.
+    pub(crate) fn mins(&self) -> &[T] {
+        &self.mins
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Keeps a flat copy of the bucket minimums to speed up locating buckets on insert.
+    ///
+    /// This pays off for large trackers with many buckets, at the cost of an extra copy of
+    /// one value per bucket.
+    pub fn min_index(mut self) -> Self {
+        self.clone_min = Some(T::clone);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::SplitMix64;
+    use crate::PercentileTracker;
+
+    #[test]
+    fn test_min_index_tracks_buckets() {
+        let mut indexed = PercentileTracker::builder()
+            .percentile(75)
+            .max_bucket_size(4)
+            .reservoir(500)
+            .min_index()
+            .build()
+            .unwrap();
+        let mut plain = PercentileTracker::builder()
+            .percentile(75)
+            .max_bucket_size(4)
+            .reservoir(500)
+            .build()
+            .unwrap();
+        let mut rng = SplitMix64(3);
+        for step in 0..5_000 {
+            let value = rng.below(2_000) as i64;
+            indexed.insert(value);
+            plain.insert(value);
+            if step % 7 == 0 {
+                assert_eq!(indexed.get_percentile(), plain.get_percentile());
+            }
+        }
+
+        let buckets = indexed.buckets.borrow();
+        let mins: Vec<i64> = buckets.iter().map(|bucket| *bucket.min()).collect();
+        assert_eq!(indexed.min_index.borrow().as_ref().unwrap().mins(), mins);
+    }
+}
@@ -0,0 +1,300 @@
+use std::cell::Ref;
+
+use crate::{Numeric, PercentileTracker};
+
+/// Running mean and sums of powers of deviations, maintained with Welford's algorithm
+/// extended to the third and fourth powers.
+pub(crate) struct Moments<T> {
+    /// Converts a stored value to `f64`. Captured when the moments are first queried, since
+    /// only then is `T` known to be [`Numeric`].
+    to_f64: fn(&T) -> f64,
+
+    /// Number of values accumulated.
+    count: u64,
+
+    /// Mean of the accumulated values.
+    mean: f64,
+
+    /// Sum of squared deviations from the mean.
+    m2: f64,
+
+    /// Sum of cubed deviations from the mean.
+    m3: f64,
+
+    /// Sum of fourth powers of deviations from the mean.
+    m4: f64,
+}
+
+impl<T> Moments<T> {
+    /// Accumulates a value.
+    pub(crate) fn push(&mut self, value: &T) {
+        let x = (self.to_f64)(value);
+        self.count += 1;
+        let n = self.count as f64;
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let term = delta * delta_n * (n - 1.0);
+        self.mean += delta_n;
+        self.m4 += term * delta_n * delta_n * (n * n - 3.0 * n + 3.0)
+            + 6.0 * delta_n * delta_n * self.m2
+            - 4.0 * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m2 += term;
+    }
+
+    /// Removes a previously accumulated value by reversing its update.
+    pub(crate) fn pop(&mut self, value: &T) {
+        if self.count <= 1 {
+            self.count = 0;
+            self.mean = 0.0;
+            self.m2 = 0.0;
+            self.m3 = 0.0;
+            self.m4 = 0.0;
+            return;
+        }
+        let x = (self.to_f64)(value);
+        let n = self.count as f64;
+        self.count -= 1;
+        self.mean -= (x - self.mean) / (n - 1.0);
+        // Undo each sum in the reverse order of `push`, from the mean it was pushed onto
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let term = delta * delta_n * (n - 1.0);
+        self.m2 = (self.m2 - term).max(0.0);
+        self.m3 -= term * delta_n * (n - 2.0) - 3.0 * delta_n * self.m2;
+        self.m4 = (self.m4
+            - term * delta_n * delta_n * (n * n - 3.0 * n + 3.0)
+            - 6.0 * delta_n * delta_n * self.m2
+            + 4.0 * delta_n * self.m3)
+            .max(0.0);
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the smallest value in the tracker, or `None` if it is empty.
+    ///
+    /// Every bucket tracks the position of its extremes, so this is O(1).
+    pub fn min(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return sketch.min().cloned();
+        }
+        self.buckets
+            .borrow()
+            .first()
+            .map(|bucket| bucket.min().clone())
+    }
+
+    /// Returns the largest value in the tracker, or `None` if it is empty.
+    ///
+    /// Every bucket tracks the position of its extremes, so this is O(1).
+    pub fn max(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return sketch.max().cloned();
+        }
+        self.buckets
+            .borrow()
+            .last()
+            .map(|bucket| bucket.max().clone())
+    }
+
+    /// Returns the mean of the values in the tracker, or `None` if it is empty.
+    ///
+    /// The first call walks every value once, and from then on the mean and variance are
+    /// maintained incrementally on insert.
+    pub fn mean(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        let moments = self.moments();
+        (moments.count > 0).then_some(moments.mean)
+    }
+
+    /// Returns the population variance of the values in the tracker, or `None` if it is empty.
+    ///
+    /// Like [`mean`](Self::mean), this is computed with Welford's algorithm, which stays
+    /// numerically stable over long streams.
+    pub fn variance(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        let moments = self.moments();
+        (moments.count > 0).then(|| moments.m2 / moments.count as f64)
+    }
+
+    /// Returns the skewness of the values in the tracker, or `None` if it is empty or every
+    /// value is equal.
+    ///
+    /// This is the population skewness `m3 / m2^1.5`, where `mk` is the mean `k`th power of
+    /// the deviations from the mean. It is positive when the upper tail is longer, as with
+    /// most latency distributions, and is maintained incrementally like the mean.
+    pub fn skewness(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        let moments = self.moments();
+        (moments.count > 0 && moments.m2 > 0.0)
+            .then(|| (moments.count as f64).sqrt() * moments.m3 / moments.m2.powf(1.5))
+    }
+
+    /// Returns the excess kurtosis of the values in the tracker, or `None` if it is empty or
+    /// every value is equal.
+    ///
+    /// This is the population kurtosis `m4 / m2^2` minus 3, so it is 0 for a normal
+    /// distribution and positive for one with heavier tails.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for latency_ms in [1, 2, 3, 10] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert!(tracker.skewness().unwrap() > 1.0);
+    /// assert!((tracker.kurtosis().unwrap() + 0.7696).abs() < 1e-9);
+    /// ```
+    pub fn kurtosis(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        let moments = self.moments();
+        (moments.count > 0 && moments.m2 > 0.0)
+            .then(|| moments.count as f64 * moments.m4 / (moments.m2 * moments.m2) - 3.0)
+    }
+
+    /// Updates the running statistics for a newly inserted value.
+    pub(crate) fn observe(&mut self, value: &T) {
+        if let Some(moments) = self.moments.get_mut() {
+            moments.push(value);
+        }
+        #[cfg(feature = "num-traits")]
+        if let Some(sum) = self.sum.get_mut() {
+            sum.push(value);
+        }
+        #[cfg(feature = "shadow-oracle")]
+        if let Some(oracle) = &mut self.oracle {
+            oracle.insert(value);
+        }
+    }
+
+    /// Updates the running statistics for a value that was removed.
+    pub(crate) fn forget(&mut self, value: &T) {
+        if let Some(moments) = self.moments.get_mut() {
+            moments.pop(value);
+        }
+        #[cfg(feature = "num-traits")]
+        if let Some(sum) = self.sum.get_mut() {
+            sum.pop(value);
+        }
+        #[cfg(feature = "shadow-oracle")]
+        if let Some(oracle) = &mut self.oracle {
+            oracle.remove(value);
+        }
+    }
+
+    /// Returns the running moments, seeding them from the stored values on first use.
+    fn moments(&self) -> Ref<'_, Moments<T>>
+    where
+        T: Numeric,
+    {
+        if self.moments.borrow().is_none() {
+            let mut moments = Moments {
+                to_f64: |value: &T| value.to_f64(),
+                count: 0,
+                mean: 0.0,
+                m2: 0.0,
+                m3: 0.0,
+                m4: 0.0,
+            };
+            for bucket in self.buckets.borrow().iter() {
+                for value in &bucket.values {
+                    moments.push(value);
+                }
+            }
+            *self.moments.borrow_mut() = Some(moments);
+        }
+        Ref::map(self.moments.borrow(), |moments| {
+            moments.as_ref().expect("Moments were just seeded")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_statistics() {
+        let mut tracker = PercentileTracker::<i64>::new(90);
+        assert_eq!(tracker.min(), None);
+        assert_eq!(tracker.mean(), None);
+        assert_eq!(tracker.variance(), None);
+
+        for value in [4, 8, 6, -2] {
+            tracker.insert(value);
+        }
+        // Seeded from the stored values
+        assert_eq!(tracker.mean(), Some(4.0));
+        assert_eq!(tracker.variance(), Some(14.0));
+
+        // Maintained incrementally
+        tracker.insert(9);
+        assert_eq!(tracker.min(), Some(-2));
+        assert_eq!(tracker.max(), Some(9));
+        assert_eq!(tracker.mean(), Some(5.0));
+        assert!((tracker.variance().unwrap() - 15.2).abs() < 1e-9);
+        assert!((tracker.skewness().unwrap() + 0.8504820550179567).abs() < 1e-9);
+        assert!((tracker.kurtosis().unwrap() + 0.6281163434903045).abs() < 1e-9);
+
+        let mut constant = PercentileTracker::new(50);
+        constant.insert(3u8);
+        constant.insert(3);
+        assert_eq!(constant.variance(), Some(0.0));
+        assert_eq!(constant.skewness(), None);
+        assert_eq!(constant.kurtosis(), None);
+    }
+
+    #[test]
+    fn test_higher_moments_survive_removal() {
+        let mut tracker = PercentileTracker::<i64>::new(50);
+        tracker.insert(0);
+        tracker.mean();
+        for value in [4, 8, 6, -2, 9, 30] {
+            tracker.insert(value);
+        }
+        // Removing values, as a reservoir or undo does, reverses their updates
+        let (bucket_idx, index) = tracker.find_value(&30).unwrap();
+        tracker.remove_from_bucket(bucket_idx, index);
+        let (bucket_idx, index) = tracker.find_value(&0).unwrap();
+        tracker.remove_from_bucket(bucket_idx, index);
+        assert!((tracker.mean().unwrap() - 5.0).abs() < 1e-9);
+        assert!((tracker.skewness().unwrap() + 0.8504820550179567).abs() < 1e-9);
+        assert!((tracker.kurtosis().unwrap() + 0.6281163434903045).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_statistics_follow_reservoir() {
+        let mut tracker = PercentileTracker::builder().reservoir(50).build().unwrap();
+        tracker.insert(0u64);
+        tracker.mean();
+        for value in 1..10_000u64 {
+            tracker.insert(value);
+        }
+        let snapshot = tracker.snapshot();
+        let values = snapshot.values();
+        let mean = values.iter().sum::<u64>() as f64 / values.len() as f64;
+        assert!((tracker.mean().unwrap() - mean).abs() < 1e-6);
+        assert_eq!(tracker.min().as_ref(), values.first());
+        assert_eq!(tracker.max().as_ref(), values.last());
+    }
+}
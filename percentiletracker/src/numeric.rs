@@ -0,0 +1,30 @@
+/// Numeric value types that can be converted to floating point for reporting.
+///
+/// The tracker itself only needs `Ord`, but formatting, unit conversion and other derived
+/// statistics need to do arithmetic on the values. This is implemented for all of the
+/// primitive integer types and for `Duration`.
+pub trait Numeric: Copy + Ord {
+    /// Converts the value to an `f64`, possibly losing precision for very large magnitudes.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_numeric {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Durations are converted to nanoseconds, so trackers of them should use [`Unit::Nanoseconds`](crate::Unit::Nanoseconds).
+impl Numeric for std::time::Duration {
+    fn to_f64(self) -> f64 {
+        self.as_nanos() as f64
+    }
+}
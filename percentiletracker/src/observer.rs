@@ -0,0 +1,232 @@
+//! Callbacks run on every insert.
+//!
+//! Observers see each value right after it lands, together with where it landed relative to
+//! the rest of the distribution, so outliers can be reported the moment they arrive rather
+//! than on the next periodic query.
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// A value that was just inserted, and where it landed in the tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsertEvent<'a, T> {
+    /// The inserted value.
+    pub value: &'a T,
+
+    /// The number of stored values strictly less than the inserted value.
+    pub rank: usize,
+
+    /// The number of values in the tracker, including the inserted one.
+    pub count: usize,
+
+    /// The percentile the tracker tracks.
+    pub percentile: usize,
+
+    /// The value at the tracked percentile, including the inserted value.
+    pub percentile_value: &'a T,
+}
+
+impl<T> InsertEvent<'_, T>
+where
+    T: Ord,
+{
+    /// Returns true if the value landed above the tracked percentile.
+    pub fn is_above_percentile(&self) -> bool {
+        self.value > self.percentile_value
+    }
+
+    /// Returns true if no stored value is larger than the inserted value.
+    pub fn is_max(&self) -> bool {
+        self.rank + 1 == self.count
+    }
+
+    /// Returns true if no stored value is smaller than the inserted value.
+    pub fn is_min(&self) -> bool {
+        self.rank == 0
+    }
+}
+
+/// A callback run on every insert.
+type Observer<T> = Box<dyn Fn(&InsertEvent<'_, T>) + Send + Sync>;
+
+/// The registered observers.
+pub(crate) struct Observers<T> {
+    /// Copies each inserted value before the tracker takes ownership of it. Captured where
+    /// `T` is known to be `Clone`.
+    pub(crate) clone: fn(&T) -> T,
+
+    /// The observers, in the order they were registered.
+    list: Vec<Observer<T>>,
+}
+
+impl<T> Observers<T>
+where
+    T: Clone,
+{
+    pub(crate) fn new() -> Self {
+        Observers {
+            clone: T::clone,
+            list: Vec::new(),
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the number of registered insert observers.
+    pub fn observer_count(&self) -> usize {
+        self.observers
+            .as_ref()
+            .map_or(0, |observers| observers.list.len())
+    }
+
+    /// Removes every insert observer.
+    pub fn clear_observers(&mut self) {
+        self.observers = None;
+    }
+
+    /// Runs every observer for a value that was just inserted.
+    ///
+    /// Finding the percentile rebalances the tracker, so observed inserts cost as much as an
+    /// insert followed by a query.
+    pub(crate) fn notify_observers(&mut self, value: T) {
+        let Some(observers) = self.observers.take() else {
+            return;
+        };
+        let rank = self.count_below(&value, false);
+        let count = self.count();
+        let percentile = self.percentile;
+        let event = InsertEvent {
+            value: &value,
+            rank,
+            count,
+            percentile,
+            percentile_value: self.get_percentile_ref(),
+        };
+        for observer in &observers.list {
+            observer(&event);
+        }
+        self.observers = Some(observers);
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Registers a callback run on every insert that the tracker keeps, after the value is
+    /// stored.
+    ///
+    /// Values discarded by the accepted range or the reservoir aren't observed.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let outliers = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&outliers);
+    /// let mut latencies = PercentileTracker::<u64>::new(90);
+    /// for ms in 1..=20 {
+    ///     latencies.insert(ms);
+    /// }
+    /// latencies.add_observer(move |event| {
+    ///     if event.is_above_percentile() {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// for ms in [12, 500, 7] {
+    ///     latencies.insert(ms);
+    /// }
+    /// assert_eq!(outliers.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn add_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&InsertEvent<'_, T>) + Send + Sync + 'static,
+    {
+        self.observers
+            .get_or_insert_with(Observers::new)
+            .list
+            .push(Box::new(observer));
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Registers a callback run on every insert that the tracker keeps.
+    ///
+    /// See [`PercentileTracker::add_observer`].
+    pub fn observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&InsertEvent<'_, T>) + Send + Sync + 'static,
+    {
+        self.observers
+            .get_or_insert_with(Observers::new)
+            .list
+            .push(Box::new(observer));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RangePolicy;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_insert_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(4)
+            .accepted_range(0..=100, RangePolicy::Ignore)
+            .observer(move |event| {
+                log.lock().unwrap().push((
+                    *event.value,
+                    event.rank,
+                    event.count,
+                    *event.percentile_value,
+                    event.is_min(),
+                    event.is_max(),
+                ))
+            })
+            .build()
+            .unwrap();
+        for value in [50, 10, 90, 500, 30, 50] {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (50, 0, 1, 50, true, true),
+                (10, 0, 2, 50, true, false),
+                (90, 2, 3, 50, false, true),
+                (30, 1, 4, 50, false, false),
+                (50, 2, 5, 50, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_and_clear_observers() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut tracker = PercentileTracker::new(90);
+        tracker.insert(1u32);
+        for tag in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            tracker.add_observer(move |event| seen.lock().unwrap().push((tag, *event.value)));
+        }
+        assert_eq!(tracker.observer_count(), 2);
+        tracker.insert(2);
+        tracker.clear_observers();
+        tracker.insert(3);
+        assert_eq!(tracker.observer_count(), 0);
+        assert_eq!(*seen.lock().unwrap(), vec![("first", 2), ("second", 2)]);
+        assert_eq!(tracker.count(), 3);
+    }
+}
@@ -0,0 +1,155 @@
+//! A shadow copy of every value, used to cross-check the tracker.
+//!
+//! With the `shadow-oracle` feature, [`PercentileTrackerBuilder::shadow_oracle`] makes a
+//! tracker keep a plain sorted `Vec` of its values alongside the buckets, and every
+//! [`get_percentile`](crate::PercentileTracker::get_percentile) compares its answer with the
+//! oracle's, panicking on divergence. Each insert costs O(n) to keep the oracle sorted, so
+//! this is for tests and canaries after changing the tracker, not for production.
+
+use std::cmp::Ordering;
+use std::mem::size_of;
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// Every value held by a tracker, kept sorted.
+pub(crate) struct ShadowOracle<T> {
+    /// The tracker's values in ascending order.
+    sorted: Vec<T>,
+
+    /// Copies inserted values into the oracle. Captured by the builder, where `T: Clone` is known.
+    clone: fn(&T) -> T,
+}
+
+impl<T> ShadowOracle<T>
+where
+    T: Ord,
+{
+    /// Creates an empty oracle.
+    pub(crate) fn new(clone: fn(&T) -> T) -> Self {
+        ShadowOracle {
+            sorted: Vec::new(),
+            clone,
+        }
+    }
+
+    /// Records an inserted value.
+    pub(crate) fn insert(&mut self, value: &T) {
+        let position = self.sorted.partition_point(|stored| stored <= value);
+        self.sorted.insert(position, (self.clone)(value));
+    }
+
+    /// Returns the bytes allocated for the recorded values.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.sorted.capacity() * size_of::<T>()
+    }
+
+    /// Records a removed value.
+    ///
+    /// # Panics
+    /// Panics if the oracle never saw the value.
+    pub(crate) fn remove(&mut self, value: &T) {
+        match self.sorted.binary_search(value) {
+            Ok(position) => {
+                self.sorted.remove(position);
+            }
+            Err(_) => panic!("Shadow oracle mismatch: the tracker removed a value it never held"),
+        }
+    }
+
+    /// Replaces the recorded values with the tracker's new values.
+    pub(crate) fn reset<'a>(&mut self, values: impl Iterator<Item = &'a T>)
+    where
+        T: 'a,
+    {
+        self.sorted.clear();
+        self.sorted.extend(values.map(self.clone));
+        self.sorted.sort_unstable();
+    }
+
+    /// Checks the value the tracker reported at a rank.
+    ///
+    /// # Panics
+    /// Panics if the tracker's count or value differs from the oracle's.
+    pub(crate) fn check(&self, count: usize, rank: usize, value: &T) {
+        assert_eq!(
+            count,
+            self.sorted.len(),
+            "Shadow oracle mismatch: the tracker holds {} values but the oracle holds {}",
+            count,
+            self.sorted.len()
+        );
+        assert!(
+            self.sorted[rank].cmp(value) == Ordering::Equal,
+            "Shadow oracle mismatch: the tracker's value at rank {} of {} differs from the oracle's",
+            rank,
+            count
+        );
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Keeps a sorted copy of every value and checks each `get_percentile` against it.
+    ///
+    /// This makes inserts O(n), so it is only meant for verifying the tracker in tests.
+    pub fn shadow_oracle(mut self) -> Self {
+        self.oracle_clone = Some(T::clone);
+        self
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Cross-checks a value returned for the tracked percentile, if the oracle is enabled.
+    pub(crate) fn check_oracle(&self, value: &T) {
+        if let Some(oracle) = &self.oracle {
+            oracle.check(self.count(), self.get_target_pos(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PercentileTracker;
+
+    fn checked_tracker() -> PercentileTracker<i64> {
+        PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .reservoir(200)
+            .shadow_oracle()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_shadow_oracle_agrees() {
+        let mut tracker = checked_tracker();
+        for value in (0..2_000i64).map(|i| (i * 7919) % 2_003) {
+            tracker.insert(value);
+            tracker.get_percentile();
+            tracker.get_percentile_ref();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Shadow oracle mismatch")]
+    fn test_shadow_oracle_catches_corruption() {
+        let mut tracker = checked_tracker();
+        for value in 0..100 {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+
+        // Corrupt the sorted critical bucket behind the tracker's back
+        let (bucket_idx, _) = tracker.cursor();
+        for value in tracker.buckets.get_mut()[bucket_idx].values.iter_mut() {
+            *value += 1;
+        }
+        tracker.get_percentile();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{rank_for_percentile, Numeric, PercentileTracker};
+
+/// The multiple of the interquartile range beyond the quartiles at which Tukey's fences sit.
+const TUKEY_FENCE: f64 = 1.5;
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the first quartile, median and third quartile, or `None` if the tracker is empty.
+    pub fn quartiles(&self) -> Option<(T, T, T)> {
+        if self.count() == 0 {
+            return None;
+        }
+        let ranks =
+            [25.0, 50.0, 75.0].map(|percentile| rank_for_percentile(percentile, self.count()));
+        let mut values = self.select_ranks(&ranks).into_iter();
+        Some((values.next()?, values.next()?, values.next()?))
+    }
+
+    /// Returns the interquartile range, the spread of the middle half of the values.
+    pub fn iqr(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        let (q1, _, q3) = self.quartiles()?;
+        Some(q3.to_f64() - q1.to_f64())
+    }
+
+    /// Returns the median absolute deviation from the median, or `None` if the tracker is empty.
+    ///
+    /// This is a dispersion measure that, unlike the standard deviation, isn't dragged
+    /// around by a few extreme values. It takes two passes: one to find the median, and one
+    /// over every value to select the median of their deviations from it.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<i64>::new(50);
+    /// for value in [1, 1, 2, 2, 4, 6, 9] {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.mad(), Some(1.0));
+    /// ```
+    pub fn mad(&self) -> Option<f64>
+    where
+        T: Numeric,
+    {
+        if self.count() == 0 {
+            return None;
+        }
+        let median = self
+            .select_rank(rank_for_percentile(50.0, self.count()))
+            .to_f64();
+        let mut deviations: Vec<f64> = self
+            .buckets
+            .borrow()
+            .iter()
+            .flat_map(|bucket| bucket.values.iter())
+            .map(|value| (value.to_f64() - median).abs())
+            .collect();
+        let rank = rank_for_percentile(50.0, deviations.len());
+        let (_, mad, _) = deviations.select_nth_unstable_by(rank, f64::total_cmp);
+        Some(*mad)
+    }
+
+    /// Returns true if `x` lies outside Tukey's fences, more than 1.5 interquartile ranges
+    /// below the first quartile or above the third.
+    ///
+    /// An empty tracker has no fences, so nothing is an outlier.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(50);
+    /// for value in 100..200 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert!(!tracker.is_outlier(&150));
+    /// assert!(tracker.is_outlier(&400));
+    /// ```
+    pub fn is_outlier(&self, x: &T) -> bool
+    where
+        T: Numeric,
+    {
+        let Some((q1, _, q3)) = self.quartiles() else {
+            return false;
+        };
+        let (q1, q3) = (q1.to_f64(), q3.to_f64());
+        let fence = TUKEY_FENCE * (q3 - q1);
+        let x = x.to_f64();
+        x < q1 - fence || x > q3 + fence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quartiles_and_outliers() {
+        let mut tracker = PercentileTracker::new(90);
+        assert_eq!(tracker.quartiles(), None);
+        assert_eq!(tracker.iqr(), None);
+        assert_eq!(tracker.mad(), None);
+        assert!(!tracker.is_outlier(&0));
+
+        for value in (0..100i64).rev() {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.quartiles(), Some((25, 50, 75)));
+        assert_eq!(tracker.iqr(), Some(50.0));
+        assert!(!tracker.is_outlier(&-50));
+        assert!(tracker.is_outlier(&-51));
+        assert!(!tracker.is_outlier(&150));
+        assert!(tracker.is_outlier(&151));
+        assert_eq!(tracker.mad(), Some(25.0));
+
+        // A single extreme value barely moves the MAD
+        tracker.insert(1_000_000);
+        assert_eq!(tracker.mad(), Some(25.0));
+    }
+}
@@ -0,0 +1,195 @@
+//! A persistent tracker, where inserting returns a new version and leaves the old one intact.
+//!
+//! [`PersistentTracker`] shares structure between versions: the bucket list and each bucket
+//! are reference counted, so an insert copies the list of bucket pointers and the one bucket
+//! it changes, and every other bucket is shared with the previous version. Readers on other
+//! threads can hold any version as a consistent snapshot while a writer keeps advancing.
+
+use std::sync::Arc;
+
+use crate::{rank_for_percentile, BuildError, MAX_BUCKET_SIZE};
+
+/// An immutable percentile tracker whose [`insert`](Self::insert) returns a new version.
+///
+/// Every bucket is kept sorted, so queries take `&self` and versions are `Send + Sync`
+/// whenever `T` is. Cloning a version is O(1).
+///
+/// ```
+/// use percentiletracker::PersistentTracker;
+///
+/// let empty = PersistentTracker::<u64>::new(50);
+/// let v1 = empty.insert(10).insert(20).insert(30);
+/// let v2 = v1.insert(40).insert(50);
+///
+/// assert_eq!(empty.get_percentile(), None);
+/// assert_eq!(v1.get_percentile(), Some(&20));
+/// assert_eq!(v2.get_percentile(), Some(&30));
+/// ```
+#[derive(Debug)]
+pub struct PersistentTracker<T> {
+    /// Sorted, non-overlapping buckets in ascending order, shared between versions.
+    buckets: Arc<Vec<Arc<Vec<T>>>>,
+
+    /// Total number of values in this version.
+    count: usize,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+}
+
+impl<T> Clone for PersistentTracker<T> {
+    fn clone(&self) -> Self {
+        PersistentTracker {
+            buckets: Arc::clone(&self.buckets),
+            count: self.count,
+            percentile: self.percentile,
+        }
+    }
+}
+
+impl<T> PersistentTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Creates an empty tracker for the given percentile.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        if !(1..=99).contains(&percentile) {
+            panic!("{}", BuildError::InvalidPercentile(percentile));
+        }
+        PersistentTracker {
+            buckets: Arc::new(Vec::new()),
+            count: 0,
+            percentile,
+        }
+    }
+
+    /// Returns a new version holding every value of this one plus `value`.
+    ///
+    /// This copies the bucket list, which holds one pointer per bucket, and the bucket the
+    /// value lands in. The bucket is split in two if it grows past the maximum bucket size.
+    pub fn insert(&self, value: T) -> Self {
+        let mut buckets = Vec::clone(&self.buckets);
+        if buckets.is_empty() {
+            buckets.push(Arc::new(vec![value]));
+        } else {
+            // The last bucket whose minimum is at most the value, or the first bucket
+            let bucket_idx = buckets
+                .partition_point(|bucket| bucket[0] <= value)
+                .saturating_sub(1);
+            let mut bucket = Vec::clone(&buckets[bucket_idx]);
+            let position = bucket.partition_point(|stored| stored <= &value);
+            bucket.insert(position, value);
+            if bucket.len() > MAX_BUCKET_SIZE {
+                let upper = bucket.split_off(bucket.len() / 2);
+                buckets.insert(bucket_idx + 1, Arc::new(upper));
+            }
+            buckets[bucket_idx] = Arc::new(bucket);
+        }
+        PersistentTracker {
+            buckets: Arc::new(buckets),
+            count: self.count + 1,
+            percentile: self.percentile,
+        }
+    }
+}
+
+impl<T> PersistentTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the number of values in this version.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true if this version holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the percentile the tracker was created for.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the value at the tracked percentile, or `None` if this version is empty.
+    pub fn get_percentile(&self) -> Option<&T> {
+        self.value_at_percentile(self.percentile as f64)
+    }
+
+    /// Returns the value at an arbitrary percentile (0-100), or `None` if this version is
+    /// empty.
+    ///
+    /// This walks the bucket lengths to the bucket holding the rank, which is already sorted.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<&T> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut rank = rank_for_percentile(percentile, self.count);
+        for bucket in self.buckets.iter() {
+            if rank < bucket.len() {
+                return Some(&bucket[rank]);
+            }
+            rank -= bucket.len();
+        }
+        None
+    }
+
+    /// Iterates over every value in this version in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.buckets.iter().flat_map(|bucket| bucket.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::PercentileTracker;
+    use std::thread;
+
+    #[test]
+    fn test_versions_are_independent() {
+        let mut rng = SplitMix64(11);
+        let mut versions = vec![PersistentTracker::new(90)];
+        let mut expected = PercentileTracker::new(90);
+        for _ in 0..1_000 {
+            let value = rng.below(300);
+            versions.push(versions.last().unwrap().insert(value));
+            expected.insert(value);
+        }
+        let latest = versions.last().unwrap();
+        assert_eq!(latest.len(), 1_000);
+        assert_eq!(latest.get_percentile(), Some(&expected.get_percentile()));
+        assert!(latest.iter().eq(expected.iter_sorted()));
+        assert!(latest
+            .buckets
+            .iter()
+            .all(|bucket| bucket.len() <= MAX_BUCKET_SIZE));
+
+        // Older versions still see exactly their own values
+        for (count, version) in versions.iter().enumerate().step_by(97) {
+            assert_eq!(version.len(), count);
+            assert_eq!(version.iter().count(), count);
+            assert!(version.iter().is_sorted());
+        }
+    }
+
+    #[test]
+    fn test_readers_hold_snapshots() {
+        let mut tracker = PersistentTracker::new(50);
+        for value in 0..100i64 {
+            tracker = tracker.insert(value);
+        }
+        let snapshot = tracker.clone();
+        let reader = thread::spawn(move || *snapshot.get_percentile().unwrap());
+        for value in 100..1_000 {
+            tracker = tracker.insert(value);
+        }
+        assert_eq!(reader.join().unwrap(), 50);
+        assert_eq!(tracker.get_percentile(), Some(&500));
+    }
+}
@@ -0,0 +1,210 @@
+use std::fmt::Write;
+
+use crate::{Dimension, FrozenSnapshot, Numeric, PercentileTracker, Unit};
+
+/// How a tracker is rendered in Prometheus exposition text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrometheusFormat<'a> {
+    /// A `summary` with one sample per percentile (0-100), e.g. `&[50.0, 99.0, 99.9]`.
+    Summary { percentiles: &'a [f64] },
+
+    /// A `histogram` with cumulative buckets at the given upper bounds, in ascending order.
+    ///
+    /// Bounds are in the exported unit, i.e. seconds for time and bytes for information.
+    /// The `+Inf` bucket is always added.
+    Histogram { bounds: &'a [f64] },
+}
+
+impl<T> FrozenSnapshot<T>
+where
+    T: Numeric,
+{
+    /// Renders the snapshot as Prometheus exposition text, including the `# TYPE` line.
+    ///
+    /// Invalid characters in the metric name are replaced with underscores, and a unit
+    /// suffix is appended. Following Prometheus conventions, time values are exported in
+    /// seconds and information in bytes.
+    pub fn to_prometheus(&self, name: &str, format: PrometheusFormat<'_>) -> String {
+        let name = prometheus_name(name, self.unit());
+        let exported: Vec<f64> = self
+            .values()
+            .iter()
+            .map(|value| exported_value(*value, self.unit()))
+            .collect();
+
+        let mut out = String::new();
+        match format {
+            PrometheusFormat::Summary { percentiles } => {
+                let _ = writeln!(out, "# TYPE {} summary", name);
+                for &percentile in percentiles {
+                    if let Some(value) = self.value_at_percentile(percentile) {
+                        let _ = writeln!(
+                            out,
+                            "{}{{quantile=\"{}\"}} {}",
+                            name,
+                            quantile(percentile),
+                            exported_value(*value, self.unit())
+                        );
+                    }
+                }
+            }
+            PrometheusFormat::Histogram { bounds } => {
+                let _ = writeln!(out, "# TYPE {} histogram", name);
+                for &bound in bounds {
+                    let count = exported.partition_point(|&value| value <= bound);
+                    let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+                }
+                let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, exported.len());
+            }
+        }
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            exported.iter().fold(0.0, |sum, value| sum + value)
+        );
+        let _ = writeln!(out, "{}_count {}", name, exported.len());
+        out
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Renders the tracker as Prometheus exposition text, ready for a `/metrics` handler.
+    ///
+    /// This takes a [`snapshot`](Self::snapshot), so it costs a copy of every value.
+    ///
+    /// ```
+    /// use percentiletracker::{PercentileTracker, PrometheusFormat, Unit};
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .unit(Unit::Milliseconds)
+    ///     .build()
+    ///     .unwrap();
+    /// tracker.insert(250);
+    /// let text = tracker.to_prometheus(
+    ///     "request_latency",
+    ///     PrometheusFormat::Histogram { bounds: &[0.1, 1.0] },
+    /// );
+    /// assert!(text.contains("request_latency_seconds_bucket{le=\"1\"} 1\n"));
+    /// ```
+    pub fn to_prometheus(&self, name: &str, format: PrometheusFormat<'_>) -> String {
+        self.snapshot().to_prometheus(name, format)
+    }
+}
+
+/// Converts a percentile into a quantile.
+///
+/// Dividing by 100 would display 99.9 as `0.9990000000000001`, so the decimal point of the
+/// percentile's shortest representation is shifted instead. Every percentile keeps its full
+/// precision, so no two percentiles share a quantile label.
+pub(crate) fn quantile(percentile: f64) -> f64 {
+    format!("{}e-2", percentile)
+        .parse()
+        .unwrap_or(percentile / 100.0)
+}
+
+/// Converts a value into the base unit Prometheus expects, seconds for time.
+pub(crate) fn exported_value<T: Numeric>(value: T, unit: Unit) -> f64 {
+    unit.convert(value.to_f64(), Unit::Seconds)
+        .unwrap_or_else(|| value.to_f64())
+}
+
+/// Replaces characters Prometheus doesn't allow in metric names, and appends a base unit suffix.
+pub(crate) fn prometheus_name(name: &str, unit: Unit) -> String {
+    let mut out: String = name
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'a'..='z' | 'A'..='Z' | '_' | ':' => c,
+            '0'..='9' if i > 0 => c,
+            _ => '_',
+        })
+        .collect();
+    match unit.dimension() {
+        Dimension::Time => out.push_str("_seconds"),
+        Dimension::Information => out.push_str("_bytes"),
+        Dimension::Dimensionless => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_summary() {
+        let mut tracker = PercentileTracker::<u64>::new(90);
+        for value in 1..=1000 {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            tracker.to_prometheus(
+                "queue-depth",
+                PrometheusFormat::Summary {
+                    percentiles: &[50.0, 99.9]
+                }
+            ),
+            "# TYPE queue_depth summary\n\
+             queue_depth{quantile=\"0.5\"} 501\n\
+             queue_depth{quantile=\"0.999\"} 1000\n\
+             queue_depth_sum 500500\n\
+             queue_depth_count 1000\n"
+        );
+    }
+
+    #[test]
+    fn test_prometheus_distinct_quantiles() {
+        let mut tracker = PercentileTracker::<u64>::new(50);
+        for value in 1..=100_000 {
+            tracker.insert(value);
+        }
+        let text = tracker.to_prometheus(
+            "depth",
+            PrometheusFormat::Summary {
+                percentiles: &[99.9, 99.99, 100.0],
+            },
+        );
+        assert!(text.contains("depth{quantile=\"0.999\"} 99901\n"));
+        assert!(text.contains("depth{quantile=\"0.9999\"} 99991\n"));
+        assert!(text.contains("depth{quantile=\"1\"} 100000\n"));
+        assert_eq!(quantile(12.345), 0.12345);
+    }
+
+    #[test]
+    fn test_prometheus_histogram() {
+        let mut tracker = PercentileTracker::<u64>::builder()
+            .unit(Unit::Microseconds)
+            .build()
+            .unwrap();
+        for value in [500, 1500, 2000, 250_000] {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            tracker.to_prometheus(
+                "rpc",
+                PrometheusFormat::Histogram {
+                    bounds: &[0.001, 0.002, 0.1]
+                }
+            ),
+            "# TYPE rpc_seconds histogram\n\
+             rpc_seconds_bucket{le=\"0.001\"} 1\n\
+             rpc_seconds_bucket{le=\"0.002\"} 3\n\
+             rpc_seconds_bucket{le=\"0.1\"} 3\n\
+             rpc_seconds_bucket{le=\"+Inf\"} 4\n\
+             rpc_seconds_sum 0.254\n\
+             rpc_seconds_count 4\n"
+        );
+
+        let empty = PercentileTracker::<u64>::new(50).to_prometheus(
+            "empty",
+            PrometheusFormat::Summary {
+                percentiles: &[50.0],
+            },
+        );
+        assert_eq!(empty, "# TYPE empty summary\nempty_sum 0\nempty_count 0\n");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{Numeric, PercentileTracker};
+
+/// How a percentile is estimated from the sorted values, following the nine sample quantile
+/// definitions of Hyndman and Fan (1996).
+///
+/// Statistical tools disagree on the definition of a percentile, so choose the method that
+/// matches whatever the results will be compared against. The first three are discontinuous
+/// and always return a stored value (or, for `R2`, the average of two), while the others
+/// interpolate linearly between the two values straddling the percentile's position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QuantileMethod {
+    /// Inverse of the empirical distribution function. SAS method 3.
+    R1,
+    /// Like `R1`, but averaging at discontinuities. SAS method 5.
+    R2,
+    /// The observation closest to `n * p`, ties to even. SAS method 2.
+    R3,
+    /// Linear interpolation of the empirical distribution function. SAS method 1.
+    R4,
+    /// Piecewise linear with knots midway between the values. Common in hydrology.
+    R5,
+    /// `(n + 1) * p` positions. Minitab, SPSS and Excel's `PERCENTILE.EXC`.
+    R6,
+    /// `(n - 1) * p + 1` positions. R's and numpy's default, and Excel's `PERCENTILE.INC`.
+    #[default]
+    R7,
+    /// Approximately median-unbiased regardless of the distribution. Recommended by Hyndman and Fan.
+    R8,
+    /// Approximately unbiased if the values are normally distributed.
+    R9,
+}
+
+impl QuantileMethod {
+    /// Locates a quantile `q` (0-1) among `n` sorted values.
+    ///
+    /// Returns the zero-based ranks of the two straddling values and the weight of the upper
+    /// one. Discontinuous methods return a weight of zero, except `R2` which averages.
+    fn locate(self, q: f64, n: usize) -> (usize, usize, f64) {
+        let n_f = n as f64;
+        let h = match self {
+            QuantileMethod::R1 | QuantileMethod::R2 | QuantileMethod::R5 => n_f * q + 0.5,
+            QuantileMethod::R3 | QuantileMethod::R4 => n_f * q,
+            QuantileMethod::R6 => (n_f + 1.0) * q,
+            QuantileMethod::R7 => (n_f - 1.0) * q + 1.0,
+            QuantileMethod::R8 => (n_f + 1.0 / 3.0) * q + 1.0 / 3.0,
+            QuantileMethod::R9 => (n_f + 0.25) * q + 0.375,
+        };
+        // Converts a one-based position into a clamped zero-based rank
+        let rank = |position: f64| (position.max(1.0).min(n_f) as usize) - 1;
+        match self {
+            QuantileMethod::R1 => (rank((h - 0.5).ceil()), rank((h - 0.5).ceil()), 0.0),
+            QuantileMethod::R2 => (rank((h - 0.5).ceil()), rank((h + 0.5).floor()), 0.5),
+            QuantileMethod::R3 => (rank(h.round_ties_even()), rank(h.round_ties_even()), 0.0),
+            _ => {
+                let h = h.clamp(1.0, n_f);
+                (rank(h.floor()), rank(h.floor() + 1.0), h - h.floor())
+            }
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Returns the tracked percentile estimated with the tracker's [`QuantileMethod`].
+    /// Panics if the tracker is empty.
+    ///
+    /// See [`interpolated_percentile`](Self::interpolated_percentile).
+    pub fn get_percentile_interpolated(&self) -> f64 {
+        self.interpolated_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty tracker")
+    }
+
+    /// Returns a percentile (0-100) estimated with the tracker's [`QuantileMethod`], or
+    /// `None` if the tracker is empty.
+    ///
+    /// With the default [`QuantileMethod::R7`], the position of percentile `p` among `n`
+    /// sorted values is `p / 100 * (n - 1)`, the same definition as numpy's default `linear`
+    /// method and Excel's `PERCENTILE.INC`. Unlike [`get_percentile`](Self::get_percentile),
+    /// the result moves smoothly as values arrive instead of jumping between stored values.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(50);
+    /// for value in [10, 20, 30, 40] {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.interpolated_percentile(50.0), Some(25.0));
+    /// assert_eq!(tracker.interpolated_percentile(90.0), Some(37.0));
+    /// ```
+    pub fn interpolated_percentile(&self, percentile: f64) -> Option<f64> {
+        if self.count() == 0 {
+            return None;
+        }
+        let q = (percentile / 100.0).clamp(0.0, 1.0);
+        let (lower, upper, weight) = self.quantile_method.locate(q, self.count());
+        let values = self.select_ranks(&[lower, upper]);
+        let (lower_value, upper_value) = (values[0].to_f64(), values[1].to_f64());
+        Some(lower_value + (upper_value - lower_value) * weight)
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the method used by the interpolated percentile queries.
+    pub fn quantile_method(&self) -> QuantileMethod {
+        self.quantile_method
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolated_percentile() {
+        let mut tracker = PercentileTracker::new(90);
+        assert_eq!(tracker.interpolated_percentile(50.0), None);
+        tracker.insert(7i64);
+        assert_eq!(tracker.get_percentile_interpolated(), 7.0);
+
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        for value in (0..=100i64).rev() {
+            tracker.insert(value * 2);
+        }
+        assert_eq!(tracker.get_percentile_interpolated(), 180.0);
+        assert_eq!(tracker.interpolated_percentile(0.0), Some(0.0));
+        assert_eq!(tracker.interpolated_percentile(100.0), Some(200.0));
+        assert!((tracker.interpolated_percentile(12.345).unwrap() - 24.69).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_quantile_methods() {
+        // Reference values from R's `quantile(1:10 * 10, c(0.1, 0.25, 0.5, 0.9), type = i)`
+        let expected = [
+            (QuantileMethod::R1, [10.0, 30.0, 50.0, 90.0]),
+            (QuantileMethod::R2, [15.0, 30.0, 55.0, 95.0]),
+            (QuantileMethod::R3, [10.0, 20.0, 50.0, 90.0]),
+            (QuantileMethod::R4, [10.0, 25.0, 50.0, 90.0]),
+            (QuantileMethod::R5, [15.0, 30.0, 55.0, 95.0]),
+            (QuantileMethod::R6, [11.0, 27.5, 55.0, 99.0]),
+            (QuantileMethod::R7, [19.0, 32.5, 55.0, 91.0]),
+            (
+                QuantileMethod::R8,
+                [41.0 / 3.0, 175.0 / 6.0, 55.0, 289.0 / 3.0],
+            ),
+            (QuantileMethod::R9, [14.0, 29.375, 55.0, 96.0]),
+        ];
+        for (method, quantiles) in expected {
+            let mut tracker = PercentileTracker::builder()
+                .quantile_method(method)
+                .build()
+                .unwrap();
+            for value in (1..=10u64).rev() {
+                tracker.insert(value * 10);
+            }
+            assert_eq!(tracker.quantile_method(), method);
+            for (percentile, expected) in [10.0, 25.0, 50.0, 90.0].into_iter().zip(quantiles) {
+                let actual = tracker.interpolated_percentile(percentile).unwrap();
+                assert!(
+                    (actual - expected).abs() < 1e-9,
+                    "{:?} p{} was {}, expected {}",
+                    method,
+                    percentile,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Radix sorting for integer values.
+//!
+//! Reads spend most of their time sorting the critical bucket. For integer values, a
+//! least-significant-digit radix sort is linear in the bucket size, and digits on which every
+//! value agrees are skipped, so values spanning a small range need only a pass or two. Small
+//! buckets still use `sort_unstable`, which wins below a few hundred values.
+
+use crate::PercentileTrackerBuilder;
+
+/// Buckets smaller than this are sorted with `sort_unstable` instead.
+const RADIX_SORT_THRESHOLD: usize = 256;
+
+/// Values that can be ordered by an unsigned 64-bit key, for radix sorting.
+pub trait RadixKey: Copy + Ord {
+    /// Returns a key whose unsigned order matches the order of the values.
+    fn radix_key(self) -> u64;
+}
+
+macro_rules! impl_radix_key_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl RadixKey for $t {
+                fn radix_key(self) -> u64 {
+                    self as u64
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_radix_key_signed {
+    ($($t:ty),*) => {
+        $(
+            impl RadixKey for $t {
+                // Flipping the sign bit moves negative values below positive ones
+                fn radix_key(self) -> u64 {
+                    (self as i64 as u64) ^ (1 << 63)
+                }
+            }
+        )*
+    };
+}
+
+impl_radix_key_unsigned!(u8, u16, u32, u64, usize);
+impl_radix_key_signed!(i8, i16, i32, i64, isize);
+
+/// Sorts values with an LSD radix sort on 8-bit digits.
+pub(crate) fn radix_sort<T: RadixKey>(values: &mut [T]) {
+    if values.len() < RADIX_SORT_THRESHOLD {
+        values.sort_unstable();
+        return;
+    }
+
+    let mut counts = [[0usize; 256]; 8];
+    for value in values.iter() {
+        let key = value.radix_key();
+        for (digit, digit_counts) in counts.iter_mut().enumerate() {
+            digit_counts[((key >> (digit * 8)) & 0xFF) as usize] += 1;
+        }
+    }
+
+    let mut scratch = values.to_vec();
+    let mut in_scratch = false;
+    for (digit, digit_counts) in counts.iter().enumerate() {
+        // Every value has the same digit here, so this pass wouldn't move anything
+        if digit_counts.contains(&values.len()) {
+            continue;
+        }
+        let mut offsets = [0usize; 256];
+        let mut total = 0;
+        for (offset, &count) in offsets.iter_mut().zip(digit_counts) {
+            *offset = total;
+            total += count;
+        }
+
+        let (from, to) = if in_scratch {
+            (&scratch[..], &mut values[..])
+        } else {
+            (&values[..], &mut scratch[..])
+        };
+        for &value in from {
+            let slot = &mut offsets[((value.radix_key() >> (digit * 8)) & 0xFF) as usize];
+            to[*slot] = value;
+            *slot += 1;
+        }
+        in_scratch = !in_scratch;
+    }
+    if in_scratch {
+        values.copy_from_slice(&scratch);
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: RadixKey,
+{
+    /// Sorts buckets with a radix sort instead of a comparison sort.
+    ///
+    /// This speeds up reads on large buckets of integers, e.g. when the tracker is queried
+    /// rarely relative to inserts, and makes no difference for small buckets.
+    pub fn radix_sort(mut self) -> Self {
+        self.sort_values = Some(radix_sort::<T>);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::PercentileTracker;
+
+    #[test]
+    fn test_radix_sort_matches_sort() {
+        let mut rng = SplitMix64(7);
+        let mut wide: Vec<i64> = (0..5_000).map(|_| rng.next() as i64).collect();
+        let mut narrow: Vec<u32> = (0..5_000).map(|_| rng.below(1_000) as u32).collect();
+        let mut small: Vec<i8> = (0..100).map(|_| rng.next() as i8).collect();
+
+        let mut expected = wide.clone();
+        expected.sort_unstable();
+        radix_sort(&mut wide);
+        assert_eq!(wide, expected);
+
+        let mut expected = narrow.clone();
+        expected.sort_unstable();
+        radix_sort(&mut narrow);
+        assert_eq!(narrow, expected);
+
+        let mut expected = small.clone();
+        expected.sort_unstable();
+        radix_sort(&mut small);
+        assert_eq!(small, expected);
+    }
+
+    #[test]
+    fn test_radix_sorted_tracker() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(1_000)
+            .radix_sort()
+            .build()
+            .unwrap();
+        let mut rng = SplitMix64(11);
+        let mut values = Vec::new();
+        for _ in 0..3_000 {
+            let value = rng.next() as i64;
+            tracker.insert(value);
+            values.push(value);
+        }
+        values.sort_unstable();
+        assert_eq!(tracker.get_percentile(), values[(values.len() * 90) / 100]);
+        assert_eq!(tracker.kth_smallest(1_234), Some(values[1_233]));
+    }
+}
@@ -0,0 +1,184 @@
+//! Screening inserted values against an accepted range.
+//!
+//! A tracker built with [`PercentileTrackerBuilder::accepted_range`] checks every inserted
+//! value against the range first, and handles values outside it according to a
+//! [`RangePolicy`]. This keeps glitches like a sensor reporting `u32::MAX` from poisoning the
+//! extreme percentiles without filtering at every call site.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// What a tracker does with an inserted value outside its accepted range.
+pub enum RangePolicy<T> {
+    /// Discards the value.
+    Ignore,
+
+    /// Inserts the nearest bound of the range instead of the value.
+    Clamp,
+
+    /// Discards the value after passing it to a callback, e.g. to log the glitch.
+    Callback(Box<dyn Fn(&T) + Send + Sync>),
+}
+
+impl<T> fmt::Debug for RangePolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangePolicy::Ignore => f.write_str("Ignore"),
+            RangePolicy::Clamp => f.write_str("Clamp"),
+            RangePolicy::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+/// The accepted range of a tracker and how it handles values outside it.
+pub(crate) struct RangeFilter<T> {
+    min: T,
+    max: T,
+    policy: RangePolicy<T>,
+
+    /// Copies a bound when clamping. Captured by the builder, where `T: Clone` is known.
+    clone: fn(&T) -> T,
+
+    /// The number of inserted values that were outside the range.
+    out_of_range: u64,
+}
+
+impl<T> RangeFilter<T>
+where
+    T: Ord,
+{
+    /// Returns true if the range is empty, i.e. its start is above its end.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    /// Returns the value to insert in place of `value`, or `None` if it should be discarded.
+    fn screen(&mut self, value: T) -> Option<T> {
+        let bound = if value < self.min {
+            &self.min
+        } else if value > self.max {
+            &self.max
+        } else {
+            return Some(value);
+        };
+        self.out_of_range += 1;
+        match &self.policy {
+            RangePolicy::Ignore => None,
+            RangePolicy::Clamp => Some((self.clone)(bound)),
+            RangePolicy::Callback(callback) => {
+                callback(&value);
+                None
+            }
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the number of inserted values that were outside the accepted range, whether
+    /// they were discarded or clamped. This is always 0 without an accepted range.
+    pub fn out_of_range(&self) -> u64 {
+        self.range_filter
+            .as_ref()
+            .map_or(0, |filter| filter.out_of_range)
+    }
+
+    /// Screens an inserted value against the accepted range, if there is one.
+    pub(crate) fn screen(&mut self, value: T) -> Option<T> {
+        match &mut self.range_filter {
+            Some(filter) => filter.screen(value),
+            None => Some(value),
+        }
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Clone + Ord,
+{
+    /// Only accepts values within `range`, handling any others according to `policy`.
+    ///
+    /// ```
+    /// use percentiletracker::{PercentileTracker, RangePolicy};
+    ///
+    /// let mut temperatures = PercentileTracker::<i32>::builder()
+    ///     .percentile(99)
+    ///     .accepted_range(-50..=60, RangePolicy::Ignore)
+    ///     .build()
+    ///     .unwrap();
+    /// for celsius in [21, 23, 22, 32_767, 24] {
+    ///     temperatures.insert(celsius);
+    /// }
+    /// assert_eq!(temperatures.max(), Some(24));
+    /// assert_eq!(temperatures.out_of_range(), 1);
+    /// ```
+    pub fn accepted_range(mut self, range: RangeInclusive<T>, policy: RangePolicy<T>) -> Self {
+        let (min, max) = range.into_inner();
+        self.range_filter = Some(RangeFilter {
+            min,
+            max,
+            policy,
+            clone: T::clone,
+            out_of_range: 0,
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildError;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_clamp() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .accepted_range(0..=100, RangePolicy::Clamp)
+            .build()
+            .unwrap();
+        for value in [-5, 50, 500, 100, 0] {
+            tracker.insert(value);
+        }
+        assert!(tracker.iter_sorted().eq(&[0, 0, 50, 100, 100]));
+        assert_eq!(tracker.out_of_range(), 2);
+    }
+
+    #[test]
+    fn test_callback() {
+        let glitches = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&glitches);
+        let mut tracker = PercentileTracker::builder()
+            .accepted_range(
+                1..=1_000u64,
+                RangePolicy::Callback(Box::new(move |&value| seen.lock().unwrap().push(value))),
+            )
+            .build()
+            .unwrap();
+        for value in [0, 10, u64::MAX, 20] {
+            tracker.insert(value);
+        }
+        assert_eq!(*glitches.lock().unwrap(), vec![0, u64::MAX]);
+        assert_eq!(tracker.count(), 2);
+        assert_eq!(tracker.seen(), 2);
+        assert_eq!(tracker.out_of_range(), 2);
+        assert_eq!(PercentileTracker::<u8>::new(50).out_of_range(), 0);
+    }
+
+    #[test]
+    fn test_empty_range() {
+        let (start, end) = (10, 1);
+        assert_eq!(
+            PercentileTracker::builder()
+                .accepted_range(start..=end, RangePolicy::Ignore)
+                .build()
+                .err(),
+            Some(BuildError::EmptyRange)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::PercentileTracker;
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the fraction of values less than or equal to `x`, or 0 if the tracker is empty.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for latency_ms in [50, 120, 180, 250, 900] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.cdf(&200), 0.6);
+    /// ```
+    pub fn cdf(&self, x: &T) -> f64 {
+        if self.count() == 0 {
+            return 0.0;
+        }
+        self.count_below(x, true) as f64 / self.count() as f64
+    }
+
+    /// Returns the percentile rank of `x` (0-100), or 0 if the tracker is empty.
+    ///
+    /// This is the share of values below `x`, counting values equal to `x` as half below, so
+    /// a value in the middle of a run of duplicates isn't pushed to either end of it. It's
+    /// found from the bucket lengths and a rank within the one bucket that straddles `x`.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut latencies = PercentileTracker::<u64>::new(99);
+    /// for ms in 1..=1_000 {
+    ///     latencies.insert(ms);
+    /// }
+    /// assert_eq!(latencies.percentile_of(&230), 22.95);
+    /// assert_eq!(latencies.percentile_of(&5_000), 100.0);
+    /// ```
+    pub fn percentile_of(&self, x: &T) -> f64 {
+        if self.count() == 0 {
+            return 0.0;
+        }
+        let below = self.count_below(x, false);
+        let at_most = self.count_below(x, true);
+        100.0 * (below + at_most) as f64 / (2 * self.count()) as f64
+    }
+
+    /// Returns the rank `x` would have among the values, i.e. how many values are smaller.
+    pub fn rank_of(&self, x: &T) -> usize {
+        self.count_below(x, false)
+    }
+
+    /// Returns the value at a zero-based rank in sorted order, or `None` if the rank is out
+    /// of bounds. Rank 0 is the smallest value.
+    ///
+    /// Only the bucket holding the rank is sorted, and the percentile cursor isn't moved.
+    pub fn value_at_rank(&self, rank: usize) -> Option<T> {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return sketch.value_at_rank(rank as u64);
+        }
+        (rank < self.count()).then(|| self.select_rank(rank))
+    }
+
+    /// Returns the `k`th smallest value, where `k = 1` is the minimum, or `None` if `k` is 0
+    /// or larger than the number of values.
+    ///
+    /// The owning bucket is found from the bucket lengths, and the value is selected within
+    /// it with `select_nth_unstable`, which is O(bucket size) rather than a sort.
+    pub fn kth_smallest(&self, k: usize) -> Option<T> {
+        if k == 0 || k > self.count() {
+            return None;
+        }
+        Some(self.select_in_place(k - 1))
+    }
+
+    /// Returns the `k`th largest value, where `k = 1` is the maximum, or `None` if `k` is 0
+    /// or larger than the number of values.
+    pub fn kth_largest(&self, k: usize) -> Option<T> {
+        if k == 0 || k > self.count() {
+            return None;
+        }
+        Some(self.select_in_place(self.count() - k))
+    }
+
+    /// Returns the `k` largest values, largest first. Returns every value if there are fewer than `k`.
+    ///
+    /// Values are taken from the last buckets, and only the bucket where the cut falls is
+    /// partitioned, so this costs little more than sorting the `k` values themselves.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for latency_ms in [40, 900, 12, 350, 75] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.top_k(2), vec![900, 350]);
+    /// assert_eq!(tracker.bottom_k(2), vec![12, 40]);
+    /// ```
+    pub fn top_k(&self, k: usize) -> Vec<T> {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut values = Vec::with_capacity(k.min(self.count()));
+        for bucket in buckets.iter_mut().rev() {
+            let needed = k - values.len();
+            if needed == 0 {
+                break;
+            }
+            if bucket.len() > needed {
+                let cut = bucket.len() - needed;
+                bucket.select_nth(cut);
+                values.extend_from_slice(&bucket.values[cut..]);
+            } else {
+                values.extend_from_slice(&bucket.values);
+            }
+        }
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        values
+    }
+
+    /// Returns the `k` smallest values, smallest first. Returns every value if there are fewer than `k`.
+    pub fn bottom_k(&self, k: usize) -> Vec<T> {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut values = Vec::with_capacity(k.min(self.count()));
+        for bucket in buckets.iter_mut() {
+            let needed = k - values.len();
+            if needed == 0 {
+                break;
+            }
+            if bucket.len() > needed {
+                bucket.select_nth(needed - 1);
+                values.extend_from_slice(&bucket.values[..needed]);
+            } else {
+                values.extend_from_slice(&bucket.values);
+            }
+        }
+        values.sort_unstable();
+        values
+    }
+
+    /// Returns the value at a zero-based rank by partitioning its bucket around it.
+    ///
+    /// Unlike [`select_rank`](Self::select_rank), this leaves an unsorted bucket unsorted.
+    ///
+    /// # Panics
+    /// Panics if the rank is out of bounds.
+    fn select_in_place(&self, rank: usize) -> T {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut offset = 0;
+        for bucket in buckets.iter_mut() {
+            if rank < offset + bucket.len() {
+                return bucket.select_nth(rank - offset).clone();
+            }
+            offset += bucket.len();
+        }
+        panic!(
+            "Rank {} is out of bounds for a tracker holding {} values",
+            rank,
+            self.count()
+        );
+    }
+
+    /// Returns the exact number of values strictly less than `x`.
+    pub fn count_less_than(&self, x: &T) -> usize {
+        self.count_below(x, false)
+    }
+
+    /// Returns the exact number of values within a range, e.g. `lo..hi` or `lo..=hi`.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for latency_ms in [50, 120, 180, 250, 900] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.count_in_range(100..250), 2);
+    /// assert_eq!(tracker.count_in_range(100..=250), 3);
+    /// assert_eq!(tracker.count_in_range(..), 5);
+    /// ```
+    pub fn count_in_range(&self, range: impl RangeBounds<T>) -> usize {
+        let end = match range.end_bound() {
+            Bound::Included(end) => self.count_below(end, true),
+            Bound::Excluded(end) => self.count_below(end, false),
+            Bound::Unbounded => self.count(),
+        };
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.count_below(start, false),
+            Bound::Excluded(start) => self.count_below(start, true),
+            Bound::Unbounded => 0,
+        };
+        end.saturating_sub(start)
+    }
+
+    /// Returns how many times `x` occurs among the values.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u16>::new(50);
+    /// for status in [200, 200, 404, 200, 500] {
+    ///     tracker.insert(status);
+    /// }
+    /// assert_eq!(tracker.count_of(&200), 3);
+    /// assert_eq!(tracker.count_of(&301), 0);
+    /// ```
+    pub fn count_of(&self, x: &T) -> usize {
+        self.count_below(x, true) - self.count_below(x, false)
+    }
+
+    /// Counts the values in each bucket delimited by ascending boundaries.
+    ///
+    /// `k` boundaries produce `k + 1` counts: values below the first boundary, values in
+    /// each half-open range `[boundaries[i], boundaries[i + 1])`, and values at or above the
+    /// last boundary. Only the tracker buckets that straddle a boundary are sorted.
+    ///
+    /// # Panics
+    /// Panics if the boundaries are not in ascending order.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for latency_ms in [5, 20, 40, 90, 150, 700] {
+    ///     tracker.insert(latency_ms);
+    /// }
+    /// assert_eq!(tracker.histogram(&[10, 100, 500]), vec![1, 3, 1, 1]);
+    /// ```
+    pub fn histogram(&self, boundaries: &[T]) -> Vec<u64> {
+        assert!(
+            boundaries.is_sorted(),
+            "Histogram boundaries must be in ascending order"
+        );
+        let mut counts = Vec::with_capacity(boundaries.len() + 1);
+        let mut previous = 0;
+        for boundary in boundaries {
+            let below = self.count_below(boundary, false);
+            counts.push((below - previous) as u64);
+            previous = below;
+        }
+        counts.push((self.count() - previous) as u64);
+        counts
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Counts the values below `x`, or at most `x` if `inclusive` is set.
+    ///
+    /// Each bucket's values lie between its minimum and the next bucket's minimum, so the
+    /// bucket minimums locate the single bucket that straddles `x`. Every bucket before it is
+    /// counted whole, and only the straddling bucket is sorted to count its share.
+    pub(crate) fn count_below(&self, x: &T, inclusive: bool) -> usize {
+        let below = |value: &T| if inclusive { value <= x } else { value < x };
+        let mut buckets = self.buckets.borrow_mut();
+        let straddling = match buckets.partition_point(|bucket| below(bucket.min())) {
+            0 => return 0,
+            end => end - 1,
+        };
+        let before: usize = buckets[..straddling]
+            .iter()
+            .map(|bucket| bucket.len())
+            .sum();
+        let bucket = &mut buckets[straddling];
+        self.sort_bucket(bucket);
+        before + bucket.values.partition_point(below)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_counting() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.cdf(&0), 0.0);
+
+        let mut values = Vec::new();
+        for value in (0..500i64).map(|i| (i * 7919) % 101) {
+            tracker.insert(value);
+            values.push(value);
+            if value % 7 == 0 {
+                tracker.get_percentile();
+            }
+        }
+        for x in [-1, 0, 1, 50, 99, 100, 101] {
+            let expected = values.iter().filter(|&&value| value <= x).count();
+            assert_eq!(tracker.count_below(&x, true), expected, "at {}", x);
+            let expected = values.iter().filter(|&&value| value < x).count();
+            assert_eq!(tracker.count_below(&x, false), expected, "below {}", x);
+        }
+        assert_eq!(
+            tracker.count_less_than(&50),
+            values.iter().filter(|&&v| v < 50).count()
+        );
+        assert_eq!(
+            tracker.count_in_range(10..20),
+            values.iter().filter(|&&v| (10..20).contains(&v)).count()
+        );
+        assert_eq!(
+            tracker.count_in_range((Bound::Excluded(10), Bound::Included(20))),
+            values.iter().filter(|&&v| v > 10 && v <= 20).count()
+        );
+        assert_eq!(
+            tracker.count_in_range(90..),
+            values.iter().filter(|&&v| v >= 90).count()
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let inverted = tracker.count_in_range(20..10);
+        assert_eq!(inverted, 0);
+        let histogram = tracker.histogram(&[0, 10, 10, 50]);
+        assert_eq!(histogram.iter().sum::<u64>(), values.len() as u64);
+        assert_eq!(histogram[0], 0);
+        assert_eq!(histogram[1], tracker.count_in_range(0..10) as u64);
+        assert_eq!(histogram[2], 0);
+        assert_eq!(histogram[4], tracker.count_in_range(50..) as u64);
+        assert_eq!(tracker.histogram(&[]), vec![values.len() as u64]);
+        for x in [-1, 0, 37, 100] {
+            let expected = values.iter().filter(|&&value| value == x).count();
+            assert_eq!(tracker.count_of(&x), expected);
+        }
+        assert_eq!(tracker.rank_of(&50), tracker.count_less_than(&50));
+        let mut sorted = values.clone();
+        sorted.sort_unstable();
+        for rank in [0, 1, 250, values.len() - 1] {
+            assert_eq!(tracker.value_at_rank(rank), Some(sorted[rank]));
+        }
+        assert_eq!(tracker.value_at_rank(values.len()), None);
+        assert_eq!(tracker.kth_smallest(1), sorted.first().copied());
+        assert_eq!(tracker.kth_smallest(42), Some(sorted[41]));
+        assert_eq!(tracker.kth_largest(1), sorted.last().copied());
+        assert_eq!(tracker.kth_largest(42), Some(sorted[sorted.len() - 42]));
+        assert_eq!(tracker.kth_smallest(0), None);
+        for k in [0, 1, 7, 100, sorted.len(), sorted.len() + 5] {
+            let bottom = &sorted[..k.min(sorted.len())];
+            assert_eq!(tracker.bottom_k(k), bottom);
+            let mut top = sorted[sorted.len() - k.min(sorted.len())..].to_vec();
+            top.reverse();
+            assert_eq!(tracker.top_k(k), top);
+        }
+        assert_eq!(tracker.kth_largest(sorted.len() + 1), None);
+        assert_eq!(tracker.cdf(&100), 1.0);
+        assert_eq!(tracker.cdf(&-1), 0.0);
+        assert!(tracker.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_percentile_of() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.percentile_of(&3), 0.0);
+        for value in [5, 1, 3, 3, 3, 9, 7, 3] {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        assert_eq!(tracker.percentile_of(&0), 0.0);
+        assert_eq!(tracker.percentile_of(&1), 6.25);
+        // Four 3s after one smaller value: (1 + 5) / 2 of 8 values
+        assert_eq!(tracker.percentile_of(&3), 37.5);
+        assert_eq!(tracker.percentile_of(&4), 62.5);
+        assert_eq!(tracker.percentile_of(&9), 93.75);
+        assert_eq!(tracker.percentile_of(&10), 100.0);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::{Debug, Write};
+
+use crate::{Dimension, Numeric, PercentileTracker, DELTA_PERCENTILES};
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Renders the distribution as a text bar chart, one row per bin, for terminals and test
+    /// output.
+    ///
+    /// The range between the smallest and largest value is split into `bins` equal-width bins.
+    /// Each row shows the bin's range, a bar of up to `width` characters scaled to the fullest
+    /// bin, and the number of values, followed by the percentiles (p50, p90, p99, p99.9)
+    /// that fall in the bin.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(90);
+    /// for value in 0..100 {
+    ///     tracker.insert(value);
+    /// }
+    /// print!("{}", tracker.render_histogram(40, 10));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `bins` is 0.
+    pub fn render_histogram(&self, width: usize, bins: usize) -> String {
+        assert!(bins > 0, "A histogram needs at least one bin");
+        let (Some(min), Some(max)) = (self.min(), self.max()) else {
+            return "(no values)\n".to_string();
+        };
+        let min = min.to_f64();
+        let span = max.to_f64() - min;
+        let bin_of = |value: T| {
+            if span == 0.0 {
+                return 0;
+            }
+            (((value.to_f64() - min) / span * bins as f64) as usize).min(bins - 1)
+        };
+
+        let mut counts = vec![0usize; bins];
+        for bucket in self.buckets.borrow().iter() {
+            for &value in &bucket.values {
+                counts[bin_of(value)] += 1;
+            }
+        }
+        let mut markers = vec![Vec::new(); bins];
+        for (percentile, value) in self.summary_at(&DELTA_PERCENTILES).percentiles {
+            markers[bin_of(value)].push(format!("p{}", percentile));
+        }
+
+        let bin_width = span / bins as f64;
+        let label = |value: f64| match self.unit.dimension() {
+            Dimension::Dimensionless if bin_width >= 1.0 => format!("{:.0}", value),
+            Dimension::Dimensionless => format!("{:.2}", value),
+            _ => self.unit.format(value),
+        };
+        let bounds: Vec<String> = (0..=bins)
+            .map(|bin| label(min + bin_width * bin as f64))
+            .collect();
+        let label_width = bounds.iter().map(|bound| bound.len()).max().unwrap_or(0);
+        let fullest = counts.iter().copied().max().unwrap_or(0).max(1);
+
+        let mut out = String::new();
+        for (bin, count) in counts.iter().enumerate() {
+            let bar = "#".repeat((count * width).div_ceil(fullest));
+            let _ = write!(
+                out,
+                "{:>lw$} .. {:<lw$} | {:<width$} {}",
+                bounds[bin],
+                bounds[bin + 1],
+                bar,
+                count,
+                lw = label_width,
+                width = width
+            );
+            if !markers[bin].is_empty() {
+                let _ = write!(out, " <- {}", markers[bin].join(" "));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord + Debug,
+{
+    /// Describes the bucket structure as indented text, for debugging rebalancing and for
+    /// regression reports.
+    ///
+    /// The first line summarizes the tracker and the percentile cursor, and each following
+    /// line describes one bucket: its size, range, whether it is sorted, and whether the
+    /// cursor points at it. Like [`stats`](Self::stats), this doesn't rebalance first, so it
+    /// shows the structure exactly as the last operation left it.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .percentile(90)
+    ///     .max_bucket_size(4)
+    ///     .build()
+    ///     .unwrap();
+    /// for value in 0..6 {
+    ///     tracker.insert(value);
+    /// }
+    /// tracker.get_percentile();
+    /// assert_eq!(
+    ///     tracker.dump_structure(),
+    ///     concat!(
+    ///         "p90 of 6 values in 2 buckets (max size 4), target rank 5, cursor at bucket 1 offset 3\n",
+    ///         "  bucket 0: 3 values in [0, 2], unsorted\n",
+    ///         "  bucket 1: 3 values in [3, 5], sorted <- cursor\n",
+    ///     )
+    /// );
+    /// ```
+    pub fn dump_structure(&self) -> String {
+        let buckets = self.buckets.borrow();
+        let (cursor_bucket, cursor_offset) = self.cursor();
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "p{} of {} values in {} buckets (max size {}), target rank {}, cursor at bucket {} offset {}",
+            self.percentile,
+            self.count(),
+            buckets.len(),
+            self.max_bucket_size,
+            self.get_target_pos(),
+            cursor_bucket,
+            cursor_offset
+        );
+        if self.needs_rebalancing.get() {
+            out.push_str(", needs rebalancing");
+        }
+        out.push('\n');
+        for (idx, bucket) in buckets.iter().enumerate() {
+            let _ = write!(
+                out,
+                "  bucket {}: {} values in [{:?}, {:?}], {}",
+                idx,
+                bucket.len(),
+                bucket.min(),
+                bucket.max(),
+                if bucket.sorted { "sorted" } else { "unsorted" }
+            );
+            if idx == cursor_bucket {
+                out.push_str(" <- cursor");
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Describes the bucket structure as a Graphviz DOT graph, with one record per bucket
+    /// in order and the cursor pointing at its bucket.
+    ///
+    /// Render it with e.g. `dot -Tsvg`. Like [`dump_structure`](Self::dump_structure), this
+    /// doesn't rebalance first.
+    pub fn dump_dot(&self) -> String {
+        let buckets = self.buckets.borrow();
+        let (cursor_bucket, cursor_offset) = self.cursor();
+        let mut out = String::from("digraph tracker {\n  rankdir=LR;\n  node [shape=record];\n");
+        for (idx, bucket) in buckets.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "  b{} [label=\"#{}|{} values|{}|{}\"];",
+                idx,
+                idx,
+                bucket.len(),
+                escape_record(&format!("[{:?}, {:?}]", bucket.min(), bucket.max())),
+                if bucket.sorted { "sorted" } else { "unsorted" }
+            );
+            if idx > 0 {
+                let _ = writeln!(out, "  b{} -> b{};", idx - 1, idx);
+            }
+        }
+        if !buckets.is_empty() {
+            let _ = writeln!(
+                out,
+                "  cursor [shape=plaintext, label=\"p{} rank {}\\noffset {}\"];\n  cursor -> b{};",
+                self.percentile,
+                self.get_target_pos(),
+                cursor_offset,
+                cursor_bucket
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Escapes the characters that are special inside a DOT record label.
+fn escape_record(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Unit;
+
+    #[test]
+    fn test_render_histogram() {
+        let mut tracker = PercentileTracker::<u64>::new(50);
+        assert_eq!(tracker.render_histogram(10, 4), "(no values)\n");
+
+        for value in (0..=100).chain([0; 25]) {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            tracker.render_histogram(10, 4),
+            concat!(
+                "  0 .. 25  | ########## 50\n",
+                " 25 .. 50  | #####      25 <- p50\n",
+                " 50 .. 75  | #####      25\n",
+                " 75 .. 100 | ######     26 <- p90 p99 p99.9\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_histogram_units() {
+        let mut tracker = PercentileTracker::<u64>::builder()
+            .unit(Unit::Microseconds)
+            .build()
+            .unwrap();
+        for value in [1_000, 1_000, 3_000] {
+            tracker.insert(value);
+        }
+        assert_eq!(
+            tracker.render_histogram(4, 2),
+            concat!(
+                "1.00 ms .. 2.00 ms | #### 2 <- p50\n",
+                "2.00 ms .. 3.00 ms | ##   1 <- p90 p99 p99.9\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_dump_structure() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            tracker.dump_structure(),
+            "p50 of 0 values in 0 buckets (max size 2), target rank 0, cursor at bucket 0 offset 0\n"
+        );
+        for value in ["b", "a", "c"] {
+            tracker.insert(value);
+        }
+        let dump = tracker.dump_structure();
+        assert_eq!(
+            dump,
+            concat!(
+                "p50 of 3 values in 1 buckets (max size 2), target rank 1, cursor at bucket 0 offset 0, needs rebalancing\n",
+                "  bucket 0: 3 values in [\"a\", \"c\"], unsorted <- cursor\n",
+            )
+        );
+
+        tracker.get_percentile();
+        assert_eq!(
+            tracker.dump_dot(),
+            concat!(
+                "digraph tracker {\n",
+                "  rankdir=LR;\n",
+                "  node [shape=record];\n",
+                "  b0 [label=\"#0|1 values|[\\\"a\\\", \\\"a\\\"]|unsorted\"];\n",
+                "  b1 [label=\"#1|2 values|[\\\"b\\\", \\\"c\\\"]|sorted\"];\n",
+                "  b0 -> b1;\n",
+                "  cursor [shape=plaintext, label=\"p50 rank 1\\noffset 1\"];\n",
+                "  cursor -> b1;\n",
+                "}\n",
+            )
+        );
+    }
+}
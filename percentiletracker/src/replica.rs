@@ -0,0 +1,224 @@
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{FrozenSnapshot, PercentileTracker};
+
+/// When a [`ReadReplica`] should take a fresh snapshot of its tracker.
+///
+/// The replica is refreshed as soon as either limit is exceeded. With both limits unset it is
+/// only refreshed by explicit calls to [`ReadReplica::refresh`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshPolicy {
+    /// Refresh once the published snapshot is at least this old.
+    pub max_age: Option<Duration>,
+
+    /// Refresh once at least this many values have been inserted since the last snapshot.
+    pub max_inserts: Option<u64>,
+}
+
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        RefreshPolicy {
+            max_age: Some(Duration::from_secs(1)),
+            max_inserts: None,
+        }
+    }
+}
+
+/// Bookkeeping for the last refresh, only touched by the writer.
+struct RefreshState {
+    /// When the published snapshot was taken.
+    taken_at: Instant,
+
+    /// The number of values added to the tracker when the snapshot was taken, which keeps
+    /// growing even if values are removed or downsampled away.
+    added: u64,
+}
+
+struct Shared<T> {
+    policy: RefreshPolicy,
+    current: RwLock<Arc<FrozenSnapshot<T>>>,
+    state: Mutex<RefreshState>,
+}
+
+/// A cheaply clonable, thread-safe handle to a periodically refreshed snapshot of a tracker.
+///
+/// The tracker itself is single-threaded. The thread that owns it calls
+/// [`refresh_if_stale`](Self::refresh_if_stale) after inserting, and any number of reader
+/// threads call [`load`](Self::load) to get the latest published [`FrozenSnapshot`]. Readers
+/// only hold a lock long enough to clone an `Arc`, so they never wait on the tracker, at the
+/// cost of seeing data that is at most one refresh interval old.
+///
+/// ```
+/// use percentiletracker::{PercentileTracker, ReadReplica, RefreshPolicy};
+///
+/// let mut tracker = PercentileTracker::<u64>::new(99);
+/// let policy = RefreshPolicy {
+///     max_age: None,
+///     max_inserts: Some(100),
+/// };
+/// let replica = ReadReplica::new(&tracker, policy);
+///
+/// let reader = replica.clone();
+/// for value in 0..100 {
+///     tracker.insert(value);
+///     replica.refresh_if_stale(&tracker);
+/// }
+/// assert_eq!(reader.load().get_percentile(), Some(&99));
+/// ```
+pub struct ReadReplica<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for ReadReplica<T> {
+    fn clone(&self) -> Self {
+        ReadReplica {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> ReadReplica<T>
+where
+    T: Clone + Ord,
+{
+    /// Creates a replica holding a snapshot of the tracker's current state.
+    pub fn new(tracker: &PercentileTracker<T>, policy: RefreshPolicy) -> Self {
+        ReadReplica {
+            shared: Arc::new(Shared {
+                policy,
+                current: RwLock::new(Arc::new(tracker.snapshot())),
+                state: Mutex::new(RefreshState {
+                    taken_at: Instant::now(),
+                    added: tracker.added,
+                }),
+            }),
+        }
+    }
+
+    /// Returns the most recently published snapshot.
+    pub fn load(&self) -> Arc<FrozenSnapshot<T>> {
+        let current = self
+            .shared
+            .current
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&current)
+    }
+
+    /// Returns the refresh policy the replica was created with.
+    pub fn policy(&self) -> RefreshPolicy {
+        self.shared.policy
+    }
+
+    /// Returns how long ago the published snapshot was taken.
+    pub fn age(&self) -> Duration {
+        self.state().taken_at.elapsed()
+    }
+
+    /// Publishes a fresh snapshot of the tracker unconditionally.
+    pub fn refresh(&self, tracker: &PercentileTracker<T>) {
+        let mut state = self.state();
+        self.publish(tracker, &mut state);
+    }
+
+    /// Publishes a fresh snapshot if the refresh policy says the current one is stale.
+    ///
+    /// This is cheap when nothing needs to happen, so it can be called after every insert.
+    /// Returns true if a snapshot was published.
+    pub fn refresh_if_stale(&self, tracker: &PercentileTracker<T>) -> bool {
+        let mut state = self.state();
+        let policy = self.shared.policy;
+        let inserts = tracker.added - state.added;
+        let stale = policy.max_inserts.is_some_and(|max| inserts >= max)
+            || policy
+                .max_age
+                .is_some_and(|max| inserts > 0 && state.taken_at.elapsed() >= max);
+        if stale {
+            self.publish(tracker, &mut state);
+        }
+        stale
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, RefreshState> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Snapshots the tracker outside of the reader lock, then swaps it in.
+    fn publish(&self, tracker: &PercentileTracker<T>, state: &mut RefreshState) {
+        let snapshot = Arc::new(tracker.snapshot());
+        *self
+            .shared
+            .current
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = snapshot;
+        state.taken_at = Instant::now();
+        state.added = tracker.added;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_on_insert_delta() {
+        let mut tracker = PercentileTracker::new(50);
+        let replica = ReadReplica::new(
+            &tracker,
+            RefreshPolicy {
+                max_age: None,
+                max_inserts: Some(10),
+            },
+        );
+        assert!(replica.load().is_empty());
+
+        for value in 0..9i64 {
+            tracker.insert(value);
+            assert!(!replica.refresh_if_stale(&tracker));
+        }
+        assert!(replica.load().is_empty());
+
+        tracker.insert(9);
+        assert!(replica.refresh_if_stale(&tracker));
+        assert_eq!(replica.load().len(), 10);
+        assert_eq!(replica.load().get_percentile(), Some(&5));
+    }
+
+    #[test]
+    fn test_refresh_on_age() {
+        let mut tracker = PercentileTracker::new(50);
+        let replica = ReadReplica::new(
+            &tracker,
+            RefreshPolicy {
+                max_age: Some(Duration::ZERO),
+                max_inserts: None,
+            },
+        );
+        // Nothing was inserted, so there's nothing to refresh
+        assert!(!replica.refresh_if_stale(&tracker));
+        tracker.insert(1i64);
+        assert!(replica.refresh_if_stale(&tracker));
+        assert_eq!(replica.load().values(), &[1]);
+    }
+
+    #[test]
+    fn test_readers_on_other_threads() {
+        let mut tracker = PercentileTracker::new(90);
+        let replica = ReadReplica::new(&tracker, RefreshPolicy::default());
+        for value in 0..100u64 {
+            tracker.insert(value);
+        }
+        replica.refresh(&tracker);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let reader = replica.clone();
+                std::thread::spawn(move || *reader.load().get_percentile().unwrap())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 90);
+        }
+    }
+}
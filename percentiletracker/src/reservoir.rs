@@ -0,0 +1,129 @@
+//! Bounded-memory sampling mode.
+//!
+//! A tracker configured with [`PercentileTrackerBuilder::reservoir`](crate::PercentileTrackerBuilder::reservoir)
+//! keeps a uniform random sample of at most `capacity` values using Vitter's Algorithm R, and
+//! answers every query over that sample. Memory stays constant no matter how long the stream
+//! runs, at the cost of exactness: with a reservoir of `k` values, the rank of a reported
+//! percentile has a standard error of roughly `sqrt(p * (1 - p) / k)` as a fraction of the
+//! stream, e.g. about ±0.1% of ranks for p90 with 100k samples. Extreme tails are the least
+//! accurate, since few samples land there.
+
+use crate::rng::SplitMix64;
+use crate::PercentileTracker;
+
+/// The seed used for reservoir sampling unless one is configured.
+pub(crate) const DEFAULT_RESERVOIR_SEED: u64 = 0x5EED;
+
+/// State for a tracker running in reservoir-sampling mode.
+#[derive(Debug, Clone)]
+pub(crate) struct Reservoir {
+    /// Maximum number of values kept.
+    pub(crate) capacity: usize,
+
+    /// Number of values offered to the tracker, including those that were not kept.
+    pub(crate) seen: u64,
+
+    /// Generator used to choose which values are kept.
+    pub(crate) rng: SplitMix64,
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the reservoir capacity if the tracker is sampling, or `None` if it keeps every value.
+    pub fn reservoir_capacity(&self) -> Option<usize> {
+        self.reservoir.as_ref().map(|reservoir| reservoir.capacity)
+    }
+
+    /// Returns the number of values ever inserted, including any a reservoir discarded.
+    pub fn seen(&self) -> u64 {
+        match &self.reservoir {
+            Some(reservoir) => reservoir.seen,
+            None => self.count() as u64,
+        }
+    }
+
+    /// Decides whether the next inserted value is kept, evicting a random stored value to
+    /// make room for it if the reservoir is full.
+    ///
+    /// Returns false if the value should be discarded.
+    pub(crate) fn admit_to_reservoir(&mut self) -> bool {
+        let count = self.count();
+        let Some(reservoir) = &mut self.reservoir else {
+            return true;
+        };
+        reservoir.seen += 1;
+        if count < reservoir.capacity {
+            return true;
+        }
+        let slot = reservoir.rng.below(reservoir.seen);
+        if slot >= reservoir.capacity as u64 {
+            return false;
+        }
+        // The stored values are in no particular order, so any fixed slot is a uniformly random victim
+        self.remove_at(slot as usize);
+        true
+    }
+
+    /// Removes the value stored at a position in bucket order, keeping the cursor consistent.
+    ///
+    /// The position is not a rank, since buckets other than the critical one are unsorted.
+    fn remove_at(&mut self, position: usize) {
+        let buckets = self.buckets.get_mut();
+        let mut offset = 0;
+        let mut bucket_idx = 0;
+        while position >= offset + buckets[bucket_idx].len() {
+            offset += buckets[bucket_idx].len();
+            bucket_idx += 1;
+        }
+        self.remove_from_bucket(bucket_idx, position - offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservoir_is_bounded() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .reservoir(1_000)
+            .build()
+            .unwrap();
+        for value in 0..100_000i64 {
+            tracker.insert(value);
+            if value % 997 == 0 {
+                assert!(tracker.verify_bucket_offset());
+            }
+        }
+        assert_eq!(tracker.count(), 1_000);
+        assert_eq!(tracker.seen(), 100_000);
+        assert_eq!(tracker.reservoir_capacity(), Some(1_000));
+        assert!(tracker.verify_bucket_offset());
+
+        // The sample is uniform, so p90 should land near 90_000
+        let p90 = tracker.get_percentile();
+        assert!((85_000..95_000).contains(&p90), "p90 was {}", p90);
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 1_000);
+        assert_eq!(snapshot.get_percentile(), Some(&p90));
+    }
+
+    #[test]
+    fn test_reservoir_exact_below_capacity() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .reservoir(100)
+            .build()
+            .unwrap();
+        for value in (0..100i64).rev() {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.get_percentile(), 50);
+        assert_eq!(tracker.seen(), 100);
+    }
+}
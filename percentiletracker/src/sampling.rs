@@ -0,0 +1,215 @@
+//! Recording only a fraction of inserts.
+//!
+//! A tracker built with [`PercentileTrackerBuilder::sampling`] keeps 1 in N inserted values
+//! and drops the rest before doing any work, so CPU and memory both shrink by a factor of N.
+//! Percentiles of the kept values estimate those of the whole stream, and counts can be
+//! scaled back up with [`PercentileTracker::sample_rate`].
+//!
+//! Deterministic sampling keeps every Nth value, which is cheapest but can alias with
+//! periodic input. Random sampling keeps each value with probability 1/N and doesn't.
+
+use std::ops::RangeBounds;
+
+use crate::rng::SplitMix64;
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// Which inserted values a sampling tracker keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    /// Keeps the first inserted value and every Nth after it.
+    Every(u64),
+
+    /// Keeps each inserted value with probability 1/`one_in`.
+    Random {
+        /// The N in 1 in N.
+        one_in: u64,
+
+        /// Seeds the generator choosing which values are kept, so runs are reproducible.
+        seed: u64,
+    },
+}
+
+impl Sampling {
+    /// Returns N, the number of inserted values each kept value stands for.
+    pub fn rate(&self) -> u64 {
+        match *self {
+            Sampling::Every(n) => n,
+            Sampling::Random { one_in, .. } => one_in,
+        }
+    }
+}
+
+/// State for a tracker that samples its inserts.
+#[derive(Debug, Clone)]
+pub(crate) struct Sampler {
+    pub(crate) sampling: Sampling,
+
+    /// Number of values offered to the tracker, including those that were dropped.
+    offered: u64,
+
+    /// Number of offered values that were dropped.
+    pub(crate) dropped: u64,
+
+    /// Generator used by random sampling.
+    rng: SplitMix64,
+}
+
+impl Sampler {
+    pub(crate) fn new(sampling: Sampling) -> Self {
+        let seed = match sampling {
+            Sampling::Every(_) => 0,
+            Sampling::Random { seed, .. } => seed,
+        };
+        Sampler {
+            sampling,
+            offered: 0,
+            dropped: 0,
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Counts an offered value, and returns true if it should be kept.
+    fn admit(&mut self) -> bool {
+        let offered = self.offered;
+        self.offered += 1;
+        let keep = match self.sampling {
+            Sampling::Every(n) => offered % n == 0,
+            Sampling::Random { one_in, .. } => self.rng.below(one_in) == 0,
+        };
+        if !keep {
+            self.dropped += 1;
+        }
+        keep
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns how the tracker samples its inserts, or `None` if it records every insert.
+    pub fn sampling(&self) -> Option<Sampling> {
+        self.sampler.as_ref().map(|sampler| sampler.sampling)
+    }
+
+    /// Returns the number of inserted values each recorded value stands for, which is 1
+    /// unless the tracker samples its inserts or has downsampled to fit a memory budget.
+    pub fn sample_rate(&self) -> u64 {
+        self.sampling().map_or(1, |sampling| sampling.rate()) * self.downsampling_factor()
+    }
+
+    /// Returns the number of values passed to [`insert`](Self::insert), including any
+    /// dropped by sampling, or `None` if the tracker doesn't sample.
+    pub fn sampled_from(&self) -> Option<u64> {
+        self.sampler.as_ref().map(|sampler| sampler.offered)
+    }
+
+    /// Decides whether the next inserted value is recorded at all.
+    pub(crate) fn admit_sample(&mut self) -> bool {
+        match &mut self.sampler {
+            Some(sampler) => sampler.admit(),
+            None => true,
+        }
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the estimated number of inserted values within a range, scaling the recorded
+    /// count by the sample rate.
+    ///
+    /// ```
+    /// use percentiletracker::{PercentileTracker, Sampling};
+    ///
+    /// let mut latencies = PercentileTracker::<u64>::builder()
+    ///     .sampling(Sampling::Every(10))
+    ///     .build()
+    ///     .unwrap();
+    /// for ms in 0..1_000 {
+    ///     latencies.insert(ms);
+    /// }
+    /// assert_eq!(latencies.count_in_range(..), 100);
+    /// assert_eq!(latencies.estimated_count_in_range(..500), 500);
+    /// ```
+    pub fn estimated_count_in_range(&self, range: impl RangeBounds<T>) -> u64 {
+        self.count_in_range(range) as u64 * self.sample_rate()
+    }
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Records only 1 in N inserted values, chosen according to `sampling`.
+    ///
+    /// Sampling happens before any other work, so the accepted range, reservoir, journal
+    /// and observers only see the values that were kept.
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuildError;
+
+    #[test]
+    fn test_every_nth() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .sampling(Sampling::Every(4))
+            .build()
+            .unwrap();
+        for value in 0..10i64 {
+            tracker.insert(value);
+        }
+        assert!(tracker.iter_sorted().eq(&[0, 4, 8]));
+        assert_eq!(tracker.sample_rate(), 4);
+        assert_eq!(tracker.sampled_from(), Some(10));
+        assert_eq!(tracker.estimated_count_in_range(1..), 8);
+    }
+
+    #[test]
+    fn test_random_sampling() {
+        let sampling = Sampling::Random {
+            one_in: 100,
+            seed: 17,
+        };
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .sampling(sampling)
+            .build()
+            .unwrap();
+        // Periodic input that deterministic sampling would alias with
+        for value in 0..200_000u64 {
+            tracker.insert(value % 100);
+        }
+        assert_eq!(tracker.sampling(), Some(sampling));
+        let estimate = tracker.estimated_count_in_range(..);
+        assert!((180_000..220_000).contains(&estimate), "{}", estimate);
+        let p90 = tracker.get_percentile();
+        assert!((85..95).contains(&p90), "p90 was {}", p90);
+
+        let mut unsampled = PercentileTracker::new(50);
+        unsampled.insert(1u8);
+        assert_eq!(unsampled.sample_rate(), 1);
+        assert_eq!(unsampled.sampled_from(), None);
+    }
+
+    #[test]
+    fn test_zero_rate() {
+        for sampling in [Sampling::Every(0), Sampling::Random { one_in: 0, seed: 1 }] {
+            assert_eq!(
+                PercentileTracker::<i64>::builder()
+                    .sampling(sampling)
+                    .build()
+                    .err(),
+                Some(BuildError::InvalidSamplingRate(0))
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! A tracker shared between threads.
+//!
+//! Queries on a [`PercentileTracker`] rebalance it through interior mutability, so even
+//! reads need exclusive access and a read-write lock wouldn't let them run in parallel.
+//! [`SharedPercentileTracker`] instead keeps producers off the tracker's lock: inserts go
+//! into one of several small buffers, and a full buffer is moved into the tracker in one go.
+//! A query drains every buffer before answering, so it always sees every insert that
+//! finished before it started.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread;
+
+use crate::PercentileTracker;
+
+/// The number of values a buffer holds before they are moved into the tracker.
+const BUFFER_LEN: usize = 64;
+
+struct Shared<T>
+where
+    T: Ord,
+{
+    tracker: Mutex<PercentileTracker<T>>,
+
+    /// Inserts not yet moved into the tracker.
+    buffers: Vec<Mutex<Vec<T>>>,
+
+    /// The buffer the next insert tries first, so concurrent inserts spread out.
+    next: AtomicUsize,
+}
+
+/// A cloneable handle to a tracker that many threads can insert into and query.
+///
+/// Every clone refers to the same tracker. Inserts only lock one of several buffers,
+/// trying each in turn before waiting on a busy one, so producers rarely wait for each other
+/// or for a query.
+///
+/// ```
+/// use std::thread;
+/// use percentiletracker::{PercentileTracker, SharedPercentileTracker};
+///
+/// let latencies = SharedPercentileTracker::new(PercentileTracker::<u64>::new(99));
+/// thread::scope(|s| {
+///     for worker in 0..4 {
+///         let latencies = latencies.clone();
+///         s.spawn(move || {
+///             for i in 0..250 {
+///                 latencies.insert(worker * 250 + i);
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(latencies.len(), 1_000);
+/// assert_eq!(latencies.get_percentile(), 990);
+/// ```
+pub struct SharedPercentileTracker<T>
+where
+    T: Ord,
+{
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> SharedPercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Shares a tracker, with one insert buffer per available CPU.
+    pub fn new(tracker: PercentileTracker<T>) -> Self {
+        let shards = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(tracker, shards)
+    }
+
+    /// Shares a tracker with a chosen number of insert buffers.
+    ///
+    /// More buffers let more threads insert without waiting, at the cost of up to
+    /// 64 values per buffer that each query has to move into the tracker.
+    ///
+    /// # Panics
+    /// Panics if `shards` is zero.
+    pub fn with_shards(tracker: PercentileTracker<T>, shards: usize) -> Self {
+        assert!(shards > 0, "A shared tracker needs at least one buffer");
+        SharedPercentileTracker {
+            shared: Arc::new(Shared {
+                tracker: Mutex::new(tracker),
+                buffers: (0..shards)
+                    .map(|_| Mutex::new(Vec::with_capacity(BUFFER_LEN)))
+                    .collect(),
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Inserts a value.
+    pub fn insert(&self, value: T) {
+        let buffers = &self.shared.buffers;
+        let first = self.shared.next.fetch_add(1, Ordering::Relaxed) % buffers.len();
+        let mut buffer = (0..buffers.len())
+            .find_map(|i| match buffers[(first + i) % buffers.len()].try_lock() {
+                Ok(buffer) => Some(buffer),
+                Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+                Err(TryLockError::WouldBlock) => None,
+            })
+            .unwrap_or_else(|| lock(&buffers[first]));
+        buffer.push(value);
+        if buffer.len() >= BUFFER_LEN {
+            let values = std::mem::replace(&mut *buffer, Vec::with_capacity(BUFFER_LEN));
+            drop(buffer);
+            let mut tracker = lock(&self.shared.tracker);
+            for value in values {
+                tracker.insert(value);
+            }
+        }
+    }
+
+    /// Returns the number of values inserted through every handle.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Retrieves the current target percentile value.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile(&self) -> T
+    where
+        T: Clone,
+    {
+        self.lock().get_percentile()
+    }
+
+    /// Moves every buffered insert into the tracker, and locks it for any other query.
+    ///
+    /// Inserts that fill a buffer wait while the guard is held, so keep it short.
+    pub fn lock(&self) -> MutexGuard<'_, PercentileTracker<T>> {
+        let mut tracker = lock(&self.shared.tracker);
+        for buffer in &self.shared.buffers {
+            for value in lock(buffer).drain(..) {
+                tracker.insert(value);
+            }
+        }
+        tracker
+    }
+}
+
+impl<T> Clone for SharedPercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns another handle to the same tracker.
+    fn clone(&self) -> Self {
+        SharedPercentileTracker {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Locks a mutex, carrying on with the data if another thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let shared = SharedPercentileTracker::with_shards(PercentileTracker::new(90), 3);
+        thread::scope(|s| {
+            for worker in 0..8u64 {
+                let shared = shared.clone();
+                s.spawn(move || {
+                    for i in 0..1_000 {
+                        shared.insert(i * 8 + worker);
+                        if i % 100 == 0 {
+                            // Queries run alongside the inserts
+                            shared.get_percentile();
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.len(), 8_000);
+        assert_eq!(shared.get_percentile(), 7_200);
+        assert!(shared.lock().verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_buffered_inserts_are_visible() {
+        let shared = SharedPercentileTracker::with_shards(PercentileTracker::new(50), 4);
+        assert!(shared.is_empty());
+        shared.insert(3u8);
+        shared.clone().insert(1);
+        assert_eq!(shared.len(), 2);
+        assert_eq!(shared.get_percentile(), 3);
+    }
+}
@@ -0,0 +1,257 @@
+use std::mem::size_of;
+
+use crate::{rank_for_percentile, PercentileTracker, Unit};
+
+/// The percentiles compared by [`PercentileTracker::delta_since`], in addition to the tracked one.
+pub const DELTA_PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 99.9];
+
+/// An immutable, fully sorted copy of a tracker's values at a point in time.
+///
+/// A snapshot can answer any percentile query without touching the live tracker, and can be
+/// compared against the tracker later with [`PercentileTracker::delta_since`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenSnapshot<T> {
+    /// All values held by the tracker, in ascending order.
+    values: Vec<T>,
+
+    /// The percentile tracked by the tracker the snapshot was taken from.
+    percentile: usize,
+
+    /// The unit of the captured values.
+    unit: Unit,
+}
+
+impl<T> FrozenSnapshot<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the number of values captured by the snapshot.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if the snapshot holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the percentile tracked by the tracker the snapshot was taken from.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the unit of the captured values.
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// Returns the value at the tracked percentile, or `None` if the snapshot is empty.
+    pub fn get_percentile(&self) -> Option<&T> {
+        self.value_at_percentile(self.percentile as f64)
+    }
+
+    /// Returns the value at an arbitrary percentile (0-100), or `None` if the snapshot is empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<&T> {
+        if self.values.is_empty() {
+            return None;
+        }
+        self.values
+            .get(rank_for_percentile(percentile, self.values.len()))
+    }
+
+    /// Returns the captured values in ascending order.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Takes the captured values, in ascending order.
+    pub(crate) fn into_values(self) -> Vec<T> {
+        self.values
+    }
+}
+
+/// How a single percentile moved between a snapshot and the live tracker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantileMovement<T> {
+    /// The percentile (0-100) that moved.
+    pub percentile: f64,
+
+    /// The value in the earlier snapshot, or `None` if it was empty.
+    pub previous: Option<T>,
+
+    /// The value in the tracker now, or `None` if it is empty.
+    pub current: Option<T>,
+}
+
+/// The changes between an earlier [`FrozenSnapshot`] and the current state of a tracker.
+///
+/// Only percentiles whose value actually changed are included, so an unchanged
+/// distribution produces an empty delta that is cheap to ship.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryDelta<T> {
+    /// The number of values in the earlier snapshot.
+    pub previous_count: usize,
+
+    /// The number of values in the tracker now.
+    pub count: usize,
+
+    /// Every compared percentile whose value changed.
+    pub movements: Vec<QuantileMovement<T>>,
+}
+
+impl<T> FrozenSnapshot<T> {
+    /// Returns the bytes allocated for the captured values.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.values.capacity() * size_of::<T>()
+    }
+}
+
+impl<T> SummaryDelta<T> {
+    /// Returns true if neither the count nor any compared percentile changed.
+    pub fn is_empty(&self) -> bool {
+        self.previous_count == self.count && self.movements.is_empty()
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the tracked percentile and each of [`DELTA_PERCENTILES`], in ascending order.
+    pub(crate) fn compared_percentiles(&self) -> Vec<f64> {
+        let mut percentiles = vec![self.percentile as f64];
+        percentiles.extend(
+            DELTA_PERCENTILES
+                .iter()
+                .filter(|&&p| p != self.percentile as f64),
+        );
+        percentiles.sort_by(f64::total_cmp);
+        percentiles
+    }
+
+    /// Captures an immutable, sorted copy of every value currently in the tracker.
+    ///
+    /// Each bucket is sorted in place and the buckets are concatenated, which is cheaper
+    /// than sorting all values at once since the buckets already partition the range.
+    pub fn snapshot(&self) -> FrozenSnapshot<T> {
+        let mut buckets = self.buckets.borrow_mut();
+        let mut values = Vec::with_capacity(self.count());
+        for bucket in buckets.iter_mut() {
+            self.sort_bucket(bucket);
+            values.extend_from_slice(&bucket.values);
+        }
+        FrozenSnapshot {
+            values,
+            percentile: self.percentile,
+            unit: self.unit,
+        }
+    }
+
+    /// Iterates over every value in ascending order without consuming or copying the tracker.
+    ///
+    /// Every bucket is sorted in place first. The buckets stay sorted afterwards, so later
+    /// queries are unaffected and tracking continues as usual once the iterator is dropped.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(90);
+    /// for value in [3, 1, 2] {
+    ///     tracker.insert(value);
+    /// }
+    /// assert_eq!(tracker.iter_sorted().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    /// tracker.insert(0);
+    /// ```
+    pub fn iter_sorted(&mut self) -> impl Iterator<Item = &T> + '_ {
+        for bucket in self.buckets.borrow_mut().iter_mut() {
+            self.sort_bucket(bucket);
+        }
+        self.buckets
+            .get_mut()
+            .iter()
+            .flat_map(|bucket| bucket.values.iter())
+    }
+
+    /// Computes what changed relative to an earlier snapshot.
+    ///
+    /// The tracked percentile and each of [`DELTA_PERCENTILES`] are compared, and only
+    /// those that moved are reported.
+    pub fn delta_since(&self, prior: &FrozenSnapshot<T>) -> SummaryDelta<T> {
+        let movements = self
+            .compared_percentiles()
+            .into_iter()
+            .filter_map(|percentile| {
+                let previous = prior.value_at_percentile(percentile).cloned();
+                let current = if self.count() == 0 {
+                    None
+                } else {
+                    Some(self.select_rank(rank_for_percentile(percentile, self.count())))
+                };
+                (previous != current).then_some(QuantileMovement {
+                    percentile,
+                    previous,
+                    current,
+                })
+            })
+            .collect();
+
+        SummaryDelta {
+            previous_count: prior.len(),
+            count: self.count(),
+            movements,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_is_sorted() {
+        let mut tracker = PercentileTracker::new(90);
+        for value in (0..1000i64).rev() {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.values(), (0..1000).collect::<Vec<_>>().as_slice());
+        assert_eq!(snapshot.get_percentile(), Some(&tracker.get_percentile()));
+        assert_eq!(snapshot.value_at_percentile(100.0), Some(&999));
+        assert!(tracker.iter_sorted().eq(snapshot.values()));
+        tracker.insert(-1);
+        assert_eq!(tracker.iter_sorted().next(), Some(&-1));
+        assert!(tracker.verify_bucket_offset());
+    }
+
+    #[test]
+    fn test_delta_since() {
+        let mut tracker = PercentileTracker::new(90);
+        for value in 0..100i64 {
+            tracker.insert(value);
+        }
+        let snapshot = tracker.snapshot();
+        assert!(tracker.delta_since(&snapshot).is_empty());
+
+        for value in 100..200i64 {
+            tracker.insert(value);
+        }
+        let delta = tracker.delta_since(&snapshot);
+        assert_eq!(delta.previous_count, 100);
+        assert_eq!(delta.count, 200);
+        let p50 = delta
+            .movements
+            .iter()
+            .find(|movement| movement.percentile == 50.0)
+            .unwrap();
+        assert_eq!(p50.previous, Some(50));
+        assert_eq!(p50.current, Some(100));
+
+        let empty = PercentileTracker::<i64>::new(50).snapshot();
+        let delta = tracker.delta_since(&empty);
+        assert!(delta
+            .movements
+            .iter()
+            .all(|movement| movement.previous.is_none()));
+    }
+}
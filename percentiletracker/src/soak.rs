@@ -0,0 +1,354 @@
+//! Long-running soak harness for the percentile tracker.
+//!
+//! The harness drives a tracker with a mixed, seeded workload of inserts and queries and
+//! periodically validates the tracker against an exact oracle. It is intended to be run for
+//! hours (see `examples/soak.rs`) to build confidence that the structure stays correct and
+//! its memory usage stays proportional to the number of stored values over billions of
+//! operations.
+//!
+//! Besides inserts and queries, the workload undoes recent inserts and periodically splits
+//! the tracker, keeping the larger half, so the removal paths are soaked too. Those only
+//! slow the growth, so the harness also "evicts" by discarding the tracker and starting a
+//! fresh one once it holds `max_len` values, which exercises construction, growth and
+//! teardown repeatedly.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::export::write_json_string;
+use crate::rng::SplitMix64;
+use crate::{target_rank, PercentileTracker};
+
+/// The number of recent inserts each tracker can undo.
+const UNDO_DEPTH: usize = 64;
+
+/// Configuration for a soak run.
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    /// Seed for the workload generator, so failing runs can be reproduced.
+    pub seed: u64,
+
+    /// The percentile tracked by each tracker (1-99).
+    pub percentile: usize,
+
+    /// Stop after this much wall-clock time.
+    pub duration: Duration,
+
+    /// Stop after this many operations, if set. Whichever limit is hit first ends the run.
+    pub max_operations: Option<u64>,
+
+    /// Relative weight of insert operations in the workload.
+    pub insert_weight: u32,
+
+    /// Relative weight of query operations in the workload.
+    pub query_weight: u32,
+
+    /// Relative weight of operations that undo the most recent insert.
+    pub undo_weight: u32,
+
+    /// Split the tracker at a random stored value every this many operations, keeping the
+    /// larger half. Zero disables splitting.
+    pub split_interval: u64,
+
+    /// Number of values a tracker may hold before it is discarded and replaced.
+    pub max_len: usize,
+
+    /// Run a full invariant validation every this many operations.
+    pub validation_interval: u64,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        SoakConfig {
+            seed: 42,
+            percentile: 90,
+            duration: Duration::from_secs(60),
+            max_operations: None,
+            insert_weight: 4,
+            query_weight: 1,
+            undo_weight: 1,
+            split_interval: 100_000,
+            max_len: 1_000_000,
+            validation_interval: 1_000_000,
+        }
+    }
+}
+
+/// A single periodic observation taken during a soak run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoakSample {
+    /// Total operations performed when the sample was taken.
+    pub operations: u64,
+
+    /// Time since the start of the run.
+    pub elapsed: Duration,
+
+    /// Number of values held by the current tracker.
+    pub len: usize,
+
+    /// Number of buckets in the current tracker.
+    pub bucket_count: usize,
+
+    /// Approximate heap bytes used by the current tracker.
+    pub approx_bytes: usize,
+
+    /// Whether every invariant held at this sample.
+    pub valid: bool,
+}
+
+/// The machine-readable result of a soak run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SoakReport {
+    /// The seed the run was started with.
+    pub seed: u64,
+
+    /// Total operations performed.
+    pub operations: u64,
+
+    /// Number of insert operations performed.
+    pub inserts: u64,
+
+    /// Number of query operations performed.
+    pub queries: u64,
+
+    /// Number of inserts taken back by undo operations.
+    pub undos: u64,
+
+    /// Number of times the tracker was split.
+    pub splits: u64,
+
+    /// Number of times a full tracker was discarded and replaced.
+    pub evictions: u64,
+
+    /// Wall-clock duration of the run.
+    pub elapsed: Duration,
+
+    /// Periodic samples, one per validation.
+    pub samples: Vec<SoakSample>,
+
+    /// Descriptions of every invariant violation observed.
+    pub violations: Vec<String>,
+}
+
+impl SoakReport {
+    /// Returns true if no invariant violations were observed.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Renders the report as a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"seed\":{},\"operations\":{},\"inserts\":{},\"queries\":{},\"undos\":{},\"splits\":{},\"evictions\":{},\"elapsed_ms\":{},\"ok\":{},\"samples\":[",
+            self.seed,
+            self.operations,
+            self.inserts,
+            self.queries,
+            self.undos,
+            self.splits,
+            self.evictions,
+            self.elapsed.as_millis(),
+            self.is_ok()
+        );
+        for (i, sample) in self.samples.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"operations\":{},\"elapsed_ms\":{},\"len\":{},\"bucket_count\":{},\"approx_bytes\":{},\"valid\":{}}}",
+                sample.operations,
+                sample.elapsed.as_millis(),
+                sample.len,
+                sample.bucket_count,
+                sample.approx_bytes,
+                sample.valid
+            );
+        }
+        out.push_str("],\"violations\":[");
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, violation);
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Runs a soak workload until one of the configured limits is reached.
+///
+/// # Panics
+/// Panics if the configured percentile is invalid, or if every operation weight is zero.
+pub fn run(config: &SoakConfig) -> SoakReport {
+    let total_weight =
+        config.insert_weight as u64 + config.query_weight as u64 + config.undo_weight as u64;
+    assert!(
+        total_weight > 0,
+        "At least one operation weight must be non-zero"
+    );
+
+    let mut rng = SplitMix64(config.seed);
+    let mut tracker = new_tracker(config);
+    let mut report = SoakReport {
+        seed: config.seed,
+        ..SoakReport::default()
+    };
+    let start = Instant::now();
+
+    loop {
+        if config
+            .max_operations
+            .is_some_and(|max| report.operations >= max)
+        {
+            break;
+        }
+        // Checking the clock on every operation would dominate the workload
+        if report.operations % 1024 == 0 && start.elapsed() >= config.duration {
+            break;
+        }
+
+        let roll = rng.next() % total_weight;
+        if roll < config.insert_weight as u64 {
+            if tracker.count() >= config.max_len {
+                tracker = new_tracker(config);
+                report.evictions += 1;
+            }
+            tracker.insert(rng.next() as i64);
+            report.inserts += 1;
+        } else if roll < config.insert_weight as u64 + config.query_weight as u64 {
+            if tracker.count() > 0 {
+                std::hint::black_box(tracker.get_percentile());
+                report.queries += 1;
+            }
+        } else {
+            report.undos += tracker.undo(1) as u64;
+        }
+        report.operations += 1;
+
+        if config.split_interval > 0
+            && report.operations % config.split_interval == 0
+            && tracker.count() > 0
+        {
+            let rank = rng.below(tracker.count() as u64) as usize;
+            let threshold = tracker.value_at_rank(rank).expect("The rank is in bounds");
+            let mut upper = tracker.split_at(&threshold);
+            if upper.count() > tracker.count() {
+                std::mem::swap(&mut tracker, &mut upper);
+            }
+            // The discarded half is checked once, since it won't be validated again
+            if let Err(violation) = upper.verify() {
+                report.violations.push(format!(
+                    "after {} operations: split-off half: {}",
+                    report.operations, violation
+                ));
+            }
+            report.splits += 1;
+        }
+
+        if config.validation_interval > 0 && report.operations % config.validation_interval == 0 {
+            let sample = validate(&tracker, report.operations, start.elapsed(), &mut report);
+            report.samples.push(sample);
+        }
+    }
+
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Builds a tracker for the workload, with a journal so inserts can be undone.
+fn new_tracker(config: &SoakConfig) -> PercentileTracker<i64> {
+    PercentileTracker::builder()
+        .percentile(config.percentile)
+        .undo_journal(UNDO_DEPTH)
+        .build()
+        .expect("Invalid soak configuration")
+}
+
+/// Checks the tracker against an exact oracle and records any violations in the report.
+fn validate(
+    tracker: &PercentileTracker<i64>,
+    operations: u64,
+    elapsed: Duration,
+    report: &mut SoakReport,
+) -> SoakSample {
+    let mut valid = true;
+    let mut fail = |message: String| {
+        valid = false;
+        report
+            .violations
+            .push(format!("after {} operations: {}", operations, message));
+    };
+
+    if !tracker.verify_bucket_offset() {
+        fail("percentile bucket offset is incorrect".to_string());
+    }
+    if let Err(violation) = tracker.verify() {
+        fail(violation.to_string());
+    }
+
+    let usage = tracker.memory_usage();
+    let mut oracle: Vec<i64> = tracker
+        .buckets
+        .borrow()
+        .iter()
+        .flat_map(|bucket| bucket.values.iter().copied())
+        .collect();
+
+    if usage.len != tracker.count() {
+        fail(format!(
+            "buckets hold {} values but total count is {}",
+            usage.len,
+            tracker.count()
+        ));
+    }
+
+    if !oracle.is_empty() {
+        oracle.sort_unstable();
+        let expected = oracle[target_rank(tracker.percentile, oracle.len())];
+        let actual = tracker.get_percentile();
+        if actual != expected {
+            fail(format!(
+                "percentile was {} but the oracle expected {}",
+                actual, expected
+            ));
+        }
+    }
+
+    SoakSample {
+        operations,
+        elapsed,
+        len: usage.len,
+        bucket_count: usage.bucket_count,
+        approx_bytes: usage.bytes,
+        valid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_soak_is_clean() {
+        let config = SoakConfig {
+            duration: Duration::from_secs(60),
+            max_operations: Some(20_000),
+            max_len: 3_000,
+            split_interval: 2_000,
+            validation_interval: 1_000,
+            ..SoakConfig::default()
+        };
+        let report = run(&config);
+        assert!(report.is_ok(), "{:?}", report.violations);
+        assert_eq!(report.operations, 20_000);
+        assert_eq!(report.samples.len(), 20);
+        assert!(report.evictions > 0);
+        assert!(report.undos > 0);
+        assert_eq!(report.splits, 10);
+        assert!(report.to_json().contains("\"ok\":true"));
+    }
+}
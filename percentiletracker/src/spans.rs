@@ -0,0 +1,166 @@
+//! A `tracing` layer that records how long spans take.
+//!
+//! [`SpanDurationLayer`] times every span accepted by its filter, from creation to close, and
+//! records the duration in nanoseconds into a tracker per span name. The [`SpanDurations`]
+//! handle returned alongside it can be queried from any thread for per-name percentiles.
+//!
+//! ```
+//! use percentiletracker::spans::SpanDurationLayer;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let (layer, durations) = SpanDurationLayer::new(|metadata| metadata.name() == "handle_request");
+//! let subscriber = tracing_subscriber::registry().with(layer);
+//! tracing::subscriber::with_default(subscriber, || {
+//!     let _span = tracing::info_span!("handle_request").entered();
+//! });
+//! assert_eq!(durations.percentiles("handle_request").unwrap().count, 1);
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tracing::span::{Attributes, Id};
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::{PercentileTracker, QuantileEstimator, Unit};
+
+/// Trackers of span durations in nanoseconds, by span name.
+type Trackers = Arc<Mutex<BTreeMap<&'static str, PercentileTracker<u64>>>>;
+
+/// When a timed span was created, stored in the span's extensions.
+struct SpanStart(Instant);
+
+/// A [`Layer`] that records the duration of matching spans into per-name trackers.
+pub struct SpanDurationLayer {
+    filter: Box<dyn Fn(&Metadata<'_>) -> bool + Send + Sync>,
+    trackers: Trackers,
+}
+
+impl SpanDurationLayer {
+    /// Creates a layer timing every span for which `filter` returns true, and a handle for
+    /// querying the recorded durations.
+    pub fn new<F>(filter: F) -> (Self, SpanDurations)
+    where
+        F: Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+    {
+        let trackers = Trackers::default();
+        let layer = SpanDurationLayer {
+            filter: Box::new(filter),
+            trackers: Arc::clone(&trackers),
+        };
+        (layer, SpanDurations { trackers })
+    }
+}
+
+impl<S> Layer<S> for SpanDurationLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !(self.filter)(attrs.metadata()) {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span
+            .extensions()
+            .get::<SpanStart>()
+            .map(|start| start.0.elapsed())
+        else {
+            return;
+        };
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        lock(&self.trackers)
+            .entry(span.name())
+            .or_insert_with(|| {
+                PercentileTracker::builder()
+                    .percentile(99)
+                    .unit(Unit::Nanoseconds)
+                    .build()
+                    .expect("The span tracker configuration is valid")
+            })
+            .insert(nanos);
+    }
+}
+
+/// The percentiles of one span name's recorded durations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanPercentiles {
+    /// Number of spans recorded.
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// A thread-safe handle for querying the durations recorded by a [`SpanDurationLayer`].
+#[derive(Clone)]
+pub struct SpanDurations {
+    trackers: Trackers,
+}
+
+impl SpanDurations {
+    /// Returns the percentiles of a span name's durations, or `None` if none were recorded.
+    pub fn percentiles(&self, name: &str) -> Option<SpanPercentiles> {
+        let trackers = lock(&self.trackers);
+        let tracker = trackers.get(name)?;
+        let at = |percentile| {
+            Duration::from_nanos(tracker.value_at_percentile(percentile).unwrap_or_default())
+        };
+        Some(SpanPercentiles {
+            count: tracker.len(),
+            p50: at(50.0),
+            p95: at(95.0),
+            p99: at(99.0),
+        })
+    }
+
+    /// Returns the names of every span with recorded durations, in sorted order.
+    pub fn names(&self) -> Vec<&'static str> {
+        lock(&self.trackers).keys().copied().collect()
+    }
+}
+
+/// Locks the trackers, recovering from a panic in another thread since the trackers are
+/// only ever inserted into.
+fn lock(trackers: &Trackers) -> MutexGuard<'_, BTreeMap<&'static str, PercentileTracker<u64>>> {
+    trackers.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_span_durations() {
+        let (layer, durations) = SpanDurationLayer::new(|metadata| metadata.name() != "ignored");
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..20 {
+                let _span = tracing::info_span!("work", i).entered();
+                if i == 19 {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            }
+            let _ignored = tracing::info_span!("ignored").entered();
+        });
+
+        assert_eq!(durations.names(), vec!["work"]);
+        let work = durations.percentiles("work").unwrap();
+        assert_eq!(work.count, 20);
+        assert!(work.p50 <= work.p95 && work.p95 <= work.p99);
+        assert!(work.p99 >= Duration::from_millis(5));
+        assert!(durations.percentiles("ignored").is_none());
+    }
+}
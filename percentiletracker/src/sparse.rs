@@ -0,0 +1,228 @@
+//! An exact backend storing a count per distinct value.
+//!
+//! Streams of quantized values, such as latencies rounded to the millisecond, repeat a few
+//! thousand distinct values billions of times. [`SparseTracker`] keeps one count per distinct
+//! value in a `BTreeMap`, so memory follows the cardinality of the stream instead of its
+//! length, and the answers are still exact.
+
+use std::collections::BTreeMap;
+
+use crate::{rank_for_percentile, target_rank, ErrorBound, MergeError, QuantileEstimator};
+
+/// An exact percentile tracker for streams with few distinct values.
+///
+/// Inserts are O(log d) for `d` distinct values, and a rank is found by walking the counts
+/// in order, which is O(d).
+///
+/// ```
+/// use percentiletracker::SparseTracker;
+///
+/// let mut latencies = SparseTracker::new(99);
+/// for i in 0..1_000_000u64 {
+///     // Millisecond latencies, mostly fast with a slow tail
+///     latencies.insert(if i % 50 == 0 { 250 } else { 5 + i % 10 });
+/// }
+/// assert_eq!(latencies.get_percentile(), 250);
+/// assert_eq!(latencies.distinct_count(), 11);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseTracker<T> {
+    /// The number of times each distinct value was inserted.
+    counts: BTreeMap<T, u64>,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// Total number of values inserted.
+    count: u64,
+}
+
+impl<T> SparseTracker<T>
+where
+    T: Ord + Clone,
+{
+    /// Creates an empty tracker for the given percentile.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        SparseTracker {
+            counts: BTreeMap::new(),
+            percentile,
+            count: 0,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the number of values inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of distinct values, which is the tracker's memory.
+    pub fn distinct_count(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns how many times a value was inserted.
+    pub fn count_of(&self, value: &T) -> u64 {
+        self.counts.get(value).copied().unwrap_or(0)
+    }
+
+    /// Inserts a value.
+    pub fn insert(&mut self, value: T) {
+        self.insert_many(value, 1);
+    }
+
+    /// Inserts `n` copies of a value at once, e.g. from a pre-aggregated histogram.
+    pub fn insert_many(&mut self, value: T, n: u64) {
+        if n == 0 {
+            return;
+        }
+        *self.counts.entry(value).or_insert(0) += n;
+        self.count += n;
+    }
+
+    /// Removes one copy of a value, returning false if it was never inserted.
+    pub fn remove(&mut self, value: &T) -> bool {
+        let Some(count) = self.counts.get_mut(value) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.counts.remove(value);
+        }
+        self.count -= 1;
+        true
+    }
+
+    /// Adds every value recorded by another tracker into this one.
+    ///
+    /// This tracker keeps its own percentile.
+    pub fn merge(&mut self, other: &SparseTracker<T>) {
+        for (value, &n) in &other.counts {
+            self.insert_many(value.clone(), n);
+        }
+    }
+
+    /// Returns the value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the tracker is empty.
+    pub fn get_percentile(&self) -> T {
+        assert!(
+            self.count > 0,
+            "Cannot query the percentile of an empty tracker"
+        );
+        let rank = target_rank(self.percentile, self.count as usize) as u64;
+        self.value_at_rank(rank)
+            .expect("The target rank is below the count")
+    }
+
+    /// Returns the value at a percentile (0-100), or `None` if empty.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<T> {
+        if self.count == 0 {
+            return None;
+        }
+        self.value_at_rank(rank_for_percentile(percentile, self.count as usize) as u64)
+    }
+
+    /// Returns the value at a zero-based rank, or `None` if the rank is out of bounds.
+    pub fn value_at_rank(&self, rank: u64) -> Option<T> {
+        let mut remaining = rank;
+        for (value, &n) in &self.counts {
+            if remaining < n {
+                return Some(value.clone());
+            }
+            remaining -= n;
+        }
+        None
+    }
+}
+
+impl<T> QuantileEstimator<T> for SparseTracker<T>
+where
+    T: Ord + Clone,
+{
+    fn insert(&mut self, value: T) {
+        SparseTracker::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    fn value_at_rank(&self, rank: usize) -> Option<T> {
+        SparseTracker::value_at_rank(self, rank as u64)
+    }
+
+    /// Sparse trackers have no parameters, so this never fails.
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        SparseTracker::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        ErrorBound::Exact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::ExactTracker;
+
+    #[test]
+    fn test_matches_exact_tracker() {
+        let mut rng = SplitMix64(37);
+        let mut sparse = SparseTracker::new(75);
+        let mut exact = ExactTracker::new(75);
+        for step in 0..5_000 {
+            let value = rng.below(40) * 5;
+            sparse.insert(value);
+            exact.insert(value);
+            if step % 97 == 0 {
+                assert_eq!(sparse.get_percentile(), exact.get_percentile());
+            }
+        }
+        assert!(sparse.distinct_count() <= 40);
+        assert_eq!(sparse.value_at_rank(5_000), None);
+
+        for value in [0, 5, 10] {
+            assert_eq!(sparse.remove(&value), exact.remove(&value));
+        }
+        assert_eq!(sparse.get_percentile(), exact.get_percentile());
+        assert!(!sparse.remove(&3));
+    }
+
+    #[test]
+    fn test_insert_many_and_merge() {
+        let mut left = SparseTracker::new(50);
+        left.insert_many("GET", 70);
+        left.insert_many("PUT", 0);
+        let mut right = SparseTracker::new(90);
+        right.insert_many("POST", 40);
+        left.merge(&right);
+        assert_eq!(left.count(), 110);
+        assert_eq!(left.distinct_count(), 2);
+        assert_eq!(left.count_of(&"POST"), 40);
+        // "GET" < "POST" and ranks 0..70 are GET
+        assert_eq!(left.get_percentile(), "GET");
+        assert_eq!(left.value_at_percentile(70.0), Some("POST"));
+    }
+}
@@ -0,0 +1,472 @@
+//! An exact percentile tracker that keeps most of its values on disk.
+//!
+//! [`SpillingTracker`] holds only a window of values around the tracked percentile in
+//! memory. Values below the window are appended to one file and values above it to another,
+//! which only needs their counts to locate the percentile. As long as the percentile stays
+//! inside the window, inserts and queries never touch the files except to append.
+//!
+//! When the window grows past its capacity, the values farthest from the percentile are
+//! spilled. When the percentile drifts out of the window, the window is rebuilt from the
+//! file it drifted into: a first pass samples the file to pick bounds around the target
+//! rank, a second pass confirms them, and a third moves the values between the bounds into
+//! memory. Memory stays proportional to the window capacity, however many values are stored.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use crate::rng::SplitMix64;
+use crate::PercentileTracker;
+
+/// Number of values sampled from a spill file to choose the bounds of a new window.
+const SAMPLE_SIZE: usize = 4096;
+
+/// The largest encoded value size supported by [`SpillValue`].
+const MAX_VALUE_SIZE: usize = 16;
+
+/// Values with a fixed-size binary encoding, so they can be spilled to disk.
+pub trait SpillValue: Copy + Ord {
+    /// Number of bytes in the encoding, at most 16.
+    const SIZE: usize;
+
+    /// Writes the value into `bytes`, which is exactly `SIZE` bytes long.
+    fn to_bytes(self, bytes: &mut [u8]);
+
+    /// Reads a value written by [`to_bytes`](Self::to_bytes).
+    fn from_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_spill_value {
+    ($($t:ty),*) => {
+        $(
+            impl SpillValue for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn to_bytes(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().expect("Spilled value has the wrong size"))
+                }
+            }
+        )*
+    };
+}
+
+impl_spill_value!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// An append-only file of spilled values.
+struct SpillFile<T> {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    len: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T> SpillFile<T>
+where
+    T: SpillValue,
+{
+    /// Creates an empty file, truncating any existing one.
+    fn create(path: PathBuf) -> io::Result<Self> {
+        assert!(
+            T::SIZE <= MAX_VALUE_SIZE,
+            "Spilled values must encode to at most 16 bytes"
+        );
+        let writer = BufWriter::new(File::create(&path)?);
+        Ok(SpillFile {
+            path,
+            writer,
+            len: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Appends a value.
+    fn push(&mut self, value: T) -> io::Result<()> {
+        let mut bytes = [0; MAX_VALUE_SIZE];
+        value.to_bytes(&mut bytes[..T::SIZE]);
+        self.writer.write_all(&bytes[..T::SIZE])?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Streams every value in the file through `f`, in the order they were appended.
+    fn for_each(&mut self, mut f: impl FnMut(T) -> io::Result<()>) -> io::Result<()> {
+        self.writer.flush()?;
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut bytes = [0; MAX_VALUE_SIZE];
+        for _ in 0..self.len {
+            reader.read_exact(&mut bytes[..T::SIZE])?;
+            f(T::from_bytes(&bytes[..T::SIZE]))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces this file's contents with another file's, moving it to this file's path.
+    fn replace_with(&mut self, mut other: SpillFile<T>) -> io::Result<()> {
+        other.writer.flush()?;
+        drop(other.writer);
+        fs::rename(&other.path, &self.path)?;
+        self.writer = BufWriter::new(OpenOptions::new().append(true).open(&self.path)?);
+        self.len = other.len;
+        Ok(())
+    }
+
+    /// Picks bounds around the value at `rank` in this file, such that roughly `width`
+    /// values lie between them. `None` means the window extends to that end of the file.
+    fn select_window(
+        &mut self,
+        rank: u64,
+        width: u64,
+        rng: &mut SplitMix64,
+    ) -> io::Result<(Option<T>, Option<T>)> {
+        let mut sample = Vec::with_capacity(SAMPLE_SIZE.min(self.len as usize));
+        let mut seen = 0;
+        self.for_each(|value| {
+            seen += 1;
+            if sample.len() < SAMPLE_SIZE {
+                sample.push(value);
+            } else {
+                let slot = rng.below(seen);
+                if slot < SAMPLE_SIZE as u64 {
+                    sample[slot as usize] = value;
+                }
+            }
+            Ok(())
+        })?;
+        sample.sort_unstable();
+
+        let len = self.len;
+        let samples = sample.len() as u64;
+        let mut width = width.max(1);
+        loop {
+            let first = rank.saturating_sub(width / 2);
+            let last = rank.saturating_add(width / 2);
+            let sample_at =
+                |rank: u64| sample[(rank as u128 * samples as u128 / len as u128) as usize];
+            let lower = (first > 0).then(|| sample_at(first));
+            let upper = (last + 1 < len).then(|| sample_at(last));
+            if lower.is_none() && upper.is_none() {
+                return Ok((None, None));
+            }
+
+            // The sample only estimates ranks, so count to make sure the window holds the target
+            let mut before = 0;
+            let mut inside = 0;
+            self.for_each(|value| {
+                if lower.is_some_and(|lower| value < lower) {
+                    before += 1;
+                } else if upper.is_none_or(|upper| value <= upper) {
+                    inside += 1;
+                }
+                Ok(())
+            })?;
+            if before <= rank && rank < before + inside {
+                return Ok((lower, upper));
+            }
+            width *= 2;
+        }
+    }
+}
+
+/// An exact percentile tracker that keeps values far from the percentile on disk.
+///
+/// ```
+/// use percentiletracker::SpillingTracker;
+///
+/// let dir = std::env::temp_dir().join(format!("spill-doctest-{}", std::process::id()));
+/// let mut tracker = SpillingTracker::new(90, &dir, 1_000).unwrap();
+/// for i in 0..100_000u64 {
+///     tracker.insert((i * 7919) % 100_000).unwrap();
+/// }
+/// assert_eq!(tracker.get_percentile().unwrap(), Some(90_000));
+/// assert!(tracker.hot_len() <= 1_000);
+/// # drop(tracker);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub struct SpillingTracker<T>
+where
+    T: SpillValue,
+{
+    /// The values between `lower` and `upper`, inclusive.
+    hot: PercentileTracker<T>,
+
+    /// Values below this go to the `below` file. `None` if nothing has been spilled below.
+    lower: Option<T>,
+
+    /// Values above this go to the `above` file. `None` if nothing has been spilled above.
+    upper: Option<T>,
+
+    /// Values smaller than every value in memory.
+    below: SpillFile<T>,
+
+    /// Values larger than every value in memory.
+    above: SpillFile<T>,
+
+    /// The directory holding the spill files.
+    dir: PathBuf,
+
+    /// The percentile to track (0-100)
+    percentile: usize,
+
+    /// The number of values kept in memory before spilling.
+    hot_capacity: usize,
+
+    /// The in-memory size that triggers the next spill.
+    spill_at: usize,
+
+    /// Generator for sampling spill files.
+    rng: SplitMix64,
+}
+
+impl<T> SpillingTracker<T>
+where
+    T: SpillValue,
+{
+    /// Creates an empty tracker that spills to files in `dir`, keeping at most about
+    /// `hot_capacity` values in memory.
+    ///
+    /// The directory is created if needed, and must not be shared with another tracker.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or `hot_capacity` is below 2.
+    pub fn new(
+        percentile: usize,
+        dir: impl Into<PathBuf>,
+        hot_capacity: usize,
+    ) -> io::Result<Self> {
+        assert!(hot_capacity >= 2, "The hot capacity must be at least 2");
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(SpillingTracker {
+            hot: PercentileTracker::new(percentile),
+            lower: None,
+            upper: None,
+            below: SpillFile::create(dir.join("below.spill"))?,
+            above: SpillFile::create(dir.join("above.spill"))?,
+            dir,
+            percentile,
+            hot_capacity,
+            spill_at: hot_capacity,
+            rng: SplitMix64(0x5EED),
+        })
+    }
+
+    /// Returns the number of values in the tracker.
+    pub fn len(&self) -> u64 {
+        self.below.len + self.hot.count() as u64 + self.above.len
+    }
+
+    /// Returns true if the tracker holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values held in memory.
+    pub fn hot_len(&self) -> usize {
+        self.hot.count()
+    }
+
+    /// Returns the number of values held on disk.
+    pub fn spilled_len(&self) -> u64 {
+        self.below.len + self.above.len
+    }
+
+    /// Inserts a value, appending it to a spill file if it falls outside the in-memory window.
+    pub fn insert(&mut self, value: T) -> io::Result<()> {
+        if self.lower.is_some_and(|lower| value < lower) {
+            return self.below.push(value);
+        }
+        if self.upper.is_some_and(|upper| value > upper) {
+            return self.above.push(value);
+        }
+        self.hot.insert(value);
+        if self.hot.count() > self.spill_at {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value at the tracked percentile, or `None` if the tracker is empty.
+    ///
+    /// This reads the spill files if the percentile has drifted out of the in-memory window.
+    pub fn get_percentile(&mut self) -> io::Result<Option<T>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let rank = self.target_rank();
+        if rank < self.below.len {
+            self.reload_below(rank)?;
+        } else if rank >= self.below.len + self.hot.count() as u64 {
+            let rank = rank - self.below.len - self.hot.count() as u64;
+            self.reload_above(rank)?;
+        }
+        Ok(self.hot.kth_smallest((rank - self.below.len) as usize + 1))
+    }
+
+    /// Returns the rank of the tracked percentile, using the same rule as [`PercentileTracker`].
+    fn target_rank(&self) -> u64 {
+        (self.percentile as u128 * self.len() as u128 / 100) as u64
+    }
+
+    /// Returns the number of values a rebuilt window holds, leaving room to grow before the next spill.
+    fn window_width(&self) -> usize {
+        self.hot_capacity / 2
+    }
+
+    /// Replaces the in-memory values and their bounds.
+    fn set_hot(&mut self, hot: PercentileTracker<T>, lower: Option<T>, upper: Option<T>) {
+        self.hot = hot;
+        self.lower = lower;
+        self.upper = upper;
+        // A run of equal values can't be split, so let the window grow rather than spill on every insert
+        self.spill_at = self.hot_capacity.max(2 * self.hot.count());
+    }
+
+    /// Shrinks the in-memory window around the percentile, spilling the values outside it.
+    fn spill(&mut self) -> io::Result<()> {
+        let snapshot = self.hot.snapshot();
+        let values = snapshot.values();
+        let width = self.window_width();
+        let local = self.target_rank().saturating_sub(self.below.len) as usize;
+        let start = local.saturating_sub(width / 2).min(values.len() - width);
+        let lower = values[start];
+        let upper = values[start + width - 1];
+
+        let mut hot = PercentileTracker::new(self.percentile);
+        for &value in values {
+            if value < lower {
+                self.below.push(value)?;
+            } else if value > upper {
+                self.above.push(value)?;
+            } else {
+                hot.insert(value);
+            }
+        }
+        self.set_hot(hot, Some(lower), Some(upper));
+        Ok(())
+    }
+
+    /// Spills again if a rebuilt window came out larger than the capacity, which happens
+    /// when the sampled bounds had to be widened.
+    fn trim(&mut self) -> io::Result<()> {
+        if self.hot.count() > self.hot_capacity {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the in-memory window around a rank that falls in the `below` file.
+    fn reload_below(&mut self, rank: u64) -> io::Result<()> {
+        let width = self.window_width() as u64;
+        let (lower, upper) = self.below.select_window(rank, width, &mut self.rng)?;
+
+        // Everything in memory is larger than the new window
+        let old = std::mem::replace(&mut self.hot, PercentileTracker::new(self.percentile));
+        for bucket in old.buckets.into_inner() {
+            for value in bucket.values {
+                self.above.push(value)?;
+            }
+        }
+
+        let mut below = SpillFile::create(self.dir.join("below.spill.tmp"))?;
+        let mut hot = PercentileTracker::new(self.percentile);
+        let above = &mut self.above;
+        self.below.for_each(|value| {
+            if lower.is_some_and(|lower| value < lower) {
+                below.push(value)
+            } else if upper.is_some_and(|upper| value > upper) {
+                above.push(value)
+            } else {
+                hot.insert(value);
+                Ok(())
+            }
+        })?;
+        self.below.replace_with(below)?;
+        let upper = upper.or_else(|| hot.max());
+        self.set_hot(hot, lower, upper);
+        self.trim()
+    }
+
+    /// Rebuilds the in-memory window around a rank that falls in the `above` file.
+    fn reload_above(&mut self, rank: u64) -> io::Result<()> {
+        let width = self.window_width() as u64;
+        let (lower, upper) = self.above.select_window(rank, width, &mut self.rng)?;
+
+        // Everything in memory is smaller than the new window
+        let old = std::mem::replace(&mut self.hot, PercentileTracker::new(self.percentile));
+        for bucket in old.buckets.into_inner() {
+            for value in bucket.values {
+                self.below.push(value)?;
+            }
+        }
+
+        let mut above = SpillFile::create(self.dir.join("above.spill.tmp"))?;
+        let mut hot = PercentileTracker::new(self.percentile);
+        let below = &mut self.below;
+        self.above.for_each(|value| {
+            if lower.is_some_and(|lower| value < lower) {
+                below.push(value)
+            } else if upper.is_some_and(|upper| value > upper) {
+                above.push(value)
+            } else {
+                hot.insert(value);
+                Ok(())
+            }
+        })?;
+        self.above.replace_with(above)?;
+        let lower = lower.or_else(|| hot.min());
+        self.set_hot(hot, lower, upper);
+        self.trim()
+    }
+}
+
+impl<T> Drop for SpillingTracker<T>
+where
+    T: SpillValue,
+{
+    fn drop(&mut self) {
+        // Best effort, there is nothing useful to do with an error here
+        let _ = fs::remove_file(&self.below.path);
+        let _ = fs::remove_file(&self.above.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spilling_tracker_is_exact() {
+        let dir = std::env::temp_dir().join(format!("spill-test-{}", std::process::id()));
+        let mut tracker = SpillingTracker::new(75, &dir, 64).unwrap();
+        assert_eq!(tracker.get_percentile().unwrap(), None);
+
+        // Scattered values, then a run below everything and a run above, to drag the
+        // percentile out of the in-memory window in both directions
+        let values = (0..3_000i64)
+            .map(|i| (i * 7919) % 3_001)
+            .chain((0..4_000).map(|i| -i))
+            .chain((0..8_000).map(|i| 10_000 + i));
+        let mut oracle = Vec::new();
+        for (step, value) in values.enumerate() {
+            tracker.insert(value).unwrap();
+            oracle.push(value);
+            if step % 101 == 0 {
+                oracle.sort_unstable();
+                let expected = oracle[oracle.len() * 75 / 100];
+                assert_eq!(tracker.get_percentile().unwrap(), Some(expected));
+                assert!(tracker.hot_len() <= 64);
+            }
+        }
+        assert_eq!(tracker.len(), 15_000);
+        assert!(tracker.spilled_len() > 14_000);
+
+        drop(tracker);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
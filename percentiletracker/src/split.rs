@@ -0,0 +1,139 @@
+use crate::{Bucket, PercentileTracker};
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Moves every value greater than or equal to `threshold` into a new tracker, and returns it.
+    ///
+    /// The buckets already partition the values by range, so whole buckets above the
+    /// threshold are moved as they are and only the bucket straddling it is divided. No value
+    /// is copied.
+    ///
+    /// The new tracker has the same percentile, bucket size, unit, quantile method, and
+    /// sorting routine as this one. Other builder options, such as a reservoir, aren't carried
+    /// over. Both trackers clear their undo journals.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut fast = PercentileTracker::<u64>::new(50);
+    /// for latency_us in [120, 80, 15_000, 95, 22_000, 110] {
+    ///     fast.insert(latency_us);
+    /// }
+    /// let slow = fast.split_at(&10_000);
+    /// assert_eq!(fast.max(), Some(120));
+    /// assert_eq!(slow.min(), Some(15_000));
+    /// ```
+    pub fn split_at(&mut self, threshold: &T) -> PercentileTracker<T> {
+        let mut buckets = std::mem::take(self.buckets.get_mut());
+
+        // Every bucket before the first with a minimum at or above the threshold holds values
+        // no larger than that minimum, so only the bucket just before it can straddle it
+        let first_upper = buckets.partition_point(|bucket| bucket.min() < threshold);
+        let mut upper = buckets.split_off(first_upper);
+        if let Some(boundary) = buckets.last_mut() {
+            if boundary.max() >= threshold {
+                let sorted = boundary.sorted;
+                let (below, above): (Vec<T>, Vec<T>) = std::mem::take(&mut boundary.values)
+                    .into_iter()
+                    .partition(|value| value < threshold);
+                *boundary = Bucket::from_run(below, sorted);
+                upper.insert(0, Bucket::from_run(above, sorted));
+            }
+        }
+
+        let mut split = PercentileTracker::builder()
+            .percentile(self.percentile)
+            .max_bucket_size(self.max_bucket_size)
+            .unit(self.unit)
+            .quantile_method(self.quantile_method)
+            .build()
+            .expect("The configuration was validated when this tracker was built");
+        split.sort_values = self.sort_values;
+        split.load_buckets(upper);
+        self.load_buckets(buckets);
+        split
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::Unit;
+
+    #[test]
+    fn test_split_at() {
+        let mut rng = SplitMix64(9);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .unit(Unit::Microseconds)
+            .min_index()
+            .build()
+            .unwrap();
+        let mut values = Vec::new();
+        for _ in 0..2_000 {
+            let value = rng.below(100);
+            tracker.insert(value);
+            values.push(value);
+            if values.len() % 300 == 0 {
+                tracker.get_percentile();
+            }
+        }
+        values.sort_unstable();
+
+        let upper = tracker.split_at(&40);
+        let boundary = values.partition_point(|&value| value < 40);
+        assert!(tracker.iter_sorted().eq(&values[..boundary]));
+        assert_eq!(upper.count(), values.len() - boundary);
+        assert_eq!(upper.unit(), Unit::Microseconds);
+        assert_eq!(upper.min(), Some(40));
+        assert_eq!(
+            upper.get_percentile(),
+            values[boundary + (values.len() - boundary) * 90 / 100]
+        );
+        assert!(upper.verify_bucket_offset());
+        assert_eq!(tracker.max(), Some(39));
+        assert_eq!(tracker.get_percentile(), values[boundary * 90 / 100]);
+        assert!(tracker.verify_bucket_offset());
+
+        // Both halves keep tracking
+        tracker.insert(1_000);
+        assert_eq!(tracker.max(), Some(1_000));
+        assert_eq!(
+            tracker.min_index.borrow().as_ref().unwrap().mins().len(),
+            tracker.buckets.borrow().len()
+        );
+    }
+
+    #[test]
+    fn test_split_at_extremes() {
+        let mut tracker = PercentileTracker::new(50);
+        for value in 0..100i32 {
+            tracker.insert(value);
+        }
+        let everything = tracker.split_at(&i32::MIN);
+        assert_eq!((tracker.count(), everything.count()), (0, 100));
+        assert_eq!(tracker.min(), None);
+        assert_eq!(tracker.cursor(), (0, 0));
+        assert!(!tracker.needs_rebalancing.get());
+        assert_eq!(
+            tracker.try_get_percentile(),
+            Err(crate::PercentileError::Empty)
+        );
+        tracker.insert(-5);
+        assert_eq!(tracker.get_percentile(), -5);
+        assert!(tracker.verify_bucket_offset());
+
+        let mut tracker = everything;
+        let nothing = tracker.split_at(&100);
+        assert_eq!((tracker.count(), nothing.count()), (100, 0));
+        assert_eq!(tracker.get_percentile(), 50);
+        assert_eq!(
+            nothing.try_get_percentile(),
+            Err(crate::PercentileError::Empty)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use crate::PercentileTracker;
+
+/// The internal state of a tracker, for monitoring and debugging.
+///
+/// Unlike [`print_stats`](PercentileTracker::print_stats), this doesn't rebalance first, so
+/// it shows whether the last inserts still have work pending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackerStats {
+    /// Number of values in the tracker.
+    pub count: usize,
+
+    /// Number of values ever inserted, including any a reservoir discarded.
+    pub seen: u64,
+
+    /// The tracked percentile (1-99).
+    pub percentile: usize,
+
+    /// The size above which the bucket holding the percentile is split.
+    pub max_bucket_size: usize,
+
+    /// Number of values in each bucket, in ascending order of the bucket's values.
+    pub bucket_sizes: Vec<usize>,
+
+    /// Number of buckets whose values are currently sorted.
+    pub sorted_buckets: usize,
+
+    /// Index of the bucket the percentile cursor points at.
+    pub cursor_bucket: usize,
+
+    /// Number of values in the buckets before the cursor's bucket.
+    pub cursor_offset: usize,
+
+    /// True if values were inserted or removed since the last rebalance.
+    pub needs_rebalancing: bool,
+}
+
+impl TrackerStats {
+    /// Returns the number of buckets.
+    pub fn bucket_count(&self) -> usize {
+        self.bucket_sizes.len()
+    }
+
+    /// Returns the number of values in the largest bucket, or 0 if there are no buckets.
+    pub fn largest_bucket(&self) -> usize {
+        self.bucket_sizes.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the mean number of values per bucket, or 0 if there are no buckets.
+    pub fn mean_bucket_size(&self) -> f64 {
+        if self.bucket_sizes.is_empty() {
+            return 0.0;
+        }
+        self.count as f64 / self.bucket_sizes.len() as f64
+    }
+}
+
+/// One bucket of a tracker, as returned by [`PercentileTracker::buckets`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketInfo<T> {
+    /// The smallest value in the bucket. Every value in later buckets is at least this.
+    pub min: T,
+
+    /// The largest value in the bucket.
+    pub max: T,
+
+    /// Number of values in the bucket.
+    pub len: usize,
+
+    /// True if the bucket's values are currently sorted.
+    pub sorted: bool,
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the number of buckets the values are partitioned into.
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.borrow().len()
+    }
+
+    /// Iterates over the buckets in ascending order of their values, describing the range
+    /// and size of each.
+    ///
+    /// This copies the extremes of every bucket up front, since the buckets themselves are
+    /// reorganized by later queries. Like [`stats`](Self::stats), it doesn't rebalance
+    /// first.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::builder()
+    ///     .max_bucket_size(4)
+    ///     .build()
+    ///     .unwrap();
+    /// for value in 0..10 {
+    ///     tracker.insert(value);
+    /// }
+    /// tracker.get_percentile();
+    /// let sizes: Vec<(u64, usize)> = tracker
+    ///     .buckets()
+    ///     .map(|bucket| (bucket.min, bucket.len))
+    ///     .collect();
+    /// assert_eq!(sizes.len(), tracker.bucket_count());
+    /// assert_eq!(sizes[0].0, 0);
+    /// assert_eq!(sizes.iter().map(|&(_, len)| len).sum::<usize>(), 10);
+    /// ```
+    pub fn buckets(&self) -> impl ExactSizeIterator<Item = BucketInfo<T>>
+    where
+        T: Clone,
+    {
+        let infos: Vec<BucketInfo<T>> = self
+            .buckets
+            .borrow()
+            .iter()
+            .map(|bucket| BucketInfo {
+                min: bucket.min().clone(),
+                max: bucket.max().clone(),
+                len: bucket.len(),
+                sorted: bucket.sorted,
+            })
+            .collect();
+        infos.into_iter()
+    }
+
+    /// Returns the tracker's internal state as data, e.g. to export it as metrics.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(90);
+    /// for value in 0..1_000 {
+    ///     tracker.insert(value);
+    /// }
+    /// assert!(tracker.stats().needs_rebalancing);
+    ///
+    /// tracker.get_percentile();
+    /// let stats = tracker.stats();
+    /// assert_eq!(stats.count, 1_000);
+    /// assert!(!stats.needs_rebalancing);
+    /// assert_eq!(stats.bucket_sizes.iter().sum::<usize>(), 1_000);
+    /// ```
+    pub fn stats(&self) -> TrackerStats {
+        let buckets = self.buckets.borrow();
+        let (cursor_bucket, cursor_offset) = self.cursor();
+        TrackerStats {
+            count: self.count(),
+            seen: self.seen(),
+            percentile: self.percentile,
+            max_bucket_size: self.max_bucket_size,
+            bucket_sizes: buckets.iter().map(|bucket| bucket.len()).collect(),
+            sorted_buckets: buckets.iter().filter(|bucket| bucket.sorted).count(),
+            cursor_bucket,
+            cursor_offset,
+            needs_rebalancing: self.needs_rebalancing.get(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(50)
+            .max_bucket_size(10)
+            .build()
+            .unwrap();
+        let empty = tracker.stats();
+        assert_eq!(empty.bucket_count(), 0);
+        assert_eq!(empty.largest_bucket(), 0);
+        assert_eq!(empty.mean_bucket_size(), 0.0);
+
+        for value in 0..100u32 {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        let stats = tracker.stats();
+        assert_eq!(stats.seen, 100);
+        assert_eq!(stats.percentile, 50);
+        assert!(stats.bucket_sizes[stats.cursor_bucket] <= 10);
+        assert_eq!(
+            stats.bucket_sizes[..stats.cursor_bucket]
+                .iter()
+                .sum::<usize>(),
+            stats.cursor_offset
+        );
+        assert!(stats.sorted_buckets >= 1);
+        assert_eq!(stats.largest_bucket(), 50);
+        assert_eq!(
+            stats.mean_bucket_size(),
+            100.0 / stats.bucket_count() as f64
+        );
+
+        let buckets: Vec<BucketInfo<u32>> = tracker.buckets().collect();
+        assert_eq!(buckets.len(), tracker.bucket_count());
+        assert_eq!(
+            buckets.iter().map(|bucket| bucket.len).collect::<Vec<_>>(),
+            stats.bucket_sizes
+        );
+        assert!(buckets.windows(2).all(|pair| pair[0].max <= pair[1].min));
+        assert!(buckets[stats.cursor_bucket].sorted);
+        assert_eq!(buckets.last().unwrap().max, 99);
+    }
+}
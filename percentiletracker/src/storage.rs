@@ -0,0 +1,195 @@
+//! Choosing what a tracker keeps its values in.
+//!
+//! A [`PercentileTracker`] keeps every value in exact buckets by default. With the `kll`
+//! feature, [`PercentileTrackerBuilder::storage`] can instead give it a [`KllSketch`], so a
+//! service keeps the same front end, hooks and merge calls while its memory stays bounded
+//! for any stream.
+//!
+//! [`KllSketch`]: crate::KllSketch
+
+use crate::{PercentileTracker, PercentileTrackerBuilder};
+
+/// What a [`PercentileTracker`] keeps its values in.
+///
+/// A sketch answers the estimator queries: [`len`](PercentileTracker::len),
+/// [`get_percentile`](PercentileTracker::get_percentile),
+/// [`get_percentile_ref`](PercentileTracker::get_percentile_ref),
+/// [`get_percentiles`](PercentileTracker::get_percentiles),
+/// [`value_at_rank`](PercentileTracker::value_at_rank), [`min`](PercentileTracker::min),
+/// [`max`](PercentileTracker::max), [`merge`](PercentileTracker::merge) and
+/// [`error_bound`](PercentileTracker::error_bound), along with everything built on them
+/// such as [`QuantileEstimator`](crate::QuantileEstimator). Methods that walk the stored
+/// values, such as snapshots, rank counts, moments, checkpoints and splits, need exact
+/// storage and see a sketch-backed tracker as empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Storage {
+    /// Every value, in buckets, so every answer is exact.
+    #[default]
+    Exact,
+
+    /// A [`KllSketch`](crate::KllSketch) of the given size, which holds about `3k` values
+    /// for any stream. See [`DEFAULT_K`](crate::kll::DEFAULT_K).
+    #[cfg(feature = "kll")]
+    Kll { k: usize },
+}
+
+impl<T> PercentileTrackerBuilder<T>
+where
+    T: Ord,
+{
+    /// Sets what the tracker keeps its values in, exact buckets by default.
+    ///
+    /// Sketch storage can't be combined with the options that act on the stored values:
+    /// a reservoir, a memory budget, undo, the minimum index, the shadow oracle, history,
+    /// alerts, amortized rebalancing or a custom sort. See [`Storage`] for which queries a
+    /// sketch answers.
+    ///
+    /// ```
+    /// use percentiletracker::{ErrorBound, PercentileTracker, Storage};
+    ///
+    /// let mut latencies = PercentileTracker::<u64>::builder()
+    ///     .percentile(99)
+    ///     .storage(Storage::Kll { k: 200 })
+    ///     .build()
+    ///     .unwrap();
+    /// for ms in 0..100_000 {
+    ///     latencies.insert(ms % 1_000);
+    /// }
+    /// assert_eq!(latencies.len(), 100_000);
+    /// assert!(latencies.get_percentile().abs_diff(990) <= 20);
+    /// assert!(matches!(latencies.error_bound(), ErrorBound::Rank(_)));
+    /// ```
+    #[cfg(feature = "kll")]
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns what the tracker keeps its values in.
+    pub fn storage(&self) -> Storage {
+        #[cfg(feature = "kll")]
+        if let Some(sketch) = &self.sketch {
+            return Storage::Kll { k: sketch.k() };
+        }
+        Storage::Exact
+    }
+
+    /// Returns true if the values are kept in a sketch rather than in buckets.
+    pub(crate) fn has_sketch(&self) -> bool {
+        self.storage() != Storage::Exact
+    }
+}
+
+#[cfg(all(test, feature = "kll"))]
+mod tests {
+    use super::*;
+    use crate::{BuildError, ErrorBound, MergeError, QuantileEstimator, RangePolicy};
+
+    fn kll_tracker(percentile: usize, k: usize) -> PercentileTracker<u64> {
+        PercentileTracker::builder()
+            .percentile(percentile)
+            .storage(Storage::Kll { k })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_kll_storage_queries() {
+        let mut tracker = kll_tracker(90, 100);
+        assert!(tracker.is_empty());
+        assert_eq!(tracker.storage(), Storage::Kll { k: 100 });
+        assert_eq!(tracker.min(), None);
+        for value in (0..50_000u64).map(|i| (i * 7919) % 50_000) {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.len(), 50_000);
+        assert_eq!(tracker.min(), Some(0));
+        assert_eq!(tracker.max(), Some(49_999));
+        let p90 = tracker.get_percentile();
+        assert!(p90.abs_diff(45_000) <= 2_500);
+        assert_eq!(*tracker.get_percentile_ref(), p90);
+        let percentiles = tracker.get_percentiles(&[50.0, 100.0]);
+        assert!(percentiles[0].abs_diff(25_000) <= 2_500);
+        assert_eq!(percentiles[1], 49_999);
+        assert_eq!(tracker.value_at_rank(50_000), None);
+        assert!(!QuantileEstimator::is_exact(&tracker));
+
+        // The sketch's answers follow a change of percentile
+        tracker.set_percentile(10).unwrap();
+        assert!(tracker.get_percentile().abs_diff(5_000) <= 2_500);
+    }
+
+    #[test]
+    fn test_kll_storage_keeps_hooks() {
+        let mut tracker = PercentileTracker::<u64>::builder()
+            .storage(Storage::Kll { k: 64 })
+            .accepted_range(10..=20, RangePolicy::Ignore)
+            .build()
+            .unwrap();
+        for value in 0..30 {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.len(), 11);
+        assert_eq!(tracker.error_bound(), ErrorBound::Exact);
+        assert_eq!(tracker.get_percentile(), 15);
+    }
+
+    #[test]
+    fn test_kll_storage_merge() {
+        let mut left = kll_tracker(50, 100);
+        let mut right = kll_tracker(50, 100);
+        let mut exact = PercentileTracker::new(50);
+        for value in 0..20_000u64 {
+            left.insert(value);
+            right.insert(value + 20_000);
+            exact.insert(value + 40_000);
+        }
+        left.merge(&right).unwrap();
+        left.merge_all([&exact]).unwrap();
+        assert_eq!(left.len(), 60_000);
+        assert_eq!(left.max(), Some(59_999));
+        assert!(left.get_percentile().abs_diff(30_000) <= 3_000);
+
+        // An exact tracker can't take in a sketch's values, nor a sketch of another size
+        let mut other = kll_tracker(50, 200);
+        assert_eq!(other.merge(&left), Err(MergeError::IncompatibleParameters));
+        assert_eq!(
+            exact.merge_all([&left]),
+            Err(MergeError::IncompatibleParameters)
+        );
+        assert_eq!(exact.len(), 20_000);
+    }
+
+    #[test]
+    fn test_kll_storage_validation() {
+        assert_eq!(
+            PercentileTracker::<u64>::builder()
+                .storage(Storage::Kll { k: 4 })
+                .build()
+                .err(),
+            Some(BuildError::InvalidSketchSize(4))
+        );
+        assert_eq!(
+            PercentileTracker::<u64>::builder()
+                .storage(Storage::Kll { k: 200 })
+                .reservoir(100)
+                .build()
+                .err(),
+            Some(BuildError::NeedsExactStorage("reservoir"))
+        );
+        assert_eq!(
+            PercentileTracker::<u64>::builder()
+                .storage(Storage::Kll { k: 200 })
+                .undo_journal(8)
+                .build()
+                .err(),
+            Some(BuildError::NeedsExactStorage("undo_journal"))
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! An exact running sum of the stored values, for integer-like types from `num-traits`.
+//!
+//! [`mean`](PercentileTracker::mean) is accumulated as an `f64`, which loses precision for
+//! large totals. [`sum`](PercentileTracker::sum) adds the values in `T` itself, so a metrics
+//! consumer can report an exact total alongside the mean and percentiles of one tracker.
+
+use std::cell::Ref;
+
+use num_traits::{WrappingAdd, WrappingSub, Zero};
+
+use crate::PercentileTracker;
+
+/// The sum of the stored values, maintained on every insert and removal.
+pub(crate) struct RunningSum<T> {
+    /// The sum so far, wrapped on overflow.
+    total: T,
+
+    /// Adds a value to the total. Captured when the sum is first queried, since only then is
+    /// `T` known to be summable.
+    add: fn(&T, &T) -> T,
+
+    /// Subtracts a value from the total.
+    sub: fn(&T, &T) -> T,
+}
+
+impl<T> RunningSum<T> {
+    /// Adds an inserted value.
+    pub(crate) fn push(&mut self, value: &T) {
+        self.total = (self.add)(&self.total, value);
+    }
+
+    /// Subtracts a removed value.
+    pub(crate) fn pop(&mut self, value: &T) {
+        self.total = (self.sub)(&self.total, value);
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Returns the sum of the values in the tracker, or zero if it is empty.
+    ///
+    /// The first call walks every value once, and from then on the sum is maintained on
+    /// insert and removal. The sum wraps on overflow, which keeps removals exact: once values
+    /// are removed again a wrapped sum comes back into range. Use a wider type than the
+    /// values need if the total itself may overflow.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut bytes = PercentileTracker::<u64>::new(99);
+    /// for size in [512, 1_024, 4_096] {
+    ///     bytes.insert(size);
+    /// }
+    /// assert_eq!(bytes.sum(), 5_632);
+    /// bytes.insert(64);
+    /// assert_eq!(bytes.sum(), 5_696);
+    /// ```
+    pub fn sum(&self) -> T
+    where
+        T: Zero + WrappingAdd + WrappingSub + Clone,
+    {
+        self.running_sum().total.clone()
+    }
+
+    /// Returns the running sum, seeding it from the stored values on first use.
+    fn running_sum(&self) -> Ref<'_, RunningSum<T>>
+    where
+        T: Zero + WrappingAdd + WrappingSub + Clone,
+    {
+        if self.sum.borrow().is_none() {
+            let mut sum = RunningSum {
+                total: T::zero(),
+                add: |total: &T, value: &T| total.wrapping_add(value),
+                sub: |total: &T, value: &T| total.wrapping_sub(value),
+            };
+            for bucket in self.buckets.borrow().iter() {
+                for value in &bucket.values {
+                    sum.push(value);
+                }
+            }
+            *self.sum.borrow_mut() = Some(sum);
+        }
+        Ref::map(self.sum.borrow(), |sum| {
+            sum.as_ref().expect("Running sum was just seeded")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_sum() {
+        let mut tracker = PercentileTracker::builder()
+            .max_bucket_size(4)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.sum(), 0i64);
+        for value in -10..=20 {
+            tracker.insert(value);
+        }
+        assert_eq!(tracker.sum(), 155);
+        assert_eq!(tracker.mean(), Some(5.0));
+
+        let (bucket_idx, index) = tracker.find_value(&20).unwrap();
+        tracker.remove_from_bucket(bucket_idx, index);
+        assert_eq!(tracker.sum(), 135);
+        tracker.insert(7);
+        assert_eq!(tracker.sum(), 142);
+    }
+
+    #[test]
+    fn test_sum_wraps_and_recovers() {
+        let mut tracker = PercentileTracker::new(50);
+        tracker.insert(200u8);
+        tracker.insert(100);
+        assert_eq!(tracker.sum(), 44);
+        let (bucket_idx, index) = tracker.find_value(&200).unwrap();
+        tracker.remove_from_bucket(bucket_idx, index);
+        assert_eq!(tracker.sum(), 100);
+    }
+}
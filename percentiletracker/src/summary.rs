@@ -0,0 +1,119 @@
+use crate::{rank_for_percentile, Numeric, PercentileTracker, DELTA_PERCENTILES};
+
+/// The usual statistics of a tracker, gathered in a single call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary<T> {
+    /// Number of values in the tracker.
+    pub count: usize,
+
+    /// Smallest value, or `None` if the tracker is empty.
+    pub min: Option<T>,
+
+    /// Largest value, or `None` if the tracker is empty.
+    pub max: Option<T>,
+
+    /// Mean of the values, or `None` if the tracker is empty.
+    pub mean: Option<f64>,
+
+    /// Each requested percentile (0-100) with its value, in ascending order of percentile.
+    /// Empty if the tracker is empty.
+    pub percentiles: Vec<(f64, T)>,
+}
+
+impl<T> Summary<T> {
+    /// Returns the value at a percentile, if it was one of the percentiles summarized.
+    pub fn percentile(&self, percentile: f64) -> Option<&T> {
+        self.percentiles
+            .iter()
+            .find(|(p, _)| *p == percentile)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Numeric,
+{
+    /// Summarizes the tracker at p50, p90, p99 and p99.9.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(90);
+    /// for value in 1..=1000 {
+    ///     tracker.insert(value);
+    /// }
+    /// let summary = tracker.summary();
+    /// assert_eq!(summary.count, 1000);
+    /// assert_eq!(summary.mean, Some(500.5));
+    /// assert_eq!(summary.percentile(99.0), Some(&991));
+    /// ```
+    pub fn summary(&self) -> Summary<T> {
+        self.summary_at(&DELTA_PERCENTILES)
+    }
+
+    /// Summarizes the tracker at the given percentiles (0-100).
+    ///
+    /// All percentiles are found in a single walk over the buckets, sorting only the
+    /// buckets that hold one of them.
+    pub fn summary_at(&self, percentiles: &[f64]) -> Summary<T> {
+        let mut percentiles = percentiles.to_vec();
+        percentiles.sort_by(f64::total_cmp);
+        percentiles.dedup();
+
+        let values = if self.count() == 0 {
+            Vec::new()
+        } else {
+            let ranks: Vec<usize> = percentiles
+                .iter()
+                .map(|&percentile| rank_for_percentile(percentile, self.count()))
+                .collect();
+            self.select_ranks(&ranks)
+        };
+
+        Summary {
+            count: self.count(),
+            min: self.min(),
+            max: self.max(),
+            mean: self.mean(),
+            percentiles: percentiles.into_iter().zip(values).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut tracker = PercentileTracker::builder()
+            .percentile(90)
+            .max_bucket_size(8)
+            .build()
+            .unwrap();
+        let empty = tracker.summary();
+        assert_eq!(empty.count, 0);
+        assert_eq!(empty.min, None);
+        assert!(empty.percentiles.is_empty());
+
+        let mut values = Vec::new();
+        for value in (0..2000i64).map(|i| (i * 7919) % 2003) {
+            tracker.insert(value);
+            values.push(value);
+        }
+        tracker.get_percentile();
+        values.sort_unstable();
+
+        let summary = tracker.summary_at(&[99.0, 25.0, 0.0, 25.0, 100.0]);
+        assert_eq!(summary.count, values.len());
+        assert_eq!(summary.min, values.first().copied());
+        assert_eq!(summary.max, values.last().copied());
+        let expected: Vec<(f64, i64)> = [0.0, 25.0, 99.0, 100.0]
+            .into_iter()
+            .map(|p| (p, values[rank_for_percentile(p, values.len())]))
+            .collect();
+        assert_eq!(summary.percentiles, expected);
+        assert!(tracker.verify_bucket_offset());
+    }
+}
@@ -0,0 +1,203 @@
+//! A tracker for extreme tail percentiles that stores only the largest values.
+//!
+//! To report p99.9 of a huge stream, only the top 0.1% of the values matter. [`TailTracker`]
+//! keeps the values above a cutoff and only counts the ones below it, so memory is a small
+//! multiple of the tail instead of the whole stream. Every stored value's rank is still
+//! known exactly, because everything below the cutoff is smaller than everything above.
+//!
+//! The cutoff only rises, so a stream that later shifts towards small values can push the
+//! lowest percentiles of the tail below it. Those queries return `None` rather than an
+//! estimate, and [`exact_from`](TailTracker::exact_from) reports where exact answers begin.
+
+use crate::rank_for_percentile;
+
+/// The smallest number of values kept before any are discarded.
+const MIN_KEPT: usize = 64;
+
+/// A tracker that answers percentiles at or above a chosen floor exactly, while storing
+/// only the values above that floor.
+///
+/// The stored values are pruned to twice the tail once they grow past four times it, so
+/// memory stays within about four times the fraction of the stream above the floor.
+///
+/// ```
+/// use percentiletracker::TailTracker;
+///
+/// let mut latencies = TailTracker::new(99.0);
+/// for i in 0..100_000u64 {
+///     latencies.insert((i * 7919) % 100_000);
+/// }
+/// assert_eq!(latencies.value_at_percentile(99.9), Some(99_900));
+/// assert_eq!(latencies.value_at_percentile(99.0), Some(99_000));
+/// assert!(latencies.stored_len() <= 4_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TailTracker<T> {
+    /// Every value at or above the cutoff, sorted when `sorted` is set.
+    kept: Vec<T>,
+
+    /// Whether `kept` is in ascending order.
+    sorted: bool,
+
+    /// The number of values below the cutoff that were counted and dropped.
+    discarded: usize,
+
+    /// Values below this are discarded. `None` until the first prune.
+    cutoff: Option<T>,
+
+    /// The lowest percentile (0-100) the tracker is sized to answer.
+    floor: f64,
+}
+
+impl<T> TailTracker<T>
+where
+    T: Ord + Clone,
+{
+    /// Creates an empty tracker that answers percentiles from `floor` (0-100) upwards.
+    ///
+    /// # Panics
+    /// Panics if the floor is not at least 0 and below 100.
+    pub fn new(floor: f64) -> Self {
+        assert!(
+            (0.0..100.0).contains(&floor),
+            "The tail floor must be at least 0 and below 100, got {}",
+            floor
+        );
+        TailTracker {
+            kept: Vec::new(),
+            sorted: true,
+            discarded: 0,
+            cutoff: None,
+            floor,
+        }
+    }
+
+    /// Returns the number of values inserted, stored or not.
+    pub fn len(&self) -> usize {
+        self.discarded + self.kept.len()
+    }
+
+    /// Returns true if no values have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values held in memory.
+    pub fn stored_len(&self) -> usize {
+        self.kept.len()
+    }
+
+    /// Returns the lowest percentile the tracker was created to answer.
+    pub fn floor(&self) -> f64 {
+        self.floor
+    }
+
+    /// Returns the lowest percentile that can currently be answered exactly, or 100 if the
+    /// tracker is empty. This is at most [`floor`](Self::floor) unless the stream has
+    /// shifted towards smaller values since the last prune.
+    pub fn exact_from(&self) -> f64 {
+        if self.is_empty() {
+            return 100.0;
+        }
+        100.0 * self.discarded as f64 / self.len() as f64
+    }
+
+    /// Inserts a value, only counting it if it's below the stored tail.
+    pub fn insert(&mut self, value: T) {
+        if self.cutoff.as_ref().is_some_and(|cutoff| &value < cutoff) {
+            self.discarded += 1;
+            return;
+        }
+        self.kept.push(value);
+        self.sorted = false;
+        if self.kept.len() > 4 * self.tail_len() {
+            self.prune();
+        }
+    }
+
+    /// Returns the value at a percentile (0-100), or `None` if the tracker is empty or the
+    /// percentile falls below the stored values.
+    pub fn value_at_percentile(&mut self, percentile: f64) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let rank = rank_for_percentile(percentile, self.len());
+        let index = rank.checked_sub(self.discarded)?;
+        self.ensure_sorted();
+        Some(self.kept[index].clone())
+    }
+
+    /// Returns the number of values the floor percentile and everything above it span, and
+    /// at least [`MIN_KEPT`].
+    fn tail_len(&self) -> usize {
+        let len = self.len();
+        (len - rank_for_percentile(self.floor, len)).max(MIN_KEPT)
+    }
+
+    /// Sorts the stored values if they aren't already.
+    fn ensure_sorted(&mut self) {
+        if !self.sorted {
+            self.kept.sort_unstable();
+            self.sorted = true;
+        }
+    }
+
+    /// Discards the smallest stored values, keeping twice the tail so later inserts of
+    /// small values don't push the floor below the cutoff straight away.
+    fn prune(&mut self) {
+        let keep = 2 * self.tail_len();
+        let drop = self.kept.len() - keep;
+        self.kept.select_nth_unstable(drop);
+        self.kept.drain(..drop);
+        self.discarded += drop;
+        // Everything drained is at most the pivot, which is now the smallest kept value
+        self.cutoff = self.kept.iter().min().cloned();
+        self.sorted = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::ExactTracker;
+
+    #[test]
+    fn test_matches_exact_tracker() {
+        let mut rng = SplitMix64(17);
+        let mut tail = TailTracker::new(99.0);
+        let mut exact = ExactTracker::new(99);
+        for step in 0..20_000 {
+            let value = rng.below(1_000_000);
+            tail.insert(value);
+            exact.insert(value);
+            if step % 997 == 0 {
+                for percentile in [99.0, 99.5, 99.9, 100.0] {
+                    assert_eq!(
+                        tail.value_at_percentile(percentile).as_ref(),
+                        exact.get_percentiles(&[percentile]).first()
+                    );
+                }
+            }
+        }
+        assert!(tail.exact_from() <= 99.0);
+        assert!(tail.stored_len() <= 4 * 200);
+        assert_eq!(tail.len(), 20_000);
+    }
+
+    #[test]
+    fn test_shifted_stream_reports_unanswerable() {
+        let mut tail = TailTracker::new(90.0);
+        for value in 1_000..2_000u32 {
+            tail.insert(value);
+        }
+        assert_eq!(tail.value_at_percentile(90.0), Some(1_900));
+        // A flood of small values moves the 90th percentile below everything stored
+        for _ in 0..10_000 {
+            tail.insert(0);
+        }
+        assert_eq!(tail.value_at_percentile(90.0), None);
+        assert_eq!(tail.value_at_percentile(99.9), Some(1_989));
+        assert!(tail.exact_from() > 90.0);
+    }
+}
@@ -0,0 +1,462 @@
+//! A t-digest for summarizing huge streams of `f64` samples in a few kilobytes.
+//!
+//! This is the merging variant of Dunning's t-digest. Samples are buffered and periodically
+//! merged into a sorted list of centroids, and the `k1` scale function keeps centroids near
+//! the tails small so extreme percentiles stay accurate while the median is summarized more
+//! coarsely. The number of centroids is bounded by roughly `compression * π / 2` regardless
+//! of how many samples are inserted.
+//!
+//! Unlike [`PercentileTracker`](crate::PercentileTracker), answers are estimates, but two
+//! digests can be merged, including digests built on different hosts and shipped around
+//! with [`TDigest::to_bytes`].
+
+use std::cell::RefCell;
+use std::f64::consts::PI;
+
+use crate::{ErrorBound, MergeError, QuantileEstimator};
+
+/// The default compression, trading roughly 150 centroids for well under 1% rank error.
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+/// A group of nearby samples, summarized by their mean and count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: u64,
+}
+
+/// The centroids and the samples that haven't been merged into them yet.
+#[derive(Debug, Clone, Default)]
+struct Digest {
+    /// Merged centroids in ascending order of mean.
+    centroids: Vec<Centroid>,
+
+    /// Centroids waiting to be merged, in insertion order.
+    unmerged: Vec<Centroid>,
+}
+
+/// An approximate percentile tracker for `f64` samples with bounded memory.
+///
+/// The API mirrors [`PercentileTracker`](crate::PercentileTracker): insert samples, and
+/// query the configured percentile at any time.
+///
+/// ```
+/// use percentiletracker::TDigest;
+///
+/// let mut digest = TDigest::new(99);
+/// for i in 0..100_000 {
+///     digest.insert(i as f64);
+/// }
+/// let p99 = digest.get_percentile();
+/// assert!((p99 - 99_000.0).abs() < 100.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    /// Centroids, merged lazily so queries can take `&self`.
+    digest: RefCell<Digest>,
+
+    /// Controls the number of centroids, and therefore accuracy and memory.
+    compression: f64,
+
+    /// The percentile to track (1-99).
+    percentile: usize,
+
+    /// Total number of samples, including unmerged ones.
+    count: u64,
+
+    /// Smallest sample seen.
+    min: f64,
+
+    /// Largest sample seen.
+    max: f64,
+}
+
+impl TDigest {
+    /// Creates an empty digest tracking the given percentile with the default compression.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive.
+    pub fn new(percentile: usize) -> Self {
+        Self::with_compression(percentile, DEFAULT_COMPRESSION)
+    }
+
+    /// Creates an empty digest with a custom compression.
+    ///
+    /// Higher compression keeps more centroids, improving accuracy at the cost of memory.
+    ///
+    /// # Panics
+    /// Panics if the percentile is not between 1 and 99 inclusive, or if the compression is
+    /// not a finite number of at least 10.
+    pub fn with_compression(percentile: usize, compression: f64) -> Self {
+        assert!(
+            (1..=99).contains(&percentile),
+            "Percentile must be between 1 and 99 inclusive, got {}",
+            percentile
+        );
+        assert!(
+            compression.is_finite() && compression >= 10.0,
+            "Compression must be at least 10, got {}",
+            compression
+        );
+        TDigest {
+            digest: RefCell::new(Digest::default()),
+            compression,
+            percentile,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Returns the tracked percentile.
+    pub fn percentile(&self) -> usize {
+        self.percentile
+    }
+
+    /// Returns the compression the digest was created with.
+    pub fn compression(&self) -> f64 {
+        self.compression
+    }
+
+    /// Returns the number of samples inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if no samples have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of centroids after merging any buffered samples.
+    pub fn centroid_count(&self) -> usize {
+        self.flush();
+        self.digest.borrow().centroids.len()
+    }
+
+    /// Inserts a sample. NaN samples are ignored, since they have no place in the order.
+    pub fn insert(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.add_centroid(Centroid {
+            mean: value,
+            weight: 1,
+        });
+    }
+
+    /// Merges every sample summarized by another digest into this one.
+    ///
+    /// The digests may use different percentiles and compressions. This digest keeps its own.
+    pub fn merge(&mut self, other: &TDigest) {
+        other.flush();
+        for centroid in &other.digest.borrow().centroids {
+            self.add_centroid(*centroid);
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Returns the estimated value at the tracked percentile.
+    ///
+    /// # Panics
+    /// Panics if the digest is empty.
+    pub fn get_percentile(&self) -> f64 {
+        self.value_at_percentile(self.percentile as f64)
+            .expect("Cannot query the percentile of an empty digest")
+    }
+
+    /// Returns the estimated value at an arbitrary percentile (0-100), or `None` if empty.
+    ///
+    /// Values are interpolated between centroid means, and between the extreme centroids and
+    /// the exact minimum and maximum, so the 0th and 100th percentiles are exact.
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        self.flush();
+        let digest = self.digest.borrow();
+        let centroids = &digest.centroids;
+        let total = self.count as f64;
+        let index = (percentile / 100.0).clamp(0.0, 1.0) * total;
+
+        let first = centroids[0];
+        let first_center = first.weight as f64 / 2.0;
+        if index <= first_center {
+            return Some(interpolate(self.min, first.mean, index / first_center));
+        }
+
+        let mut cumulative = 0.0;
+        for pair in centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_center = cumulative + left.weight as f64 / 2.0;
+            let right_center = cumulative + left.weight as f64 + right.weight as f64 / 2.0;
+            if index <= right_center {
+                let fraction = (index - left_center) / (right_center - left_center);
+                return Some(interpolate(left.mean, right.mean, fraction));
+            }
+            cumulative += left.weight as f64;
+        }
+
+        let last = centroids[centroids.len() - 1];
+        let last_half = last.weight as f64 / 2.0;
+        let fraction = (index - (total - last_half)) / last_half;
+        Some(interpolate(last.mean, self.max, fraction))
+    }
+
+    /// Serializes the digest so it can be shipped to another host and merged there.
+    ///
+    /// The encoding is little-endian: the compression, percentile, count, min and max, the
+    /// number of centroids, then each centroid's mean and weight.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.flush();
+        let digest = self.digest.borrow();
+        let mut bytes = Vec::with_capacity(48 + digest.centroids.len() * 16);
+        bytes.extend_from_slice(&self.compression.to_le_bytes());
+        bytes.extend_from_slice(&(self.percentile as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.count.to_le_bytes());
+        bytes.extend_from_slice(&self.min.to_le_bytes());
+        bytes.extend_from_slice(&self.max.to_le_bytes());
+        bytes.extend_from_slice(&(digest.centroids.len() as u64).to_le_bytes());
+        for centroid in &digest.centroids {
+            bytes.extend_from_slice(&centroid.mean.to_le_bytes());
+            bytes.extend_from_slice(&centroid.weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Restores a digest serialized with [`to_bytes`](Self::to_bytes).
+    ///
+    /// Returns `None` if the bytes are truncated or describe an invalid digest.
+    pub fn from_bytes(bytes: &[u8]) -> Option<TDigest> {
+        let mut words = bytes.chunks(8).map(|chunk| <[u8; 8]>::try_from(chunk).ok());
+        let mut next = || words.next().flatten();
+
+        let compression = f64::from_le_bytes(next()?);
+        let percentile = usize::try_from(u64::from_le_bytes(next()?)).ok()?;
+        let count = u64::from_le_bytes(next()?);
+        let min = f64::from_le_bytes(next()?);
+        let max = f64::from_le_bytes(next()?);
+        let len = u64::from_le_bytes(next()?);
+        if !(1..=99).contains(&percentile) || !compression.is_finite() || compression < 10.0 {
+            return None;
+        }
+
+        let mut centroids = Vec::new();
+        for _ in 0..len {
+            centroids.push(Centroid {
+                mean: f64::from_le_bytes(next()?),
+                weight: u64::from_le_bytes(next()?),
+            });
+        }
+        let total = centroids
+            .iter()
+            .try_fold(0u64, |total, centroid| total.checked_add(centroid.weight));
+        if next().is_some() || total != Some(count) {
+            return None;
+        }
+
+        Some(TDigest {
+            digest: RefCell::new(Digest {
+                centroids,
+                unmerged: Vec::new(),
+            }),
+            compression,
+            percentile,
+            count,
+            min,
+            max,
+        })
+    }
+
+    /// Buffers a centroid, merging the buffer once it has grown large enough.
+    fn add_centroid(&mut self, centroid: Centroid) {
+        self.count += centroid.weight;
+        self.min = self.min.min(centroid.mean);
+        self.max = self.max.max(centroid.mean);
+        let buffer_limit = (self.compression * 5.0) as usize;
+        let digest = self.digest.get_mut();
+        digest.unmerged.push(centroid);
+        if digest.unmerged.len() >= buffer_limit {
+            self.flush();
+        }
+    }
+
+    /// Merges any buffered centroids into the sorted centroid list.
+    fn flush(&self) {
+        let mut digest = self.digest.borrow_mut();
+        if digest.unmerged.is_empty() {
+            return;
+        }
+        let mut all = std::mem::take(&mut digest.unmerged);
+        all.append(&mut digest.centroids);
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total = self.count as f64;
+        let mut merged = Vec::with_capacity(all.len().min(self.compression as usize * 2));
+        let mut current = all[0];
+        let mut weight_before = 0.0;
+        let mut q_limit = self.q_limit(0.0);
+        for centroid in all.into_iter().skip(1) {
+            let q = (weight_before + (current.weight + centroid.weight) as f64) / total;
+            if q <= q_limit {
+                let weight = current.weight + centroid.weight;
+                current.mean +=
+                    (centroid.mean - current.mean) * centroid.weight as f64 / weight as f64;
+                current.weight = weight;
+            } else {
+                weight_before += current.weight as f64;
+                merged.push(current);
+                q_limit = self.q_limit(weight_before / total);
+                current = centroid;
+            }
+        }
+        merged.push(current);
+        digest.centroids = merged;
+    }
+
+    /// Returns the largest quantile a centroid starting at quantile `q` may extend to.
+    ///
+    /// This is one unit of the `k1` scale function `k(q) = δ / 2π * asin(2q - 1)` past `q`.
+    fn q_limit(&self, q: f64) -> f64 {
+        let k = self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin() + 1.0;
+        if k >= self.compression / 4.0 {
+            return 1.0;
+        }
+        ((2.0 * PI * k / self.compression).sin() + 1.0) / 2.0
+    }
+}
+
+impl QuantileEstimator<f64> for TDigest {
+    fn insert(&mut self, value: f64) {
+        TDigest::insert(self, value);
+    }
+
+    fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Estimates the value at a rank by interpolating at the middle of that rank's position.
+    fn value_at_rank(&self, rank: usize) -> Option<f64> {
+        if rank as u64 >= self.count {
+            return None;
+        }
+        self.value_at_percentile((rank as f64 + 0.5) * 100.0 / self.count as f64)
+    }
+
+    fn value_at_percentile(&self, percentile: f64) -> Option<f64> {
+        TDigest::value_at_percentile(self, percentile)
+    }
+
+    /// Digests with any compression can be merged, so this never fails.
+    fn merge(&mut self, other: &Self) -> Result<(), MergeError> {
+        TDigest::merge(self, other);
+        Ok(())
+    }
+
+    fn error_bound(&self) -> ErrorBound {
+        // Rank error shrinks towards the tails, so this is the worst case, near the median
+        ErrorBound::Rank(1.0 / self.compression)
+    }
+}
+
+/// Linearly interpolates between `a` and `b`, with the fraction clamped to `0..=1`.
+fn interpolate(a: f64, b: f64, fraction: f64) -> f64 {
+    a + (b - a) * fraction.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that a quantile estimate's rank is within `tolerance` of the requested rank.
+    fn assert_rank_close(sorted: &[f64], estimate: f64, percentile: f64, tolerance: f64) {
+        let rank = sorted.partition_point(|&value| value < estimate) as f64 / sorted.len() as f64;
+        assert!(
+            (rank - percentile / 100.0).abs() <= tolerance,
+            "p{} estimate {} has rank {}",
+            percentile,
+            estimate,
+            rank
+        );
+    }
+
+    fn shuffled(count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|i| ((i as u64 * 2_654_435_761) % count as u64) as f64)
+            .collect()
+    }
+
+    #[test]
+    fn test_tdigest_accuracy() {
+        let values = shuffled(100_003);
+        let mut digest = TDigest::new(99);
+        for &value in &values {
+            digest.insert(value);
+        }
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        assert_eq!(digest.count(), values.len() as u64);
+        assert!(digest.centroid_count() <= 200);
+        for percentile in [1.0, 10.0, 50.0, 90.0, 99.0, 99.9] {
+            let estimate = digest.value_at_percentile(percentile).unwrap();
+            assert_rank_close(&sorted, estimate, percentile, 0.005);
+        }
+        assert_eq!(digest.value_at_percentile(0.0), Some(0.0));
+        assert_eq!(digest.value_at_percentile(100.0), Some(100_002.0));
+    }
+
+    #[test]
+    fn test_tdigest_small_is_exact() {
+        let mut digest = TDigest::new(50);
+        assert_eq!(digest.value_at_percentile(50.0), None);
+        digest.insert(7.0);
+        assert_eq!(digest.get_percentile(), 7.0);
+        digest.insert(f64::NAN);
+        assert_eq!(digest.count(), 1);
+    }
+
+    #[test]
+    fn test_tdigest_merge_across_hosts() {
+        let values = shuffled(50_000);
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        let mut merged = TDigest::new(90);
+        for shard in values.chunks(10_000) {
+            let mut digest = TDigest::with_compression(50, 200.0);
+            for &value in shard {
+                digest.insert(value);
+            }
+            let restored = TDigest::from_bytes(&digest.to_bytes()).unwrap();
+            assert_eq!(restored.count(), digest.count());
+            merged.merge(&restored);
+        }
+
+        assert_eq!(merged.count(), 50_000);
+        assert_eq!(merged.percentile(), 90);
+        for percentile in [50.0, 90.0, 99.0] {
+            let estimate = merged.value_at_percentile(percentile).unwrap();
+            assert_rank_close(&sorted, estimate, percentile, 0.01);
+        }
+        assert!(TDigest::from_bytes(&merged.to_bytes()[..20]).is_none());
+    }
+
+    #[test]
+    fn test_tdigest_from_bytes_rejects_overflowing_weights() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&100.0f64.to_le_bytes());
+        bytes.extend_from_slice(&50u64.to_le_bytes());
+        // The count two maximal weights would wrap around to
+        bytes.extend_from_slice(&u64::MAX.wrapping_mul(2).to_le_bytes());
+        bytes.extend_from_slice(&1.0f64.to_le_bytes());
+        bytes.extend_from_slice(&2.0f64.to_le_bytes());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        for mean in [1.0f64, 2.0] {
+            bytes.extend_from_slice(&mean.to_le_bytes());
+            bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        }
+        assert!(TDigest::from_bytes(&bytes).is_none());
+    }
+}
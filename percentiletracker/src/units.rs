@@ -0,0 +1,187 @@
+use std::fmt;
+
+use crate::{Numeric, PercentileTracker};
+
+/// The unit of the samples stored in a tracker.
+///
+/// Knowing the unit lets reporting code scale values to something readable, e.g. a p99 of
+/// `1_500_000` nanoseconds is displayed as `1.50 ms`, and prevents mixing up values that
+/// were recorded in different units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Unit {
+    /// Plain numbers with no unit.
+    #[default]
+    None,
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+    Bytes,
+}
+
+/// The physical quantity a [`Unit`] measures. Only units of the same dimension can be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    Dimensionless,
+    Time,
+    Information,
+}
+
+/// Display scales for time, as (nanoseconds per unit, suffix), from largest to smallest.
+const TIME_SCALES: [(f64, &str); 4] = [(1e9, "s"), (1e6, "ms"), (1e3, "µs"), (1.0, "ns")];
+
+/// Display scales for information, as (bytes per unit, suffix), from largest to smallest.
+const BYTE_SCALES: [(f64, &str); 5] = [
+    (1099511627776.0, "TiB"),
+    (1073741824.0, "GiB"),
+    (1048576.0, "MiB"),
+    (1024.0, "KiB"),
+    (1.0, "B"),
+];
+
+impl Unit {
+    /// Returns the dimension this unit measures.
+    pub fn dimension(self) -> Dimension {
+        match self {
+            Unit::None => Dimension::Dimensionless,
+            Unit::Nanoseconds | Unit::Microseconds | Unit::Milliseconds | Unit::Seconds => {
+                Dimension::Time
+            }
+            Unit::Bytes => Dimension::Information,
+        }
+    }
+
+    /// Returns the short suffix for this unit, e.g. `ms`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::None => "",
+            Unit::Nanoseconds => "ns",
+            Unit::Microseconds => "µs",
+            Unit::Milliseconds => "ms",
+            Unit::Seconds => "s",
+            Unit::Bytes => "B",
+        }
+    }
+
+    /// Returns how many of the dimension's base unit (nanoseconds or bytes) one of this unit is.
+    fn base_factor(self) -> f64 {
+        match self {
+            Unit::None | Unit::Nanoseconds | Unit::Bytes => 1.0,
+            Unit::Microseconds => 1e3,
+            Unit::Milliseconds => 1e6,
+            Unit::Seconds => 1e9,
+        }
+    }
+
+    /// Converts a value in this unit into another unit of the same dimension.
+    ///
+    /// Returns `None` if the units measure different dimensions.
+    pub fn convert(self, value: f64, to: Unit) -> Option<f64> {
+        if self.dimension() != to.dimension() {
+            return None;
+        }
+        Some(value * self.base_factor() / to.base_factor())
+    }
+
+    /// Formats a value in this unit, scaled to the most readable unit of the same dimension.
+    pub fn format(self, value: f64) -> String {
+        let scales: &[(f64, &str)] = match self.dimension() {
+            Dimension::Dimensionless => return format!("{}", value),
+            Dimension::Time => &TIME_SCALES,
+            Dimension::Information => &BYTE_SCALES,
+        };
+        let base = value * self.base_factor();
+        let (factor, suffix) = scales
+            .iter()
+            .find(|(factor, _)| base.abs() >= *factor)
+            .unwrap_or(&scales[scales.len() - 1]);
+        if *factor == 1.0 {
+            format!("{} {}", base, suffix)
+        } else {
+            format!("{:.2} {}", base / factor, suffix)
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.suffix())
+    }
+}
+
+impl<T> PercentileTracker<T>
+where
+    T: Clone + Ord,
+{
+    /// Returns the unit of the samples stored in this tracker.
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// Formats a value from this tracker using its unit.
+    pub fn format_value(&self, value: T) -> String
+    where
+        T: Numeric,
+    {
+        self.unit.format(value.to_f64())
+    }
+}
+
+/// Displays the tracked percentile and the number of values, e.g. `p99=1.50 ms (n=1000)`.
+impl<T> fmt::Display for PercentileTracker<T>
+where
+    T: Numeric,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count() == 0 {
+            write!(f, "p{}=- (n=0)", self.percentile)
+        } else {
+            write!(
+                f,
+                "p{}={} (n={})",
+                self.percentile,
+                self.format_value(self.get_percentile()),
+                self.count()
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_format() {
+        assert_eq!(Unit::Nanoseconds.format(1_500_000.0), "1.50 ms");
+        assert_eq!(Unit::Nanoseconds.format(999.0), "999 ns");
+        assert_eq!(Unit::Milliseconds.format(2500.0), "2.50 s");
+        assert_eq!(Unit::Microseconds.format(0.0), "0 ns");
+        assert_eq!(Unit::Bytes.format(3.0 * 1024.0 * 1024.0), "3.00 MiB");
+        assert_eq!(Unit::None.format(42.0), "42");
+    }
+
+    #[test]
+    fn test_unit_convert() {
+        assert_eq!(
+            Unit::Milliseconds.convert(1.5, Unit::Microseconds),
+            Some(1500.0)
+        );
+        assert_eq!(Unit::Seconds.convert(1.0, Unit::Bytes), None);
+    }
+
+    #[test]
+    fn test_tracker_display() {
+        let mut tracker = PercentileTracker::<u64>::builder()
+            .percentile(90)
+            .unit(Unit::Microseconds)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.to_string(), "p90=- (n=0)");
+        for value in 1..=10 {
+            tracker.insert(value * 1000);
+        }
+        assert_eq!(tracker.unit(), Unit::Microseconds);
+        assert_eq!(tracker.to_string(), "p90=10.00 ms (n=10)");
+    }
+}
@@ -0,0 +1,274 @@
+//! Checking every structural invariant of a tracker.
+
+use std::fmt;
+
+use crate::PercentileTracker;
+
+/// A broken invariant found by [`PercentileTracker::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A bucket holds no values. Emptied buckets are removed.
+    EmptyBucket { bucket: usize },
+
+    /// A bucket's cached minimum or maximum position is out of bounds or not an extreme.
+    StaleExtremes { bucket: usize },
+
+    /// A bucket is flagged as sorted but its values aren't in order.
+    UnsortedBucket { bucket: usize },
+
+    /// A bucket holds a value larger than the minimum of the next bucket.
+    OverlappingBuckets { bucket: usize },
+
+    /// The stored count doesn't match the number of values in the buckets.
+    CountMismatch { recorded: usize, actual: usize },
+
+    /// The percentile cursor points past the last bucket.
+    CursorOutOfBounds { bucket: usize, buckets: usize },
+
+    /// The cursor's offset doesn't match the number of values before its bucket.
+    CursorOffsetMismatch { recorded: usize, actual: usize },
+
+    /// The flat index of bucket minimums disagrees with a bucket.
+    StaleMinIndex { bucket: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::EmptyBucket { bucket } => write!(f, "Bucket {} is empty", bucket),
+            InvariantViolation::StaleExtremes { bucket } => {
+                write!(f, "Bucket {} caches the wrong minimum or maximum", bucket)
+            }
+            InvariantViolation::UnsortedBucket { bucket } => {
+                write!(f, "Bucket {} is flagged as sorted but isn't", bucket)
+            }
+            InvariantViolation::OverlappingBuckets { bucket } => write!(
+                f,
+                "Bucket {} holds a value above the minimum of the next bucket",
+                bucket
+            ),
+            InvariantViolation::CountMismatch { recorded, actual } => write!(
+                f,
+                "The tracker records {} values but its buckets hold {}",
+                recorded, actual
+            ),
+            InvariantViolation::CursorOutOfBounds { bucket, buckets } => {
+                write!(f, "The cursor points at bucket {} of {}", bucket, buckets)
+            }
+            InvariantViolation::CursorOffsetMismatch { recorded, actual } => write!(
+                f,
+                "The cursor records {} values before its bucket but there are {}",
+                recorded, actual
+            ),
+            InvariantViolation::StaleMinIndex { bucket } => {
+                write!(f, "The minimum index disagrees with bucket {}", bucket)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+impl<T> PercentileTracker<T>
+where
+    T: Ord,
+{
+    /// Checks every structural invariant of the tracker, returning the first one broken.
+    ///
+    /// This checks that buckets are non-empty, cache their true extremes, are really sorted
+    /// when flagged as sorted, and partition the values in ascending order; that the count
+    /// and the percentile cursor agree with the buckets; and that the minimum index, if
+    /// enabled, mirrors the buckets. It doesn't rebalance first, so it can be called after
+    /// any operation, e.g. after every step of a fuzz test. It reads every value once.
+    ///
+    /// ```
+    /// use percentiletracker::PercentileTracker;
+    ///
+    /// let mut tracker = PercentileTracker::<u64>::new(99);
+    /// for value in (0..1_000).rev() {
+    ///     tracker.insert(value);
+    ///     if value % 100 == 0 {
+    ///         tracker.get_percentile();
+    ///     }
+    /// }
+    /// assert_eq!(tracker.verify(), Ok(()));
+    /// ```
+    pub fn verify(&self) -> Result<(), InvariantViolation> {
+        let buckets = self.buckets.borrow();
+        // Every other check reads the extremes of buckets, which empty buckets don't have
+        if let Some(bucket) = buckets.iter().position(|bucket| bucket.values.is_empty()) {
+            return Err(InvariantViolation::EmptyBucket { bucket });
+        }
+        for (bucket_idx, bucket) in buckets.iter().enumerate() {
+            let values = &bucket.values;
+            let extremes_valid = bucket.min_idx < values.len()
+                && bucket.max_idx < values.len()
+                && values
+                    .iter()
+                    .all(|value| bucket.min() <= value && value <= bucket.max());
+            if !extremes_valid {
+                return Err(InvariantViolation::StaleExtremes { bucket: bucket_idx });
+            }
+            if bucket.sorted && !values.is_sorted() {
+                return Err(InvariantViolation::UnsortedBucket { bucket: bucket_idx });
+            }
+            if buckets
+                .get(bucket_idx + 1)
+                .is_some_and(|next| bucket.max() > next.min())
+            {
+                return Err(InvariantViolation::OverlappingBuckets { bucket: bucket_idx });
+            }
+        }
+
+        let actual: usize = buckets.iter().map(|bucket| bucket.len()).sum();
+        if actual != self.count() {
+            return Err(InvariantViolation::CountMismatch {
+                recorded: self.count(),
+                actual,
+            });
+        }
+
+        let (cursor_bucket, cursor_offset) = self.cursor();
+        if !buckets.is_empty() && cursor_bucket >= buckets.len() {
+            return Err(InvariantViolation::CursorOutOfBounds {
+                bucket: cursor_bucket,
+                buckets: buckets.len(),
+            });
+        }
+        let actual: usize = buckets
+            .iter()
+            .take(cursor_bucket)
+            .map(|bucket| bucket.len())
+            .sum();
+        if actual != cursor_offset {
+            return Err(InvariantViolation::CursorOffsetMismatch {
+                recorded: cursor_offset,
+                actual,
+            });
+        }
+
+        if let Some(index) = self.min_index.borrow().as_ref() {
+            let mins = index.mins();
+            for bucket_idx in 0..mins.len().max(buckets.len()) {
+                let matches = match (mins.get(bucket_idx), buckets.get(bucket_idx)) {
+                    (Some(min), Some(bucket)) => min == bucket.min(),
+                    _ => false,
+                };
+                if !matches {
+                    return Err(InvariantViolation::StaleMinIndex { bucket: bucket_idx });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::Bucket;
+
+    #[test]
+    fn test_verify_random_operations() {
+        let mut rng = SplitMix64(5);
+        let mut tracker = PercentileTracker::builder()
+            .percentile(75)
+            .max_bucket_size(8)
+            .min_index()
+            .undo_journal(64)
+            .build()
+            .unwrap();
+        assert_eq!(tracker.verify(), Ok(()));
+        for step in 0..5_000 {
+            match rng.below(10) {
+                0 => {
+                    tracker.get_percentile();
+                }
+                1 => {
+                    tracker.undo(1 + rng.below(3) as usize);
+                }
+                _ => tracker.insert(rng.below(500)),
+            }
+            if let Err(violation) = tracker.verify() {
+                panic!("Step {}: {}", step, violation);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() {
+        let mut tracker = PercentileTracker::builder()
+            .max_bucket_size(4)
+            .min_index()
+            .build()
+            .unwrap();
+        for value in 0..20u32 {
+            tracker.insert(value);
+        }
+        tracker.get_percentile();
+        assert_eq!(tracker.verify(), Ok(()));
+
+        tracker.buckets.get_mut().swap(0, 1);
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::OverlappingBuckets { bucket: 0 })
+        );
+        tracker.buckets.get_mut().swap(0, 1);
+
+        tracker.buckets.get_mut()[0].values.reverse();
+        tracker.buckets.get_mut()[0].sorted = true;
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::StaleExtremes { bucket: 0 })
+        );
+        tracker.buckets.get_mut()[0].locate_extremes();
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::UnsortedBucket { bucket: 0 })
+        );
+        tracker.buckets.get_mut()[0].sorted = false;
+
+        tracker.total_count += 1;
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::CountMismatch {
+                recorded: 21,
+                actual: 20
+            })
+        );
+        tracker.total_count -= 1;
+
+        let (cursor_bucket, cursor_offset) = tracker.cursor();
+        tracker.set_cursor(cursor_bucket, cursor_offset + 1);
+        assert!(matches!(
+            tracker.verify(),
+            Err(InvariantViolation::CursorOffsetMismatch { .. })
+        ));
+        tracker.set_cursor(cursor_bucket, cursor_offset);
+
+        tracker.buckets.get_mut().push(Bucket {
+            values: Vec::new(),
+            min_idx: 0,
+            max_idx: 0,
+            sorted: true,
+        });
+        let last = tracker.buckets.borrow().len() - 1;
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::EmptyBucket { bucket: last })
+        );
+        tracker.buckets.get_mut().pop();
+
+        tracker
+            .min_index
+            .get_mut()
+            .as_mut()
+            .unwrap()
+            .update(1, &100);
+        assert_eq!(
+            tracker.verify(),
+            Err(InvariantViolation::StaleMinIndex { bucket: 1 })
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! JavaScript bindings, built with `wasm-bindgen`.
+//!
+//! [`WasmTracker`] is exported to JavaScript as `PercentileTracker` and tracks `number`s, so
+//! a browser dashboard can compute percentiles with the same implementation as the backend:
+//!
+//! ```js
+//! const tracker = new PercentileTracker(99);
+//! tracker.insertMany(new Float64Array(latencies));
+//! const p99 = tracker.getPercentile();
+//! const saved = tracker.snapshot();
+//! const restored = PercentileTracker.restore(99, saved);
+//! ```
+
+use std::cmp::Ordering;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{rank_for_percentile, PercentileTracker};
+
+/// An `f64` ordered with `total_cmp`. NaN is rejected before values are wrapped.
+#[derive(Debug, Clone, Copy)]
+struct Number(f64);
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Number {}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A percentile tracker over JavaScript numbers.
+#[wasm_bindgen(js_name = PercentileTracker)]
+pub struct WasmTracker {
+    tracker: PercentileTracker<Number>,
+}
+
+#[wasm_bindgen(js_class = PercentileTracker)]
+impl WasmTracker {
+    /// Creates a tracker for a percentile between 1 and 99, throwing if it is out of range.
+    #[wasm_bindgen(constructor)]
+    pub fn new(percentile: usize) -> Result<WasmTracker, JsError> {
+        let tracker = PercentileTracker::builder()
+            .percentile(percentile)
+            .build()
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(WasmTracker { tracker })
+    }
+
+    /// Recreates a tracker from the values returned by [`snapshot`](Self::snapshot).
+    pub fn restore(percentile: usize, values: &[f64]) -> Result<WasmTracker, JsError> {
+        let mut tracker = WasmTracker::new(percentile)?;
+        tracker.insert_many(values)?;
+        Ok(tracker)
+    }
+
+    /// Inserts a value, throwing if it is `NaN`.
+    pub fn insert(&mut self, value: f64) -> Result<(), JsError> {
+        if value.is_nan() {
+            return Err(JsError::new("Cannot track NaN"));
+        }
+        self.tracker.insert(Number(value));
+        Ok(())
+    }
+
+    /// Inserts every value in an array, throwing before inserting any if one is `NaN`.
+    #[wasm_bindgen(js_name = insertMany)]
+    pub fn insert_many(&mut self, values: &[f64]) -> Result<(), JsError> {
+        if values.iter().any(|value| value.is_nan()) {
+            return Err(JsError::new("Cannot track NaN"));
+        }
+        for &value in values {
+            self.tracker.insert(Number(value));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of values in the tracker.
+    pub fn count(&self) -> usize {
+        self.tracker.count()
+    }
+
+    /// Returns the percentile the tracker was created for.
+    pub fn percentile(&self) -> usize {
+        self.tracker.percentile
+    }
+
+    /// Returns the value at the tracked percentile, or `undefined` if the tracker is empty.
+    #[wasm_bindgen(js_name = getPercentile)]
+    pub fn get_percentile(&self) -> Option<f64> {
+        self.value_at_percentile(self.tracker.percentile as f64)
+    }
+
+    /// Returns the value at an arbitrary percentile (0-100), or `undefined` if the tracker
+    /// is empty.
+    #[wasm_bindgen(js_name = valueAtPercentile)]
+    pub fn value_at_percentile(&self, percentile: f64) -> Option<f64> {
+        let count = self.tracker.count();
+        if count == 0 {
+            return None;
+        }
+        self.tracker
+            .value_at_rank(rank_for_percentile(percentile, count))
+            .map(|value| value.0)
+    }
+
+    /// Returns every value in ascending order as a `Float64Array`, which can be stored and
+    /// passed to [`restore`](Self::restore) later.
+    pub fn snapshot(&self) -> Vec<f64> {
+        self.tracker
+            .snapshot()
+            .values()
+            .iter()
+            .map(|value| value.0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_tracker() {
+        let mut tracker = WasmTracker::new(90).unwrap();
+        assert_eq!(tracker.get_percentile(), None);
+        tracker.insert(-0.5).unwrap();
+        tracker
+            .insert_many(&(1..100).map(f64::from).collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(tracker.count(), 100);
+        assert_eq!(tracker.get_percentile(), Some(90.0));
+        assert_eq!(tracker.value_at_percentile(0.0), Some(-0.5));
+        assert_eq!(tracker.value_at_percentile(100.0), Some(99.0));
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.len(), 100);
+        assert!(snapshot.windows(2).all(|pair| pair[0] <= pair[1]));
+        let restored = WasmTracker::restore(tracker.percentile(), &snapshot).unwrap();
+        assert_eq!(restored.get_percentile(), tracker.get_percentile());
+        assert_eq!(restored.snapshot(), snapshot);
+    }
+}